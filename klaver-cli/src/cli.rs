@@ -1,42 +1,153 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-use klaver_core::throw_if;
-use klaver_modules::{Global, global_info};
+use klaver_core::{error::BoxError, throw_if};
+use klaver_modules::{
+    Environ, Global, global_info,
+    loaders::{Bundle, BytecodeLoader, FetchedModule, Fetcher, LockFile, Prefetcher, UrlLoader},
+    resolvers::ImportMapResolver,
+};
 use klaver_vm::RuntimeError;
-use klaver_wintertc::{TokioBackend, WinterTcInstance, fs::FileSystemEntry};
-use rquickjs::CatchResultExt;
+use klaver_wintertc::{
+    Permissions, TokioBackend, WinterTcInstance, fs::FileSystemEntry, permissions::Grant,
+};
+use rquickjs::{CatchResultExt, Ctx, prelude::Func};
 
 use crate::run;
 
-#[derive(clap::Parser)]
+#[derive(clap::Parser, Default)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
-    path: Option<String>,
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+    pub(crate) path: Option<String>,
     #[clap(short, long, default_value_t = false)]
-    exec: bool,
+    pub(crate) exec: bool,
     #[clap(short, long, default_value_t = false)]
-    types: bool,
+    pub(crate) types: bool,
     #[clap(short, long, default_value_t = false)]
-    compile: bool,
+    pub(crate) compile: bool,
+    /// Compile the module graph to a QuickJS bytecode bundle. Used with --compile
+    #[clap(short, long, default_value_t = false)]
+    pub(crate) bytecode: bool,
+    /// Where to write the bytecode bundle
+    #[clap(short, long)]
+    pub(crate) output: Option<String>,
+    /// Rewrite imports with an import map, like importmap.json
+    #[clap(long)]
+    pub(crate) import_map: Option<String>,
+    /// Only import url modules from the local cache
+    #[clap(long, default_value_t = false)]
+    pub(crate) offline: bool,
+    /// The lock file with the hashes of url modules. Defaults to klaver.lock next to the entry module
+    #[clap(long)]
+    pub(crate) lock: Option<PathBuf>,
+    /// Deny scripts access to the file system, network, environment, workers and timers,
+    /// unless allowed with the --allow-* flags
+    #[clap(long, default_value_t = false)]
+    pub(crate) sandbox: bool,
+    /// Allow reading these paths, like --allow-read=./data, or every path when no paths are given. Used with --sandbox
+    #[clap(long, num_args = 0.., value_delimiter = ',', require_equals = true)]
+    pub(crate) allow_read: Option<Vec<PathBuf>>,
+    /// Allow writing these paths, or every path when no paths are given. Used with --sandbox
+    #[clap(long, num_args = 0.., value_delimiter = ',', require_equals = true)]
+    pub(crate) allow_write: Option<Vec<PathBuf>>,
+    /// Allow network access to these hosts, or every host when no hosts are given. Used with --sandbox
+    #[clap(long, num_args = 0.., value_delimiter = ',', require_equals = true)]
+    pub(crate) allow_net: Option<Vec<String>>,
+    /// Allow reading these environment variables, or all of them when no names are given. Used with --sandbox
+    #[clap(long, num_args = 0.., value_delimiter = ',', require_equals = true)]
+    pub(crate) allow_env: Option<Vec<String>>,
+    /// Allow spawning workers. Used with --sandbox
+    #[clap(long, default_value_t = false)]
+    pub(crate) allow_worker: bool,
+    /// Allow timers, like setTimeout. Used with --sandbox
+    #[clap(long, default_value_t = false)]
+    pub(crate) allow_timers: bool,
+}
+
+#[derive(clap::Subcommand)]
+pub(crate) enum Command {
+    /// Build a standalone executable, with the module graph embedded in a copy of klaver
+    Build {
+        path: String,
+        /// Where to write the executable. Defaults to the entry module's name
+        #[clap(short, long)]
+        output: Option<String>,
+    },
 }
 
 impl Cli {
     pub async fn run() -> color_eyre::Result<()> {
-        let cli = Cli::parse();
+        // Executables built with `klaver build` run their embedded app, and leave the arguments to it.
+        // Safety: the bundle was embedded by `klaver build`, and is part of the executable we trust to run
+        let embedded = unsafe { Bundle::from_executable(std::env::current_exe()?)? };
+
+        let cli = match embedded {
+            Some(_) => Cli::default(),
+            None => Cli::parse(),
+        };
+
+        let permissions = cli.permissions();
 
-        let builder = klaver::Builder::new(TokioBackend)
+        let fetcher = ModuleFetcher {
+            permissions: permissions.clone(),
+        };
+        let url_loader = UrlLoader::new(klaver_dir().join("remote"))
+            .lock_file(LockFile::open(cli.lock_path())?)
+            .offline(cli.offline)
+            .fetcher(fetcher.clone());
+        let url_modules = UrlModules {
+            prefetcher: url_loader.prefetcher(),
+            fetcher,
+            offline: cli.offline,
+        };
+
+        let mut builder = klaver::Builder::new(TokioBackend)
             .search_path(".")
+            // Modules are compiled again after prefetching url modules
+            .cache(true)
             .global::<CliGlobal>()
             .module::<klaver_vm::VmModule>()
             .module::<klaver_image::Module>()
+            .module::<klaver_router::Module>()
             // .module::<klaver_dom::Module>()
-            .module::<klaver_runtime::TaskModule>();
+            .module::<klaver_runtime::TaskModule>()
+            .url_modules(url_loader);
+
+        builder = builder.permissions(permissions.clone());
+
+        if let Some(import_map) = &cli.import_map {
+            builder = builder.import_map(ImportMapResolver::from_file(import_map)?);
+        }
+
+        // Precompiled bundles are run from their entry point
+        let mut path = cli.entry().map(|path| path.to_string());
+        let bundle = match embedded {
+            Some(bundle) => Some(BytecodeLoader::new(bundle)),
+            None => match path.as_deref() {
+                Some(path) if !cli.compile && cli.command.is_none() && path.ends_with(".kbc") => {
+                    // Safety: running a bundle trusts it like running an executable
+                    Some(unsafe { BytecodeLoader::open(path)? })
+                }
+                _ => None,
+            },
+        };
+
+        if let Some(loader) = bundle {
+            path = Some(loader.bundle().entry().to_string());
+            builder = builder.bundle(loader);
+        }
 
         let vm = builder.build().await?;
 
         vm.async_with(async move |ctx| {
-            //
+            // The working directory is only exposed when the permissions allow it
+            let cwd = Path::new(".");
+            if permissions.check_write(cwd).is_err() && permissions.check_read(cwd).is_err() {
+                return Ok(());
+            }
+
             let instance = WinterTcInstance::from_ctx(&ctx).catch(&ctx)?;
 
             let path = instance
@@ -48,7 +159,13 @@ impl Cli {
                 .map_err(|err| RuntimeError::Custom(Box::new(err)))?;
 
             ctx.globals()
-                .set("Fs", FileSystemEntry { path })
+                .set(
+                    "Fs",
+                    FileSystemEntry {
+                        path,
+                        root: cwd.to_path_buf(),
+                    },
+                )
                 .catch(&ctx)?;
             Ok(())
         })
@@ -56,14 +173,165 @@ impl Cli {
 
         klaver_runtime::set_promise_hook(vm.runtime()).await;
 
-        run::run(
-            vm,
-            cli.path.as_ref().map(|m| &**m),
-            cli.exec,
-            cli.types,
-            cli.compile,
-        )
-        .await?;
+        run::run(vm, &cli, path.as_deref(), &url_modules).await?;
+
+        Ok(())
+    }
+
+    fn entry(&self) -> Option<&str> {
+        match &self.command {
+            Some(Command::Build { path, .. }) => Some(path),
+            None => self.path.as_deref(),
+        }
+    }
+
+    /// The lock file given with --lock, or klaver.lock next to the entry module
+    fn lock_path(&self) -> PathBuf {
+        if let Some(lock) = &self.lock {
+            return lock.clone();
+        }
+
+        let dir = self
+            .entry()
+            .filter(|entry| !self.exec && !is_remote(entry))
+            .and_then(|entry| Path::new(entry).parent())
+            .unwrap_or(Path::new("."));
+
+        dir.join("klaver.lock")
+    }
+
+    fn permissions(&self) -> Permissions {
+        if !self.sandbox {
+            return Permissions::all();
+        }
+
+        let mut permissions = Permissions::none()
+            .allow_workers(self.allow_worker)
+            .allow_timers(self.allow_timers);
+
+        permissions.read = grant(&self.allow_read);
+        permissions.write = grant(&self.allow_write);
+        permissions.net = grant(&self.allow_net);
+        permissions.env = grant(&self.allow_env);
+
+        permissions
+    }
+}
+
+/// A flag without values allows everything
+fn grant<T: Clone>(flag: &Option<Vec<T>>) -> Grant<T> {
+    match flag {
+        Some(entries) if entries.is_empty() => Grant::All,
+        Some(entries) => Grant::Only(entries.clone()),
+        None => Grant::none(),
+    }
+}
+
+pub(crate) fn is_remote(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// Where klaver keeps its global state, like cached url modules
+fn klaver_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("KLAVER_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    match std::env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(".cache").join("klaver"),
+        None => std::env::temp_dir().join("klaver"),
+    }
+}
+
+/// Downloads url modules, with the same net permissions as the scripts
+#[derive(Clone)]
+struct ModuleFetcher {
+    permissions: Permissions,
+}
+
+impl ModuleFetcher {
+    fn check(permissions: &Permissions, url: &reqwest::Url) -> Result<(), BoxError> {
+        let Some(host) = url.host_str() else {
+            return Err(format!("Invalid module url: {url}").into());
+        };
+        permissions.check_net(host)?;
+        Ok(())
+    }
+
+    /// Redirects are checked against the permissions too
+    fn policy(&self) -> reqwest::redirect::Policy {
+        let permissions = self.permissions.clone();
+
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > 10 {
+                attempt.error("Too many redirects")
+            } else if let Err(err) = Self::check(&permissions, attempt.url()) {
+                attempt.error(err)
+            } else {
+                attempt.follow()
+            }
+        })
+    }
+
+    async fn fetch_async(self, url: String) -> Result<FetchedModule, BoxError> {
+        let url = reqwest::Url::parse(&url)?;
+        Self::check(&self.permissions, &url)?;
+
+        let client = reqwest::Client::builder().redirect(self.policy()).build()?;
+
+        let resp = client.get(url).send().await?.error_for_status()?;
+
+        // Imports are resolved against the url the module was served from
+        Ok(FetchedModule {
+            url: resp.url().to_string(),
+            content: resp.bytes().await?.to_vec(),
+        })
+    }
+}
+
+impl Fetcher for ModuleFetcher {
+    fn fetch(&self, url: &str) -> Result<FetchedModule, BoxError> {
+        let url = reqwest::Url::parse(url)?;
+        Self::check(&self.permissions, &url)?;
+
+        let policy = self.policy();
+
+        // The blocking client can't be used on the async runtime's thread
+        std::thread::spawn(move || -> Result<FetchedModule, BoxError> {
+            let client = reqwest::blocking::Client::builder()
+                .redirect(policy)
+                .build()?;
+
+            let resp = client.get(url).send()?.error_for_status()?;
+
+            Ok(FetchedModule {
+                url: resp.url().to_string(),
+                content: resp.bytes()?.to_vec(),
+            })
+        })
+        .join()
+        .map_err(|_| "Fetching module panicked")?
+    }
+}
+
+/// Url modules are loaded synchronously, which blocks the event loop while they download.
+/// The static imports of the entry module are downloaded asynchronously before it runs instead,
+/// so only dynamic imports of uncached modules block
+pub(crate) struct UrlModules {
+    prefetcher: Prefetcher,
+    fetcher: ModuleFetcher,
+    offline: bool,
+}
+
+impl UrlModules {
+    pub(crate) async fn prefetch(&self, env: &Environ, entry: &str) -> Result<(), RuntimeError> {
+        if self.offline {
+            return Ok(());
+        }
+
+        self.prefetcher
+            .prefetch(env, entry, |url| self.fetcher.clone().fetch_async(url))
+            .await?;
 
         Ok(())
     }
@@ -74,10 +342,27 @@ pub struct CliGlobal;
 impl Global for CliGlobal {
     fn define<'a, 'js: 'a>(
         &'a self,
-        _ctx: rquickjs::Ctx<'js>,
+        ctx: rquickjs::Ctx<'js>,
     ) -> impl Future<Output = rquickjs::Result<()>> + 'a {
-        async move { Ok(()) }
+        async move {
+            ctx.globals().set("env", Func::new(env))?;
+            Ok(())
+        }
+    }
+}
+
+/// Read an environment variable, if the permissions allow it
+fn env(ctx: Ctx<'_>, name: String) -> rquickjs::Result<Option<String>> {
+    let instance = WinterTcInstance::from_ctx(&ctx)?;
+    if let Err(err) = instance.borrow().settings().permissions().check_env(&name) {
+        return Err(err.throw(&ctx));
     }
+
+    Ok(std::env::var(&name).ok())
 }
 
-global_info!("cli" @types: "declare const Fs:FileSystem;" => CliGlobal);
+const CLI_TYPES: &str = r#"declare const Fs: FileSystem | undefined;
+declare function env(name: string): string | undefined;
+"#;
+
+global_info!("cli" @types: CLI_TYPES => CliGlobal);
@@ -1,20 +1,34 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use color_eyre::eyre::eyre;
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize};
 use klaver::Vm;
-use klaver_modules::loaders::{SwcCompiler, SwcCompilerOptions, SwcDecocators};
+use klaver_core::error::ResolutionError;
+use klaver_modules::loaders::{Bundle, SwcCompiler, SwcCompilerOptions, SwcDecocators};
+use klaver_vm::RuntimeError;
 use reedline::{DefaultPrompt, Reedline, Signal};
 use rquickjs::{CatchResultExt, Object, Value};
 
+use crate::cli::{Cli, Command, UrlModules, is_remote};
+
 pub async fn run(
     vm: Vm,
+    cli: &Cli,
     source: Option<&str>,
-    exec: bool,
-    types: bool,
-    compile: bool,
+    url_modules: &UrlModules,
 ) -> color_eyre::Result<()> {
     if let Some(source) = source {
-        if exec {
+        if let Some(Command::Build { output, .. }) = &cli.command {
+            let bundle = Bundle::compile(vm.env(), &module_path(source)).await?;
+
+            let output = match output {
+                Some(output) => PathBuf::from(output),
+                None => Path::new(source).with_extension(std::env::consts::EXE_EXTENSION),
+            };
+
+            bundle.write_executable(std::env::current_exe()?, &output)?;
+
+            println!("Built {}", output.display());
+        } else if cli.exec {
             vm.async_with(async |ctx| {
                 ctx.eval_promise(source)
                     .catch(&ctx)?
@@ -24,7 +38,22 @@ pub async fn run(
                 Ok(())
             })
             .await?;
-        } else if compile {
+        } else if cli.compile && cli.bytecode {
+            let bundle = Bundle::compile(vm.env(), &module_path(source)).await?;
+
+            let output = match &cli.output {
+                Some(output) => PathBuf::from(output),
+                None => Path::new(source).with_extension("kbc"),
+            };
+
+            bundle.save(&output)?;
+
+            println!(
+                "Compiled {} modules to {}",
+                bundle.modules().count(),
+                output.display()
+            );
+        } else if cli.compile {
             let compiler = SwcCompiler::new_with(SwcCompilerOptions {
                 decorators: SwcDecocators::Legacy,
                 async_context: false,
@@ -35,15 +64,20 @@ pub async fn run(
                 .compile(Path::new(source))
                 .map_err(|err| eyre!("{err}"))?;
             println!("{}", String::from_utf8(ret.code)?);
-        } else if types {
+        } else if cli.types {
             vm.env().typings().files().write_to(source, true).await?;
         } else {
-            let path = if !(source.starts_with("../") && !source.starts_with("./")) {
-                format!("./{}", source)
-            } else {
-                source.to_string()
-            };
-            vm.run_module(&path).await?;
+            let module = module_path(source);
+            url_modules.prefetch(vm.env(), &module).await?;
+
+            match vm.run_module(&module).await {
+                Ok(()) => {}
+                Err(RuntimeError::Resolution(err)) => {
+                    print_resolution_error(&err);
+                    std::process::exit(1);
+                }
+                Err(err) => return Err(err.into()),
+            }
         }
     } else {
         let mut prompt = DefaultPrompt::default();
@@ -91,3 +125,40 @@ pub async fn run(
 
     Ok(())
 }
+
+fn module_path(source: &str) -> String {
+    if source.starts_with("./")
+        || source.starts_with("../")
+        || Path::new(source).is_absolute()
+        || is_remote(source)
+    {
+        source.to_string()
+    } else {
+        format!("./{}", source)
+    }
+}
+
+fn print_resolution_error(err: &ResolutionError) {
+    let from = if err.base.is_empty() {
+        String::new()
+    } else {
+        format!(" from {}", err.base)
+    };
+
+    eprintln!(
+        "{} Could not resolve {}{from}",
+        "error:".red().bold(),
+        err.specifier.bold()
+    );
+
+    if let Some(mapped) = &err.mapped {
+        eprintln!("  mapped to {} by the import map", mapped.bold());
+    }
+
+    for attempt in &err.attempts {
+        eprintln!("  {} {}: {}", "×".red(), attempt.resolver, attempt.reason);
+        for candidate in &attempt.candidates {
+            eprintln!("      {}", format!("tried {candidate}").dimmed());
+        }
+    }
+}
@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use rquickjs::CaughtError;
 
-use crate::error::exception::CaugthException;
+use crate::error::{exception::CaugthException, resolution::ResolutionError};
 
 pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
@@ -9,6 +11,11 @@ pub enum RuntimeError {
     Quick(rquickjs::Error),
     Custom(BoxError),
     Exception(CaugthException),
+    Resolution(ResolutionError),
+    /// The call ran longer than its time limit
+    Timeout(Duration),
+    /// The call was stopped with an interrupt handle
+    Interrupted,
 }
 
 impl RuntimeError {
@@ -25,6 +32,9 @@ impl std::fmt::Display for RuntimeError {
             RuntimeError::Exception(e) => {
                 write!(f, "{e}")
             }
+            RuntimeError::Resolution(e) => write!(f, "{e}"),
+            RuntimeError::Timeout(limit) => write!(f, "Execution timed out after {limit:?}"),
+            RuntimeError::Interrupted => write!(f, "Execution interrupted"),
         }
     }
 }
@@ -35,6 +45,8 @@ impl std::error::Error for RuntimeError {
             Self::Quick(e) => Some(e),
             Self::Custom(e) => Some(&**e),
             Self::Exception(e) => Some(e),
+            Self::Resolution(e) => Some(e),
+            Self::Timeout(_) | Self::Interrupted => None,
         }
     }
 }
@@ -62,3 +74,9 @@ impl<'js> From<CaugthException> for RuntimeError {
         RuntimeError::Exception(value)
     }
 }
+
+impl From<ResolutionError> for RuntimeError {
+    fn from(value: ResolutionError) -> Self {
+        RuntimeError::Resolution(value)
+    }
+}
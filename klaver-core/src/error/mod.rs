@@ -1,8 +1,14 @@
 mod error;
 mod exception;
 mod macros;
+mod resolution;
 mod stack_trace;
 
-pub use self::{error::*, exception::CaugthException, stack_trace::*};
+pub use self::{
+    error::*,
+    exception::CaugthException,
+    resolution::{ResolutionError, ResolveAttempt},
+    stack_trace::*,
+};
 
 pub use rquickjs::Exception;
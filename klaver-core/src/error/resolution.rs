@@ -0,0 +1,84 @@
+use core::fmt;
+
+/// A module specifier no resolver could resolve, with the reason each of them gave
+#[derive(Debug, Clone)]
+pub struct ResolutionError {
+    pub base: String,
+    pub specifier: String,
+    /// The specifier after applying the import map, if it had an entry for it
+    pub mapped: Option<String>,
+    pub attempts: Vec<ResolveAttempt>,
+}
+
+/// A failed attempt by a single resolver
+#[derive(Debug, Clone)]
+pub struct ResolveAttempt {
+    pub resolver: String,
+    pub reason: String,
+    /// Paths looked up on the file system, if any
+    pub candidates: Vec<String>,
+}
+
+impl ResolveAttempt {
+    pub fn new(resolver: impl Into<String>, reason: impl Into<String>) -> ResolveAttempt {
+        ResolveAttempt {
+            resolver: resolver.into(),
+            reason: reason.into(),
+            candidates: Vec::new(),
+        }
+    }
+
+    pub fn with_candidates(mut self, candidates: Vec<String>) -> Self {
+        self.candidates = candidates;
+        self
+    }
+}
+
+impl ResolutionError {
+    /// The reasons of every attempt on a single line
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(mapped) = &self.mapped {
+            out.push_str(&format!("mapped to '{mapped}' by the import map; "));
+        }
+
+        if self.attempts.is_empty() {
+            out.push_str("no resolvers configured");
+        }
+
+        for (idx, attempt) in self.attempts.iter().enumerate() {
+            if idx > 0 {
+                out.push_str("; ");
+            }
+            out.push_str(&format!("{}: {}", attempt.resolver, attempt.reason));
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for ResolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not resolve '{}'", self.specifier)?;
+
+        if !self.base.is_empty() {
+            write!(f, " from '{}'", self.base)?;
+        }
+
+        if let Some(mapped) = &self.mapped {
+            write!(f, ", mapped to '{mapped}' by the import map")?;
+        }
+
+        for attempt in &self.attempts {
+            write!(f, "\n  {}: {}", attempt.resolver, attempt.reason)?;
+            for candidate in &attempt.candidates {
+                write!(f, "\n    tried {candidate}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResolutionError {}
@@ -1,15 +1,23 @@
 use crate::{
-    GlobalBuilder, GlobalInfo, Globals, Loader, ModuleBuilder, ModuleInfo, Resolver, Typings,
-    environ::Environ, environ_builder::EnvBuilder, loader::ModuleLoader, loaders::BuiltinLoader,
-    resolvers::BuiltinResolver,
+    Global, GlobalBuilder, GlobalInfo, Globals, Loader, ModuleBuilder, ModuleInfo, Resolver,
+    Typings,
+    cache::CompileCache,
+    environ::Environ,
+    environ_builder::EnvBuilder,
+    loader::ModuleLoader,
+    loaders::BuiltinLoader,
+    resolvers::{BuiltinResolver, ImportMapResolver},
 };
+use std::{collections::HashSet, path::PathBuf};
+
 #[derive(Default)]
 pub struct Builder {
     modules: EnvBuilder,
     typings: Typings,
     resolvers: Vec<Box<dyn Resolver + Send + Sync>>,
     loaders: Vec<Box<dyn Loader + Send + Sync>>,
-    cache: bool,
+    cache: Option<CompileCache>,
+    import_map: Option<ImportMapResolver>,
 }
 
 impl Builder {
@@ -19,12 +27,21 @@ impl Builder {
             typings: Typings::default(),
             resolvers: Vec::default(),
             loaders: Vec::default(),
-            cache: false,
+            cache: None,
+            import_map: None,
         }
     }
 
+    /// Cache compiled modules in memory, keyed by a hash of their content.
+    /// The cache is shared by all runtimes created from the environment
     pub fn cache(mut self, on: bool) -> Self {
-        self.cache = on;
+        self.cache = on.then(CompileCache::new);
+        self
+    }
+
+    /// Like `cache`, but also persist compiled modules to `dir`
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache = Some(CompileCache::with_dir(dir));
         self
     }
 
@@ -35,6 +52,12 @@ impl Builder {
         self
     }
 
+    /// Rewrite specifiers with an import map, before they are resolved
+    pub fn import_map(mut self, import_map: ImportMapResolver) -> Self {
+        self.import_map = Some(import_map);
+        self
+    }
+
     /// Add a custom resolver to the environment.
     /// Resolvers are responsible for resolving module names to their corresponding modules.
     pub fn resolver<R: Resolver + Send + Sync + 'static>(mut self, resolver: R) -> Self {
@@ -69,6 +92,13 @@ impl Builder {
         self
     }
 
+    /// Add a global instance to the environment. Unlike `global`, the instance can carry
+    /// configuration. It is defined in every context, after the globals added before it
+    pub fn define_global<G: Global + Send + Sync + 'static>(mut self, global: G) -> Self {
+        self.modules.register_global(global);
+        self
+    }
+
     /// Build the environment with the provided modules, resolvers, and loaders.
     pub fn build(self) -> Environ {
         let mut resolvers = Vec::<Box<dyn Resolver + Send + Sync>>::default();
@@ -76,12 +106,15 @@ impl Builder {
         // Builtins resolver
         let mut builtin_resolver = BuiltinResolver::default();
 
-        for module in self
+        let builtins = self
             .modules
             .modules
             .keys()
             .chain(self.modules.modules_src.keys())
-        {
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        for module in &builtins {
             builtin_resolver.add_module(module);
         }
 
@@ -98,9 +131,9 @@ impl Builder {
         loaders.push(Box::new(builtin_loader));
         loaders.extend(self.loaders);
 
-        let modules = ModuleLoader::new(resolvers, loaders);
+        let modules = ModuleLoader::new(resolvers, loaders, self.import_map, builtins);
         let globals = Globals::new(self.modules.globals);
 
-        Environ::new(modules, globals, self.typings)
+        Environ::new(modules, globals, self.typings, self.cache)
     }
 }
@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub code: Vec<u8>,
+    pub source_map: Vec<u8>,
+}
+
+struct CacheInner {
+    entries: RwLock<HashMap<String, CacheEntry>>,
+    dir: Option<PathBuf>,
+}
+
+/// Content addressed cache for compiled modules.
+/// It is owned by the `Environ`, so it is shared by all runtimes created from it.
+/// Entries are kept in memory, and optionally persisted to a directory,
+/// so they survive restarts
+#[derive(Clone)]
+pub struct CompileCache(Arc<CacheInner>);
+
+impl Default for CompileCache {
+    fn default() -> Self {
+        CompileCache::new()
+    }
+}
+
+impl CompileCache {
+    /// An in-memory cache
+    pub fn new() -> CompileCache {
+        CompileCache(Arc::new(CacheInner {
+            entries: RwLock::default(),
+            dir: None,
+        }))
+    }
+
+    /// A cache persisted to `dir`. The directory is created on the first write
+    pub fn with_dir(dir: impl Into<PathBuf>) -> CompileCache {
+        CompileCache(Arc::new(CacheInner {
+            entries: RwLock::default(),
+            dir: Some(dir.into()),
+        }))
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.0.dir.as_deref()
+    }
+
+    /// Create a cache key from the source and everything else affecting the output,
+    /// like compiler options
+    pub fn key<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> String {
+        let mut hasher = Sha256::new();
+
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+
+        to_hex(&hasher.finalize())
+    }
+
+    pub fn get(&self, key: &str) -> Option<CacheEntry> {
+        if let Some(entry) = self.0.entries.read().expect("Lock").get(key) {
+            return Some(entry.clone());
+        }
+
+        let dir = self.0.dir.as_ref()?;
+
+        let code = std::fs::read(dir.join(format!("{key}.js"))).ok()?;
+        let source_map = std::fs::read(dir.join(format!("{key}.js.map"))).ok()?;
+
+        let entry = CacheEntry { code, source_map };
+
+        self.0
+            .entries
+            .write()
+            .expect("Lock")
+            .insert(key.to_string(), entry.clone());
+
+        Some(entry)
+    }
+
+    pub fn insert(&self, key: &str, entry: CacheEntry) {
+        if let Some(dir) = &self.0.dir {
+            if let Err(err) = persist(dir, key, &entry) {
+                tracing::warn!(dir = %dir.display(), error = %err, "Could not persist compile cache entry");
+            }
+        }
+
+        self.0
+            .entries
+            .write()
+            .expect("Lock")
+            .insert(key.to_string(), entry);
+    }
+
+    /// Drop an in-memory entry. A persisted entry is kept, since it is still valid for the same source
+    pub fn remove(&self, key: &str) -> Option<CacheEntry> {
+        self.0.entries.write().expect("Lock").remove(key)
+    }
+
+    /// Drop the in-memory entries. Persisted entries are kept
+    pub fn clear(&self) {
+        self.0.entries.write().expect("Lock").clear();
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut out, byte| {
+            let _ = write!(out, "{byte:02x}");
+            out
+        })
+}
+
+fn persist(dir: &Path, key: &str, entry: &CacheEntry) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    // The source map is written before the code, since an entry is only read when
+    // the code exists. Files are renamed in place, so concurrent writers never expose
+    // a partially written entry
+    write_atomic(&dir.join(format!("{key}.js.map")), &entry.source_map)?;
+    write_atomic(&dir.join(format!("{key}.js")), &entry.code)?;
+
+    Ok(())
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)
+}
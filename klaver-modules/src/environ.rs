@@ -2,12 +2,18 @@ use klaver_core::{RuntimeError, throw};
 use rquickjs::{AsyncContext, AsyncRuntime, CatchResultExt, Ctx, JsLifetime};
 use std::sync::{Arc, Weak};
 
-use crate::{Typings, global::Globals, loader::ModuleLoader};
+use crate::{
+    Typings,
+    cache::CompileCache,
+    global::Globals,
+    loader::{ContextModules, ModuleLoader},
+};
 
 struct Inner {
     pub(crate) modules: ModuleLoader,
     pub(crate) globals: Globals,
     pub(crate) typings: Typings,
+    pub(crate) cache: Option<CompileCache>,
 }
 
 /// Environ is a struct that contains the modules, globals and typings for the environment.
@@ -16,11 +22,17 @@ struct Inner {
 pub struct Environ(Arc<Inner>);
 
 impl Environ {
-    pub fn new(modules: ModuleLoader, globals: Globals, typings: Typings) -> Environ {
+    pub fn new(
+        modules: ModuleLoader,
+        globals: Globals,
+        typings: Typings,
+        cache: Option<CompileCache>,
+    ) -> Environ {
         Environ(Arc::new(Inner {
             modules,
             globals,
             typings,
+            cache,
         }))
     }
 
@@ -32,6 +44,11 @@ impl Environ {
         &self.0.typings
     }
 
+    /// Cache for compiled modules, shared by all runtimes created from this environment
+    pub fn cache(&self) -> Option<&CompileCache> {
+        self.0.cache.as_ref()
+    }
+
     /// Creates a new runtime and attaches the modules to it.
     pub async fn create_runtime(&self) -> Result<AsyncRuntime, RuntimeError> {
         let runtime = AsyncRuntime::new()?;
@@ -40,6 +57,7 @@ impl Environ {
     }
 
     /// Initializes the environment by attaching the globals to the context and storing the environment in the context.
+    /// Failed imports of the context are recorded in its `ResolutionErrors`, and loaded modules in its `LoadedModules`
     pub async fn init(&self, context: &AsyncContext) -> Result<(), RuntimeError> {
         context
             .async_with(async |ctx| {
@@ -47,6 +65,7 @@ impl Environ {
                 self.0.globals.attach(ctx.clone()).await.catch(&ctx)?;
                 ctx.store_userdata(self.downgrade())
                     .map_err(|err| RuntimeError::Custom(Box::from(err.to_string())))?;
+                ContextModules::register(&ctx).catch(&ctx)?;
                 Result::<_, RuntimeError>::Ok(())
             })
             .await?;
@@ -73,4 +92,8 @@ impl WeakEnviron {
             None => throw!(ctx, "Could not upgrade environment"),
         }
     }
+
+    pub fn try_upgrade(&self) -> Option<Environ> {
+        self.0.upgrade().map(Environ)
+    }
 }
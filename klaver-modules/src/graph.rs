@@ -0,0 +1,182 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// The dependency graph of every module resolved by a `ModuleLoader`,
+/// stored as importer → imported, using resolved paths.
+/// Modules imported directly by the host, like with `run_module`, have no importer
+#[derive(Default)]
+pub struct ModuleGraph {
+    imports: RwLock<BTreeMap<String, BTreeSet<String>>>,
+    /// The generation each module was last invalidated in
+    invalidated: RwLock<BTreeMap<String, u64>>,
+    generation: AtomicU64,
+}
+
+impl ModuleGraph {
+    pub fn new() -> ModuleGraph {
+        ModuleGraph::default()
+    }
+
+    pub(crate) fn record(&self, base: &str, path: &str) {
+        let mut imports = self.imports.write().expect("Lock");
+
+        if !imports.contains_key(path) {
+            imports.insert(path.to_string(), BTreeSet::new());
+        }
+
+        if !base.is_empty() && base != path {
+            imports
+                .entry(base.to_string())
+                .or_default()
+                .insert(path.to_string());
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.imports.read().expect("Lock").contains_key(path)
+    }
+
+    /// All modules in the graph
+    pub fn modules(&self) -> Vec<String> {
+        self.imports.read().expect("Lock").keys().cloned().collect()
+    }
+
+    /// Modules imported by `path`
+    pub fn imports(&self, path: &str) -> Vec<String> {
+        self.imports
+            .read()
+            .expect("Lock")
+            .get(path)
+            .map(|imports| imports.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Modules importing `path`
+    pub fn importers(&self, path: &str) -> Vec<String> {
+        self.imports
+            .read()
+            .expect("Lock")
+            .iter()
+            .filter(|(_, imports)| imports.contains(path))
+            .map(|(importer, _)| importer.clone())
+            .collect()
+    }
+
+    /// `path` and every module importing it, directly or transitively
+    pub fn dependents(&self, path: &str) -> Vec<String> {
+        let imports = self.imports.read().expect("Lock");
+
+        if !imports.contains_key(path) {
+            return Vec::new();
+        }
+
+        let mut found = BTreeSet::from([path.to_string()]);
+        let mut queue = vec![path.to_string()];
+
+        while let Some(next) = queue.pop() {
+            for (importer, deps) in imports.iter() {
+                if deps.contains(&next) && found.insert(importer.clone()) {
+                    queue.push(importer.clone());
+                }
+            }
+        }
+
+        found.into_iter().collect()
+    }
+
+    /// Incremented every time modules are invalidated.
+    /// Contexts created before a change still hold the old modules
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Whether `path` was invalidated after `generation`
+    pub fn invalidated_since(&self, path: &str, generation: u64) -> bool {
+        self.invalidated
+            .read()
+            .expect("Lock")
+            .get(path)
+            .is_some_and(|invalidated| *invalidated > generation)
+    }
+
+    /// Remove `path` and everything importing it. Returns the removed modules
+    pub(crate) fn invalidate(&self, path: &str) -> Vec<String> {
+        let dependents = self.dependents(path);
+
+        if dependents.is_empty() {
+            return dependents;
+        }
+
+        // Every importer of a removed module is removed too, so no dangling edges are left
+        let mut imports = self.imports.write().expect("Lock");
+        for module in &dependents {
+            imports.remove(module);
+        }
+        drop(imports);
+
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+
+        let mut invalidated = self.invalidated.write().expect("Lock");
+        for module in &dependents {
+            invalidated.insert(module.clone(), generation);
+        }
+
+        dependents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> ModuleGraph {
+        let graph = ModuleGraph::new();
+        graph.record("", "/app/main.js");
+        graph.record("/app/main.js", "/app/config.js");
+        graph.record("/app/main.js", "/app/util.js");
+        graph.record("/app/util.js", "/app/config.js");
+        graph.record("", "/app/other.js");
+        graph
+    }
+
+    #[test]
+    fn record_imports() {
+        let graph = graph();
+
+        assert_eq!(
+            graph.imports("/app/main.js"),
+            vec!["/app/config.js", "/app/util.js"]
+        );
+        assert_eq!(
+            graph.importers("/app/config.js"),
+            vec!["/app/main.js", "/app/util.js"]
+        );
+        assert!(graph.imports("/app/other.js").is_empty());
+        assert_eq!(graph.modules().len(), 4);
+    }
+
+    #[test]
+    fn invalidate_dependents() {
+        let graph = graph();
+        let generation = graph.generation();
+
+        assert_eq!(
+            graph.invalidate("/app/config.js"),
+            vec!["/app/config.js", "/app/main.js", "/app/util.js"]
+        );
+        assert_eq!(graph.generation(), generation + 1);
+        assert_eq!(graph.modules(), vec!["/app/other.js"]);
+
+        assert!(graph.invalidated_since("/app/main.js", generation));
+        assert!(!graph.invalidated_since("/app/main.js", generation + 1));
+        assert!(!graph.invalidated_since("/app/other.js", generation));
+
+        assert!(graph.invalidate("/app/config.js").is_empty());
+        assert_eq!(graph.generation(), generation + 1);
+    }
+}
@@ -1,7 +1,9 @@
 mod builder;
+mod cache;
 mod environ;
 mod environ_builder;
 mod global;
+mod graph;
 mod loader;
 mod module;
 mod source_map;
@@ -12,9 +14,11 @@ pub mod resolvers;
 
 pub use self::{
     builder::Builder,
+    cache::{CacheEntry, CompileCache},
     environ::{Environ, WeakEnviron},
     global::*,
-    loader::{Loader, QuickWrap, Resolver},
+    graph::ModuleGraph,
+    loader::{LoadedModules, Loader, QuickWrap, ResolutionErrors, Resolver},
     module::*,
     types::Typings,
 };
@@ -1,7 +1,14 @@
-use rquickjs::{Ctx, Module, loader::ImportAttributes};
-use std::sync::{Arc, Mutex};
+use klaver_core::{
+    Core,
+    error::{ResolutionError, ResolveAttempt},
+};
+use rquickjs::{Class, Ctx, JsLifetime, Module, class::Trace, loader::ImportAttributes};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
 
-use crate::source_map::SourceMaps;
+use crate::{graph::ModuleGraph, resolvers::ImportMapResolver, source_map::SourceMaps};
 
 /// Loader is a trait that defines the interface for loading modules.
 /// Contrary to rquickjs's Loader, self is not mutable, and it is expected to be thread safe.
@@ -14,6 +21,11 @@ pub trait Loader {
         path: &str,
         attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<rquickjs::Module<'js, rquickjs::module::Declared>>;
+
+    /// Called when `path` is reloaded, so any state kept for it can be dropped
+    fn invalidate(&self, path: &str) {
+        let _ = path;
+    }
 }
 
 /// Resolver is a trait that defines the interface for resolving module paths.
@@ -28,6 +40,42 @@ pub trait Resolver {
         name: &str,
         attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<String>;
+
+    /// Name of the resolver in resolution errors
+    fn name(&self) -> String {
+        short_type_name::<Self>().to_string()
+    }
+
+    /// Like `resolve`, but describes why `name` could not be resolved.
+    /// Resolvers looking up files should override it to report the paths they tried
+    fn try_resolve<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+        attributes: Option<ImportAttributes<'js>>,
+    ) -> Result<String, ResolveAttempt> {
+        self.resolve(ctx, base, name, attributes)
+            .map_err(|err| ResolveAttempt::new(self.name(), reason(&err)))
+    }
+}
+
+/// The message of a resolver error, without the specifier and base repeated
+pub(crate) fn reason(error: &rquickjs::Error) -> String {
+    match error {
+        rquickjs::Error::Resolving {
+            message: Some(message),
+            ..
+        } if !message.is_empty() => message.clone(),
+        rquickjs::Error::Resolving { .. } => "not found".to_string(),
+        error => error.to_string(),
+    }
+}
+
+fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
 }
 
 /// QuickWrap is a wrapper around a Loader or Resolver that allows it to be shared across multiple runtimes.
@@ -68,6 +116,10 @@ where
     ) -> rquickjs::Result<String> {
         self.0.lock().unwrap().resolve(ctx, base, name, attributes)
     }
+
+    fn name(&self) -> String {
+        short_type_name::<T>().to_string()
+    }
 }
 
 /**
@@ -82,24 +134,67 @@ pub struct ModuleLoader(Arc<ModulesInner>);
 struct ModulesInner {
     resolvers: Vec<Box<dyn Resolver + Send + Sync>>,
     loaders: Vec<Box<dyn Loader + Send + Sync>>,
+    import_map: Option<ImportMapResolver>,
+    builtins: HashSet<String>,
     source_maps: SourceMaps,
+    graph: ModuleGraph,
 }
 
 impl ModuleLoader {
     pub fn new(
         resolvers: Vec<Box<dyn Resolver + Send + Sync>>,
         loaders: Vec<Box<dyn Loader + Send + Sync>>,
+        import_map: Option<ImportMapResolver>,
+        builtins: HashSet<String>,
     ) -> ModuleLoader {
         ModuleLoader(Arc::new(ModulesInner {
             resolvers,
             loaders,
+            import_map,
+            builtins,
             source_maps: SourceMaps::new(),
+            graph: ModuleGraph::new(),
         }))
     }
 
     pub fn source_maps(&self) -> &SourceMaps {
         &self.0.source_maps
     }
+
+    /// Whether `name` is a module built into the environment, rather than loaded from a path
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.0.builtins.contains(name)
+    }
+
+    /// The dependency graph of every module resolved so far
+    pub fn graph(&self) -> &ModuleGraph {
+        &self.0.graph
+    }
+
+    /// Invalidate `path` and every module importing it, dropping their source maps
+    /// and any state the loaders keep for them. QuickJS never unloads a module from
+    /// a context, so the new code is only seen by contexts created afterwards.
+    /// Returns the invalidated modules, which is empty if `path` was never loaded
+    pub fn reload(&self, path: &str) -> Vec<String> {
+        let mut modules = self.0.graph.invalidate(path);
+
+        if modules.is_empty() {
+            // Files are resolved to canonical paths
+            if let Ok(canonical) = std::fs::canonicalize(path) {
+                modules = self.0.graph.invalidate(&canonical.to_string_lossy());
+            }
+        }
+
+        for module in &modules {
+            tracing::trace!(path = %module, "Invalidating module");
+            self.0.source_maps.remove(module);
+            for loader in self.0.loaders.iter() {
+                loader.invalidate(module);
+            }
+        }
+
+        modules
+    }
 }
 
 impl ModuleLoader {
@@ -116,12 +211,30 @@ impl rquickjs::loader::Loader for ModuleLoader {
         name: &str,
         attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
-        let mut error = None;
+        // Read before loading, so an invalidation while loading is not missed
+        let generation = self.0.graph.generation();
+
+        let mut error: Option<rquickjs::Error> = None;
         for loader in self.0.loaders.iter() {
             match loader.load(&self.0.source_maps, ctx, name, attributes.clone()) {
-                Ok(ret) => return Ok(ret),
+                Ok(ret) => {
+                    if let Some(loaded) = LoadedModules::from_ctx(ctx) {
+                        loaded.insert(name, generation);
+                    }
+                    return Ok(ret);
+                }
+                // Keep the first error explaining why a loader failed, over loaders
+                // just passing on a module they do not handle
                 Err(err) => {
-                    error = Some(err);
+                    if !matches!(
+                        error,
+                        Some(rquickjs::Error::Loading {
+                            message: Some(_),
+                            ..
+                        })
+                    ) {
+                        error = Some(err);
+                    }
                 }
             }
         }
@@ -138,13 +251,138 @@ impl rquickjs::loader::Resolver for ModuleLoader {
         name: &str,
         attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<String> {
+        let mapped = self
+            .0
+            .import_map
+            .as_ref()
+            .and_then(|map| map.rewrite(base, name));
+
+        let specifier = mapped.as_deref().unwrap_or(name);
+
+        let mut attempts = Vec::with_capacity(self.0.resolvers.len());
+
         for resolver in self.0.resolvers.iter() {
-            if let Ok(ret) = resolver.resolve(ctx, base, name, attributes.clone()) {
-                return Ok(ret);
+            match resolver.try_resolve(ctx, base, specifier, attributes.clone()) {
+                Ok(ret) => {
+                    self.0.graph.record(base, &ret);
+                    return Ok(ret);
+                }
+                Err(attempt) => attempts.push(attempt),
             }
         }
 
-        Err(rquickjs::Error::new_resolving(base, name))
+        let error = ResolutionError {
+            base: base.to_string(),
+            specifier: name.to_string(),
+            mapped,
+            attempts,
+        };
+
+        let err = rquickjs::Error::new_resolving_message(base, name, error.summary());
+        if let Some(errors) = ResolutionErrors::from_ctx(ctx) {
+            errors.insert(err.to_string(), error);
+        }
+
+        Err(err)
+    }
+}
+
+/// The state the module loader keeps for a context, registered in its `Core` by `Environ::init`.
+/// Userdata is shared by every context of a runtime, so it can't be kept there
+#[rquickjs::class]
+#[derive(Clone, Default)]
+pub(crate) struct ContextModules {
+    errors: ResolutionErrors,
+    loaded: LoadedModules,
+}
+
+impl<'js> Trace<'js> for ContextModules {
+    fn trace<'a>(&self, _tracer: rquickjs::class::Tracer<'a, 'js>) {}
+}
+
+unsafe impl<'js> JsLifetime<'js> for ContextModules {
+    type Changed<'to> = ContextModules;
+}
+
+impl ContextModules {
+    const KEY: &str = "ContextModules";
+
+    pub(crate) fn register(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+        let core = Core::from_ctx(ctx)?;
+        core.borrow_mut().register(
+            Self::KEY,
+            Class::instance(ctx.clone(), ContextModules::default())?,
+        )
+    }
+
+    fn from_ctx(ctx: &Ctx<'_>) -> Option<ContextModules> {
+        let core = Core::from_ctx(ctx).ok()?;
+        let modules = core
+            .borrow()
+            .get::<Option<Class<ContextModules>>>(Self::KEY)
+            .ok()??;
+        let modules = modules.borrow().clone();
+        Some(modules)
+    }
+}
+
+/// The failed imports of a context, with the attempt of every resolver.
+/// Kept for every context initialized by `Environ::init`
+#[derive(Clone, Default)]
+pub struct ResolutionErrors(Arc<Mutex<HashMap<(String, String), (String, ResolutionError)>>>);
+
+impl ResolutionErrors {
+    pub fn from_ctx(ctx: &Ctx<'_>) -> Option<ResolutionErrors> {
+        ContextModules::from_ctx(ctx).map(|modules| modules.errors)
+    }
+
+    /// Keyed by the failing import, so a later failure of the same import replaces it
+    fn insert(&self, message: String, error: ResolutionError) {
+        let key = (error.base.clone(), error.specifier.clone());
+        self.0.lock().expect("Lock").insert(key, (message, error));
+    }
+
+    /// The structured error behind a failed import.
+    /// QuickJS only carries the message of the resolver's error in the exception it throws,
+    /// so that is what connects the exception to the import
+    pub fn take(&self, message: &str) -> Option<ResolutionError> {
+        let mut errors = self.0.lock().expect("Lock");
+
+        let key = errors
+            .iter()
+            .find(|(_, (msg, _))| msg == message)
+            .map(|(key, _)| key.clone())?;
+
+        errors.remove(&key).map(|(_, error)| error)
+    }
+}
+
+/// The modules loaded into a context, with the graph generation they were loaded in.
+/// Kept for every context initialized by `Environ::init`
+#[derive(Clone, Default)]
+pub struct LoadedModules(Arc<Mutex<HashMap<String, u64>>>);
+
+impl LoadedModules {
+    pub fn from_ctx(ctx: &Ctx<'_>) -> Option<LoadedModules> {
+        ContextModules::from_ctx(ctx).map(|modules| modules.loaded)
+    }
+
+    fn insert(&self, path: &str, generation: u64) {
+        self.0
+            .lock()
+            .expect("Lock")
+            .entry(path.to_string())
+            .or_insert(generation);
+    }
+
+    /// Whether any of the modules was invalidated after it was loaded.
+    /// QuickJS never unloads a module, so the context holds the old code
+    pub fn is_stale(&self, graph: &ModuleGraph) -> bool {
+        self.0
+            .lock()
+            .expect("Lock")
+            .iter()
+            .any(|(path, generation)| graph.invalidated_since(path, *generation))
     }
 }
 
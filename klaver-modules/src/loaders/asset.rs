@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use rquickjs::{
+    Ctx, Module, TypedArray, Value,
+    loader::ImportAttributes,
+    module::{Declarations, Exports, ModuleDef},
+};
+
+use crate::{loaders::Transformer, source_map::SourceMaps};
+
+pub(crate) fn has_type(attributes: Option<&ImportAttributes<'_>>, ty: &str) -> bool {
+    attributes
+        .and_then(|attrs| attrs.get_type().ok().flatten())
+        .is_some_and(|m| m == ty)
+}
+
+fn read(path: &Path) -> rquickjs::Result<Vec<u8>> {
+    std::fs::read(path).map_err(|err| {
+        rquickjs::Error::new_loading_message(path.to_string_lossy().as_ref(), err.to_string())
+    })
+}
+
+/// Quote `text` as a javascript string literal
+fn quote<'js>(ctx: &Ctx<'js>, path: &Path, text: Vec<u8>) -> rquickjs::Result<String> {
+    let text = String::from_utf8(text).map_err(|err| {
+        rquickjs::Error::new_loading_message(path.to_string_lossy().as_ref(), err.to_string())
+    })?;
+
+    match ctx.json_stringify(text)? {
+        Some(literal) => literal.to_string(),
+        None => Ok("\"\"".to_string()),
+    }
+}
+
+/// Loads `import config from "./config.json" with { type: "json" }` as the parsed value
+pub struct JsonTransformer;
+
+impl Transformer for JsonTransformer {
+    fn transform<'js>(
+        &self,
+        _sourcemaps: &SourceMaps,
+        ctx: &Ctx<'js>,
+        path: &Path,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        let literal = quote(ctx, path, read(path)?)?;
+
+        Module::declare(
+            ctx.clone(),
+            path.to_string_lossy().as_ref(),
+            format!("export default JSON.parse({literal});"),
+        )
+    }
+
+    fn map(&self, _path: &Path, line: usize, col: usize) -> Option<(usize, usize)> {
+        Some((line, col))
+    }
+
+    fn can_transform(&self, _path: &Path, attributes: Option<&ImportAttributes<'_>>) -> bool {
+        has_type(attributes, "json")
+    }
+}
+
+/// Loads `import text from "./file.txt" with { type: "text" }` as a string
+pub struct TextTransformer;
+
+impl Transformer for TextTransformer {
+    fn transform<'js>(
+        &self,
+        _sourcemaps: &SourceMaps,
+        ctx: &Ctx<'js>,
+        path: &Path,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        let literal = quote(ctx, path, read(path)?)?;
+
+        Module::declare(
+            ctx.clone(),
+            path.to_string_lossy().as_ref(),
+            format!("export default {literal};"),
+        )
+    }
+
+    fn map(&self, _path: &Path, line: usize, col: usize) -> Option<(usize, usize)> {
+        Some((line, col))
+    }
+
+    fn can_transform(&self, _path: &Path, attributes: Option<&ImportAttributes<'_>>) -> bool {
+        has_type(attributes, "text")
+    }
+}
+
+/// Loads `import bytes from "./file.bin" with { type: "bytes" }` as a `Uint8Array`.
+/// The array is created natively and exported from a synthetic module,
+/// so the content never goes through the parser
+pub struct BytesTransformer;
+
+/// Key of the pending array on the `import.meta` of a bytes module.
+/// The module has no source, so nothing else can see its `import.meta`
+const BYTES_KEY: &str = "bytes";
+
+struct BytesModule;
+
+impl ModuleDef for BytesModule {
+    fn declare<'js>(decl: &Declarations<'js>) -> rquickjs::Result<()> {
+        decl.declare("default")?;
+        Ok(())
+    }
+
+    fn evaluate<'js>(_ctx: &Ctx<'js>, exports: &Exports<'js>) -> rquickjs::Result<()> {
+        let meta = exports.module().meta()?;
+        let bytes = meta.get::<_, Value<'js>>(BYTES_KEY)?;
+        meta.remove(BYTES_KEY)?;
+
+        exports.export("default", bytes)?;
+        Ok(())
+    }
+}
+
+impl Transformer for BytesTransformer {
+    fn transform<'js>(
+        &self,
+        _sourcemaps: &SourceMaps,
+        ctx: &Ctx<'js>,
+        path: &Path,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        let bytes = TypedArray::<u8>::new(ctx.clone(), read(path)?)?;
+
+        let module =
+            Module::declare_def::<BytesModule, _>(ctx.clone(), path.to_string_lossy().as_ref())?;
+        module.meta()?.set(BYTES_KEY, bytes)?;
+
+        Ok(module)
+    }
+
+    fn map(&self, _path: &Path, line: usize, col: usize) -> Option<(usize, usize)> {
+        Some((line, col))
+    }
+
+    fn can_transform(&self, _path: &Path, attributes: Option<&ImportAttributes<'_>>) -> bool {
+        has_type(attributes, "bytes")
+    }
+}
@@ -0,0 +1,517 @@
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use klaver_core::RuntimeError;
+use rquickjs::{
+    AsyncContext, AsyncRuntime, CatchResultExt, Ctx, Module, WriteOptions,
+    loader::ImportAttributes, qjs,
+};
+use sha2::{Digest, Sha256};
+use tracing::trace;
+
+use crate::{
+    Environ, Loader, Resolver,
+    loader::ModuleLoader,
+    source_map::{SourceMap, SourceMaps},
+};
+
+const MAGIC: &[u8; 4] = b"KLVB";
+const VERSION: u32 = 2;
+/// Marks an executable with an embedded bundle. It is preceded by the bundle length
+const TRAILER: &[u8; 8] = b"KLVREXE1";
+
+pub struct BundleModule {
+    pub bytecode: Vec<u8>,
+    pub source_map: Option<SourceMap>,
+}
+
+/// A module graph compiled to QuickJS bytecode.
+/// Bytecode is tied to the QuickJS version and the endianness it was compiled with.
+/// Serialized bundles are stamped with both, and with a checksum of their content,
+/// and are rejected when they don't match the running QuickJS
+#[derive(Default)]
+pub struct Bundle {
+    entry: String,
+    modules: HashMap<String, BundleModule>,
+    imports: HashMap<(String, String), String>,
+}
+
+impl Bundle {
+    /// Compile `entry` and its static imports with the loaders and resolvers of `env`.
+    /// Modules are declared but never evaluated. Builtin modules are not included,
+    /// and are expected to be provided by the runtime loading the bundle
+    pub async fn compile(env: &Environ, entry: &str) -> Result<Bundle, RuntimeError> {
+        let recorder = Recorder {
+            modules: env.modules().clone(),
+            bundle: Arc::new(Mutex::new(Bundle {
+                entry: entry.to_string(),
+                ..Default::default()
+            })),
+        };
+
+        let runtime = AsyncRuntime::new()?;
+        runtime.set_loader(recorder.clone(), recorder.clone()).await;
+
+        let context = AsyncContext::full(&runtime).await?;
+        env.init(&context).await?;
+
+        context
+            .with(|ctx| {
+                recorder.clone().compile_entry(&ctx, entry).catch(&ctx)?;
+                Result::<_, RuntimeError>::Ok(())
+            })
+            .await?;
+
+        drop(context);
+        drop(runtime);
+
+        let bundle = std::mem::take(&mut *recorder.bundle.lock().expect("Lock"));
+
+        Ok(bundle)
+    }
+
+    /// The specifier the bundle was compiled from
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = (&str, &BundleModule)> {
+        self.modules.iter().map(|(name, module)| (&**name, module))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BundleModule> {
+        self.modules.get(name)
+    }
+
+    /// Read a bundle saved with `save`.
+    ///
+    /// # Safety
+    /// See `from_bytes`
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Bundle> {
+        unsafe { Bundle::from_bytes(&std::fs::read(path)?) }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    /// Write a copy of the `runtime` executable to `output`, with the bundle appended
+    pub fn write_executable(
+        &self,
+        runtime: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        std::fs::copy(runtime, &output)?;
+
+        let payload = self.to_bytes();
+
+        let mut file = OpenOptions::new().append(true).open(output)?;
+        file.write_all(&payload)?;
+        file.write_all(&(payload.len() as u64).to_le_bytes())?;
+        file.write_all(TRAILER)?;
+
+        Ok(())
+    }
+
+    /// Read the bundle embedded in an executable by `write_executable`.
+    /// Returns `None` if the executable has no bundle
+    ///
+    /// # Safety
+    /// See `from_bytes`
+    pub unsafe fn from_executable(path: impl AsRef<Path>) -> io::Result<Option<Bundle>> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+
+        let trailer_len = 8 + TRAILER.len() as u64;
+        if size < trailer_len {
+            return Ok(None);
+        }
+
+        let mut trailer = [0; 16];
+        file.seek(SeekFrom::End(-(trailer_len as i64)))?;
+        file.read_exact(&mut trailer)?;
+
+        if &trailer[8..] != TRAILER {
+            return Ok(None);
+        }
+
+        let len = u64::from_le_bytes(trailer[..8].try_into().expect("8 bytes"));
+        if len > size - trailer_len {
+            return Err(invalid("Invalid embedded bundle"));
+        }
+
+        let mut payload = vec![0; len as usize];
+        file.seek(SeekFrom::End(-((trailer_len + len) as i64)))?;
+        file.read_exact(&mut payload)?;
+
+        unsafe { Bundle::from_bytes(&payload).map(Some) }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_bytes(&mut out, self.entry.as_bytes());
+
+        write_u32(&mut out, self.modules.len() as u32);
+        for (name, module) in &self.modules {
+            write_bytes(&mut out, name.as_bytes());
+            write_bytes(&mut out, &module.bytecode);
+
+            let mappings = module
+                .source_map
+                .as_ref()
+                .map(|m| m.mappings())
+                .unwrap_or_default();
+
+            write_u32(&mut out, mappings.len() as u32);
+            for mapping in mappings {
+                write_u32(&mut out, mapping.src_line);
+                write_u32(&mut out, mapping.src_col);
+                write_u32(&mut out, mapping.dst_line);
+                write_u32(&mut out, mapping.dst_col);
+            }
+        }
+
+        write_u32(&mut out, self.imports.len() as u32);
+        for ((base, name), resolved) in &self.imports {
+            write_bytes(&mut out, base.as_bytes());
+            write_bytes(&mut out, name.as_bytes());
+            write_bytes(&mut out, resolved.as_bytes());
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        write_u32(&mut header, VERSION);
+        write_bytes(&mut header, runtime_stamp().as_bytes());
+        header.extend_from_slice(&Sha256::digest(&out));
+        header.extend_from_slice(&out);
+
+        header
+    }
+
+    /// Read a bundle serialized with `to_bytes`. Bundles compiled by another QuickJS version,
+    /// or with a checksum that doesn't match their content, are rejected.
+    ///
+    /// # Safety
+    /// The bytecode is loaded as is by QuickJS, which trusts it to be well formed.
+    /// The checksum only guards against corruption, not against crafted bundles,
+    /// so `bytes` must come from `to_bytes` of a bundle compiled by `Bundle::compile`
+    pub unsafe fn from_bytes(bytes: &[u8]) -> io::Result<Bundle> {
+        let mut reader = Reader(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("Not a klaver bundle"));
+        }
+
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(invalid(format!("Unsupported bundle version: {version}")));
+        }
+
+        let stamp = reader.string()?;
+        if stamp != runtime_stamp() {
+            return Err(invalid(format!(
+                "Bundle was compiled for {stamp}, but the runtime is {}",
+                runtime_stamp()
+            )));
+        }
+
+        let checksum = reader.take(32)?;
+        if Sha256::digest(reader.0).as_slice() != checksum {
+            return Err(invalid("Bundle checksum does not match its content"));
+        }
+
+        let entry = reader.string()?;
+
+        let mut modules = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let bytecode = reader.bytes()?.to_vec();
+
+            let count = reader.u32()?;
+            let source_map = if count == 0 {
+                None
+            } else {
+                let mut mappings = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    mappings.push((
+                        (reader.u32()?, reader.u32()?),
+                        (reader.u32()?, reader.u32()?),
+                    ));
+                }
+                Some(SourceMap::from_iter(mappings))
+            };
+
+            modules.insert(
+                name,
+                BundleModule {
+                    bytecode,
+                    source_map,
+                },
+            );
+        }
+
+        let mut imports = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let base = reader.string()?;
+            let name = reader.string()?;
+            imports.insert((base, name), reader.string()?);
+        }
+
+        Ok(Bundle {
+            entry,
+            modules,
+            imports,
+        })
+    }
+}
+
+/// BytecodeLoader loads modules from a precompiled `Bundle`.
+/// It is both a resolver and a loader, since the files the bundle was compiled from
+/// are not expected to exist where it runs
+#[derive(Clone)]
+pub struct BytecodeLoader(Arc<Bundle>);
+
+impl BytecodeLoader {
+    pub fn new(bundle: Bundle) -> BytecodeLoader {
+        BytecodeLoader(Arc::new(bundle))
+    }
+
+    /// Load the bundle saved at `path`.
+    ///
+    /// # Safety
+    /// See `Bundle::from_bytes`
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<BytecodeLoader> {
+        Ok(BytecodeLoader::new(unsafe { Bundle::open(path)? }))
+    }
+
+    pub fn bundle(&self) -> &Bundle {
+        &self.0
+    }
+}
+
+impl Loader for BytecodeLoader {
+    fn load<'js>(
+        &self,
+        sourcemaps: &SourceMaps,
+        ctx: &Ctx<'js>,
+        path: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        let Some(module) = self.0.modules.get(path) else {
+            return Err(rquickjs::Error::new_loading(path));
+        };
+
+        if let Some(source_map) = &module.source_map {
+            sourcemaps.insert(path.to_string(), source_map.clone());
+        }
+
+        // Safety: the bytecode was written by `Module::write` when the bundle was compiled,
+        // which the unsafe constructors of `Bundle` require from their callers. QuickJS may reference the buffer instead of copying it, which is fine since the
+        // bundle is kept alive by the environment for as long as the runtime exists
+        let module = unsafe { Module::load(ctx.clone(), &module.bytecode)? };
+        module.meta()?.set("url", format!("file://{}", path))?;
+
+        Ok(module)
+    }
+}
+
+impl Resolver for BytecodeLoader {
+    fn resolve<'js>(
+        &self,
+        _ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<String> {
+        match self.0.imports.get(&(base.to_string(), name.to_string())) {
+            Some(path) => {
+                trace!(base = %base, name = %name, path = %path, "Resolved bundled module");
+                Ok(path.clone())
+            }
+            None => Err(rquickjs::Error::new_resolving(base, name)),
+        }
+    }
+}
+
+/// Wraps the environment's module loader, and records every module it loads from disk
+#[derive(Clone)]
+struct Recorder {
+    modules: ModuleLoader,
+    bundle: Arc<Mutex<Bundle>>,
+}
+
+impl Recorder {
+    fn compile_entry<'js>(mut self, ctx: &Ctx<'js>, entry: &str) -> rquickjs::Result<()> {
+        let path = rquickjs::loader::Resolver::resolve(&mut self, ctx, "", entry, None)?;
+        rquickjs::loader::Loader::load(&mut self, ctx, &path, None)?;
+
+        let bundle = self.bundle.lock().expect("Lock");
+        let Some(module) = bundle.modules.get(&path) else {
+            return Err(rquickjs::Error::new_loading_message(
+                &path,
+                "Entry is not a file module",
+            ));
+        };
+        let bytecode = module.bytecode.clone();
+        drop(bundle);
+
+        link(ctx, &bytecode)
+    }
+}
+
+/// Resolve the static imports of the module compiled to `bytecode`, loading them
+/// with the runtime's loader, without evaluating anything
+pub(crate) fn link(ctx: &Ctx<'_>, bytecode: &[u8]) -> rquickjs::Result<()> {
+    // Safety: QuickJS only exposes import resolution for module values, and a
+    // declared module can't be converted into one, so it is read back from its bytecode.
+    // This is the same sequence `qjs` uses to resolve precompiled modules
+    unsafe {
+        let raw = ctx.as_raw().as_ptr();
+        let module = qjs::JS_ReadObject(
+            raw,
+            bytecode.as_ptr(),
+            bytecode.len() as _,
+            qjs::JS_READ_OBJ_BYTECODE as i32,
+        );
+
+        if qjs::JS_IsException(module) {
+            return Err(rquickjs::Error::Exception);
+        }
+
+        let ret = qjs::JS_ResolveModule(raw, module);
+        qjs::JS_FreeValue(raw, module);
+
+        if ret < 0 {
+            return Err(rquickjs::Error::Exception);
+        }
+    }
+
+    Ok(())
+}
+
+impl rquickjs::loader::Resolver for Recorder {
+    fn resolve<'js>(
+        &mut self,
+        ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+        attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<String> {
+        let path =
+            rquickjs::loader::Resolver::resolve(&mut self.modules, ctx, base, name, attributes)?;
+
+        self.bundle
+            .lock()
+            .expect("Lock")
+            .imports
+            .insert((base.to_string(), name.to_string()), path.clone());
+
+        Ok(path)
+    }
+}
+
+impl rquickjs::loader::Loader for Recorder {
+    fn load<'js>(
+        &mut self,
+        ctx: &Ctx<'js>,
+        name: &str,
+        attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        // Bytes modules are native, so they have no bytecode
+        if super::asset::has_type(attributes.as_ref(), "bytes") {
+            return Err(rquickjs::Error::new_loading_message(
+                name,
+                "Bytes imports can not be compiled to a bundle",
+            ));
+        }
+
+        let module = rquickjs::loader::Loader::load(&mut self.modules, ctx, name, attributes)?;
+
+        // Builtin modules are provided by the runtime
+        if self.modules.is_builtin(name) {
+            return Ok(module);
+        }
+
+        // Modules from other loaders, like url or memory modules, would be missing when the bundle runs
+        if !Path::new(name).is_file() {
+            return Err(rquickjs::Error::new_loading_message(
+                name,
+                "Only file and builtin modules can be compiled to a bundle",
+            ));
+        }
+
+        let bytecode = module.write(WriteOptions::default())?;
+        let source_map = self.modules.source_maps().get(name);
+
+        trace!(path = %name, size = bytecode.len(), "Compiled module to bytecode");
+
+        self.bundle.lock().expect("Lock").modules.insert(
+            name.to_string(),
+            BundleModule {
+                bytecode,
+                source_map,
+            },
+        );
+
+        Ok(module)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(invalid)
+    }
+}
+
+/// The QuickJS version and endianness, which bytecode is only valid for
+fn runtime_stamp() -> String {
+    // Safety: QuickJS returns a static, nul terminated version string
+    let version = unsafe { CStr::from_ptr(qjs::JS_GetVersion()) };
+    let endian = if cfg!(target_endian = "big") {
+        "big"
+    } else {
+        "little"
+    };
+
+    format!("quickjs {} {endian} endian", version.to_string_lossy())
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,563 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use klaver_core::{throw, throw_if};
+use rquickjs::{
+    Ctx, Function, Module, Object, Symbol, Value, context::EvalOptions, function::This,
+    loader::ImportAttributes,
+};
+
+use crate::{environ::WeakEnviron, loaders::Transformer, source_map::SourceMaps};
+
+/// The `type` field of the nearest package.json, cached by directory
+#[derive(Clone, Default)]
+struct PackageTypes(Arc<Mutex<HashMap<PathBuf, Option<String>>>>);
+
+impl PackageTypes {
+    fn lookup(&self, path: &Path) -> Option<String> {
+        let dir = path.parent()?;
+
+        if let Some(ty) = self.0.lock().expect("Lock").get(dir) {
+            return ty.clone();
+        }
+
+        let ty = dir
+            .ancestors()
+            .map(|dir| dir.join("package.json"))
+            .find(|pkg| pkg.is_file())
+            .and_then(|pkg| std::fs::read(pkg).ok())
+            .and_then(|content| serde_json::from_slice::<serde_json::Value>(&content).ok())
+            .and_then(|pkg| pkg.get("type")?.as_str().map(String::from));
+
+        self.0
+            .lock()
+            .expect("Lock")
+            .insert(dir.to_path_buf(), ty.clone());
+
+        ty
+    }
+
+    /// Whether `path` is a CommonJS module, following node: `.cjs` files always are,
+    /// and `.js` files are when their package.json says so. Without a `type` field
+    /// the syntax decides, so files using `require` but no `import` or `export` are CommonJS
+    fn is_commonjs(&self, path: &Path) -> bool {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("cjs") => true,
+            Some("js") => match self.lookup(path).as_deref() {
+                Some("module") => false,
+                Some(_) => true,
+                None => std::fs::read_to_string(path)
+                    .map(|source| !has_esm_syntax(&source) && has_cjs_syntax(&source))
+                    .unwrap_or(false),
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Loads CommonJS modules. The module is run in a function scope providing `require`, `module`,
+/// `exports`, `__filename` and `__dirname`, and `module.exports` becomes the default export.
+/// Named exports are detected from the source, like `exports.name = ...`.
+///
+/// Modules are cached by path before they run, so cycles between CommonJS modules see the
+/// partially filled exports, like in node. `require` of an ES module evaluates it synchronously,
+/// and fails if the module uses top-level await
+#[derive(Default)]
+pub struct CjsTransformer {
+    types: PackageTypes,
+}
+
+impl CjsTransformer {
+    pub fn new() -> CjsTransformer {
+        CjsTransformer::default()
+    }
+}
+
+impl Transformer for CjsTransformer {
+    fn transform<'js>(
+        &self,
+        _sourcemaps: &SourceMaps,
+        ctx: &Ctx<'js>,
+        path: &Path,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        let filename = path.to_string_lossy().to_string();
+        let source = throw_if!(ctx, std::fs::read_to_string(path));
+
+        let mut wrapper =
+            String::from("const __module = import.meta.cjs();\nexport default __module.exports;\n");
+        for name in detect_exports(&source) {
+            wrapper.push_str(&format!("export const {name} = __module.exports.{name};\n"));
+        }
+
+        let module = Module::declare(ctx.clone(), filename.as_str(), wrapper)?;
+
+        let types = self.types.clone();
+        module.meta()?.set(
+            "cjs",
+            Function::new(ctx.clone(), move |ctx: Ctx<'js>| {
+                load(&ctx, &types, &filename)
+            })?,
+        )?;
+
+        Ok(module)
+    }
+
+    fn map(&self, _path: &Path, line: usize, col: usize) -> Option<(usize, usize)> {
+        Some((line, col))
+    }
+
+    fn can_transform(&self, path: &Path, attributes: Option<&ImportAttributes<'_>>) -> bool {
+        let typed = attributes
+            .and_then(|attrs| attrs.get_type().ok().flatten())
+            .is_some();
+
+        !typed && self.types.is_commonjs(path)
+    }
+}
+
+/// The `require.cache` shared by all CommonJS modules in a context
+fn cache<'js>(ctx: &Ctx<'js>) -> rquickjs::Result<Object<'js>> {
+    let key = Symbol::new_global(ctx.clone(), "klaver.cjs.cache")?;
+
+    if let Some(cache) = ctx.globals().get::<_, Option<Object>>(key.clone())? {
+        return Ok(cache);
+    }
+
+    let cache = Object::new(ctx.clone())?;
+    ctx.globals().set(key, cache.clone())?;
+
+    Ok(cache)
+}
+
+/// Run the CommonJS module at `filename`, or return it from the cache
+fn load<'js>(
+    ctx: &Ctx<'js>,
+    types: &PackageTypes,
+    filename: &str,
+) -> rquickjs::Result<Object<'js>> {
+    let cache = cache(ctx)?;
+
+    if let Some(module) = cache.get::<_, Option<Object>>(filename)? {
+        return Ok(module);
+    }
+
+    let path = Path::new(filename);
+    let dirname = path
+        .parent()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let exports = Object::new(ctx.clone())?;
+    let require = create_require(ctx, types, filename)?;
+
+    let module = Object::new(ctx.clone())?;
+    module.set("id", filename)?;
+    module.set("filename", filename)?;
+    module.set("path", dirname.as_str())?;
+    module.set("exports", exports.clone())?;
+    module.set("require", require.clone())?;
+    module.set("loaded", false)?;
+
+    // Cached before running, so cycles get the exports as they are so far
+    cache.set(filename, module.clone())?;
+
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            cache.remove(filename)?;
+            throw!(ctx, format!("Cannot load module '{filename}': {err}"))
+        }
+    };
+
+    let ret = if path.extension().is_some_and(|ext| ext == "json") {
+        ctx.json_parse(source)
+            .and_then(|value| module.set("exports", value))
+    } else {
+        compile(ctx, filename, &source).and_then(|func| {
+            func.call::<_, ()>((
+                This(exports.clone()),
+                exports,
+                require,
+                module.clone(),
+                filename,
+                dirname,
+            ))
+        })
+    };
+
+    if let Err(err) = ret {
+        cache.remove(filename)?;
+        return Err(err);
+    }
+
+    module.set("loaded", true)?;
+
+    Ok(module)
+}
+
+/// Compile `source` as the body of the CommonJS module function.
+/// The header is on the first line, so line numbers match the file
+fn compile<'js>(ctx: &Ctx<'js>, filename: &str, source: &str) -> rquickjs::Result<Function<'js>> {
+    let source = match source.strip_prefix("#!") {
+        Some(rest) => format!("//{rest}"),
+        None => source.to_string(),
+    };
+
+    let options = EvalOptions {
+        strict: false,
+        filename: Some(filename.to_string()),
+        ..Default::default()
+    };
+
+    ctx.eval_with_options(
+        format!("(function (exports, require, module, __filename, __dirname) {{{source}\n}})"),
+        options,
+    )
+}
+
+fn create_require<'js>(
+    ctx: &Ctx<'js>,
+    types: &PackageTypes,
+    filename: &str,
+) -> rquickjs::Result<Function<'js>> {
+    let require = {
+        let types = types.clone();
+        let base = filename.to_string();
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, specifier: String| {
+            require_module(&ctx, &types, &base, &specifier)
+        })?
+        .with_name("require")?
+    };
+
+    let base = filename.to_string();
+    require.set(
+        "resolve",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, specifier: String| {
+            resolve(&ctx, &base, &specifier)
+        })?,
+    )?;
+    require.set("cache", cache(ctx)?)?;
+
+    Ok(require)
+}
+
+fn resolve<'js>(ctx: &Ctx<'js>, base: &str, specifier: &str) -> rquickjs::Result<String> {
+    let Some(env) = ctx
+        .userdata::<WeakEnviron>()
+        .and_then(|env| env.try_upgrade())
+    else {
+        throw!(ctx, "Environment not initialized")
+    };
+
+    let mut modules = env.modules().clone();
+
+    rquickjs::loader::Resolver::resolve(&mut modules, ctx, base, specifier, None)
+}
+
+fn require_module<'js>(
+    ctx: &Ctx<'js>,
+    types: &PackageTypes,
+    base: &str,
+    specifier: &str,
+) -> rquickjs::Result<Value<'js>> {
+    let path = resolve(ctx, base, specifier)?;
+
+    let is_json = Path::new(&path)
+        .extension()
+        .is_some_and(|ext| ext == "json");
+
+    if is_json || types.is_commonjs(Path::new(&path)) {
+        return load(ctx, types, &path)?.get("exports");
+    }
+
+    // ES modules and builtins are evaluated synchronously, and their namespace returned
+    match Module::import(ctx, path.as_str())?.finish::<Object>() {
+        Ok(namespace) => Ok(namespace.into_value()),
+        Err(rquickjs::Error::WouldBlock) => throw!(
+            ctx,
+            format!(
+                "Cannot require() ES module '{path}' which does not finish synchronously, use import() instead"
+            )
+        ),
+        Err(err) => Err(err),
+    }
+}
+
+fn has_esm_syntax(source: &str) -> bool {
+    source.lines().any(|line| {
+        let line = line.trim_start();
+        ["import", "export"].iter().any(|keyword| {
+            line.strip_prefix(keyword)
+                .and_then(|rest| rest.chars().next())
+                .is_some_and(|c| matches!(c, ' ' | '{' | '*' | '"' | '\''))
+        })
+    })
+}
+
+fn has_cjs_syntax(source: &str) -> bool {
+    source.contains("require(") || source.contains("module.exports") || source.contains("exports.")
+}
+
+const RESERVED: &[&str] = &[
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+    "__esModule",
+    "__module",
+];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn ident(input: &str) -> Option<&str> {
+    let end = input
+        .char_indices()
+        .find(|(_, c)| !is_ident_char(*c))
+        .map(|(idx, _)| idx)
+        .unwrap_or(input.len());
+
+    let ident = &input[..end];
+
+    match ident.chars().next() {
+        Some(c) if !c.is_ascii_digit() => Some(ident),
+        _ => None,
+    }
+}
+
+/// Find the names assigned on `exports`, like `exports.name = ...`,
+/// `module.exports.name = ...`, `Object.defineProperty(exports, "name", ...)`
+/// and the keys of `module.exports = { ... }`, outside strings and comments
+fn detect_exports(source: &str) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+
+    // Searched without strings and comments. The names of `defineProperty` are read from the source
+    let code = blank_literals(source);
+
+    for (idx, _) in code.match_indices("exports") {
+        let before = code[..idx].trim_end_matches("module.");
+        if before
+            .chars()
+            .next_back()
+            .is_some_and(|c| is_ident_char(c) || c == '.')
+        {
+            continue;
+        }
+
+        let rest = &code[idx + "exports".len()..];
+
+        if let Some(rest) = rest.strip_prefix('.') {
+            // exports.name = value
+            if let Some(name) = ident(rest) {
+                let after = rest[name.len()..].trim_start();
+                if after.starts_with('=') && !after.starts_with("==") {
+                    names.insert(name.to_string());
+                }
+            }
+        } else if before.len() < idx {
+            // module.exports = { name, other: value }
+            let rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix('=').map(str::trim_start)
+                && let Some(rest) = rest.strip_prefix('{')
+            {
+                names.extend(object_keys(rest));
+            }
+        }
+    }
+
+    for (idx, pattern) in code.match_indices("Object.defineProperty(") {
+        let rest = code[idx + pattern.len()..].trim_start();
+        let Some(rest) = rest
+            .strip_prefix("module.exports")
+            .or_else(|| rest.strip_prefix("exports"))
+        else {
+            continue;
+        };
+
+        let Some(rest) = rest.trim_start().strip_prefix(',') else {
+            continue;
+        };
+
+        let rest = &source[code.len() - rest.trim_start().len()..];
+        if let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\''))
+            && let Some(end) = rest[1..].find(quote)
+        {
+            names.insert(rest[1..end + 1].to_string());
+        }
+    }
+
+    names.retain(|name| ident(name) == Some(name.as_str()) && !RESERVED.contains(&name.as_str()));
+
+    names
+}
+
+/// Keys at the top level of an object literal, starting after the opening brace
+fn object_keys(input: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut depth = 0usize;
+    let mut expect_key = true;
+
+    let mut chars = input.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' if depth == 0 => break,
+            '}' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => expect_key = true,
+            c if expect_key && depth == 0 && !c.is_whitespace() => {
+                expect_key = false;
+                if let Some(key) = ident(&input[idx..]) {
+                    // Skip the rest of the key, so its characters are not seen as nesting
+                    for _ in 1..key.chars().count() {
+                        chars.next();
+                    }
+                    keys.push(key.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    keys
+}
+
+/// The source with comments and the contents of strings and template literals blanked out,
+/// so text inside them is not taken for code. Offsets in it are offsets in the source
+fn blank_literals(source: &str) -> String {
+    fn blank(output: &mut String, c: char) {
+        if c == '\n' {
+            output.push(c);
+        } else {
+            output.extend(std::iter::repeat_n(' ', c.len_utf8()));
+        }
+    }
+
+    let mut output = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                blank(&mut output, c);
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    blank(&mut output, c);
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                blank(&mut output, c);
+                let mut prev = ' ';
+                // The opening star can't close the comment
+                if let Some(c) = chars.next() {
+                    blank(&mut output, c);
+                }
+                for c in chars.by_ref() {
+                    blank(&mut output, c);
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '"' | '\'' | '`' => {
+                output.push(c);
+                while let Some(next) = chars.next() {
+                    if next == c {
+                        output.push(next);
+                        break;
+                    }
+
+                    blank(&mut output, next);
+
+                    if next == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            blank(&mut output, escaped);
+                        }
+                    } else if next == '\n' && c != '`' {
+                        // An unterminated string ends at the line
+                        break;
+                    }
+                }
+            }
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_outside_literals() {
+        let names = detect_exports(
+            r#"
+            // exports.commented = 1;
+            /* module.exports = { block: 1 }; */
+            const text = "exports.quoted = 1";
+            const template = `exports.template = ${1}`;
+            exports.named = 1;
+            Object.defineProperty(exports, "defined", { value: 1 });
+            "#,
+        );
+
+        assert_eq!(names.into_iter().collect::<Vec<_>>(), ["defined", "named"]);
+    }
+
+    #[test]
+    fn object_keys_outside_literals() {
+        let names = detect_exports(
+            r#"module.exports = { a: "x, y", b: 'it\'s, z', c: `w, ${v}`, /* d, */ e };"#,
+        );
+
+        assert_eq!(names.into_iter().collect::<Vec<_>>(), ["a", "b", "c", "e"]);
+    }
+}
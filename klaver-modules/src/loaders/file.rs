@@ -22,6 +22,11 @@ pub trait Transformer {
     ) -> bool {
         true
     }
+
+    /// Called when `path` is reloaded, so any state kept for it can be dropped
+    fn invalidate(&self, path: &Path) {
+        let _ = path;
+    }
 }
 
 impl Transformer for () {
@@ -103,4 +108,10 @@ impl Loader for FileLoader {
         module.meta()?.set("url", format!("file://{}", path))?;
         Ok(module)
     }
+
+    fn invalidate(&self, path: &str) {
+        for transformer in &self.transformer {
+            transformer.invalidate(Path::new(path));
+        }
+    }
 }
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use relative_path::RelativePath;
+use rquickjs::{Ctx, Module, loader::ImportAttributes};
+use tracing::trace;
+
+use crate::{Loader, Resolver, source_map::SourceMaps};
+
+#[cfg(feature = "swc")]
+use crate::{
+    loaders::{SwcCompiler, SwcCompilerOptions},
+    source_map::SourceMap,
+};
+
+struct Inner {
+    modules: RwLock<HashMap<String, Arc<str>>>,
+    #[cfg(feature = "swc")]
+    compiler: Option<SwcCompiler>,
+}
+
+/// MemoryModules is a resolver and loader for modules that only exist in memory,
+/// like scripts generated at runtime or stored in a database.
+/// Modules can be added, replaced and removed at any time. Since QuickJS caches
+/// modules by name, changes are only seen by contexts that have not imported the module yet
+#[derive(Clone)]
+pub struct MemoryModules(Arc<Inner>);
+
+impl Default for MemoryModules {
+    fn default() -> Self {
+        MemoryModules::new()
+    }
+}
+
+impl MemoryModules {
+    pub fn new() -> MemoryModules {
+        MemoryModules(Arc::new(Inner {
+            modules: RwLock::default(),
+            #[cfg(feature = "swc")]
+            compiler: None,
+        }))
+    }
+
+    /// Compile `.ts`, `.tsx` and `.jsx` modules with swc before loading them
+    #[cfg(feature = "swc")]
+    pub fn new_with(opts: SwcCompilerOptions) -> MemoryModules {
+        MemoryModules(Arc::new(Inner {
+            modules: RwLock::default(),
+            compiler: Some(SwcCompiler::new_with(opts)),
+        }))
+    }
+
+    /// Add a module, or replace it if it exists
+    pub fn insert(&self, name: impl Into<String>, source: impl Into<String>) -> &Self {
+        self.0
+            .modules
+            .write()
+            .expect("Lock")
+            .insert(name.into(), Arc::from(source.into()));
+        self
+    }
+
+    /// Remove a module. Returns `true` if it existed
+    pub fn remove(&self, name: &str) -> bool {
+        self.0.modules.write().expect("Lock").remove(name).is_some()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.modules.read().expect("Lock").contains_key(name)
+    }
+
+    pub fn clear(&self) {
+        self.0.modules.write().expect("Lock").clear();
+    }
+
+    fn get(&self, name: &str) -> Option<Arc<str>> {
+        self.0.modules.read().expect("Lock").get(name).cloned()
+    }
+}
+
+impl Resolver for MemoryModules {
+    fn resolve<'js>(
+        &self,
+        _ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<String> {
+        let full = if name.starts_with('.') {
+            match RelativePath::new(base).parent() {
+                Some(dir) => dir.join_normalized(name).to_string(),
+                None => RelativePath::new(name).normalize().to_string(),
+            }
+        } else {
+            name.to_string()
+        };
+
+        if self.contains(&full) {
+            trace!(base = %base, name = %name, path = %full, "Resolved memory module");
+            Ok(full)
+        } else {
+            Err(rquickjs::Error::new_resolving(base, name))
+        }
+    }
+}
+
+impl Loader for MemoryModules {
+    fn load<'js>(
+        &self,
+        sourcemaps: &SourceMaps,
+        ctx: &Ctx<'js>,
+        path: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        let Some(source) = self.get(path) else {
+            return Err(rquickjs::Error::new_loading(path));
+        };
+
+        #[cfg(feature = "swc")]
+        if let Some(compiler) = &self.0.compiler {
+            let compile = RelativePath::new(path)
+                .extension()
+                .map(|ext| matches!(ext, "ts" | "tsx" | "jsx"))
+                .unwrap_or(false);
+
+            if compile {
+                let result = compiler
+                    .compile_source(path, source.to_string())
+                    .map_err(|err| rquickjs::Error::new_loading_message(path, err.to_string()))?;
+
+                sourcemaps.insert(
+                    path.to_string(),
+                    SourceMap::from_iter(
+                        result
+                            .sourcemap
+                            .tokens()
+                            .map(|token| (token.get_src(), token.get_dst())),
+                    ),
+                );
+
+                return Module::declare(ctx.clone(), path, result.code);
+            }
+        }
+
+        #[cfg(not(feature = "swc"))]
+        let _ = sourcemaps;
+
+        Module::declare(ctx.clone(), path, source.as_bytes())
+    }
+}
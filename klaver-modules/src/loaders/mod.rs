@@ -1,10 +1,20 @@
+mod asset;
 mod builtin;
+mod bytecode;
+mod cjs;
 mod file;
+mod memory;
 #[cfg(feature = "swc")]
 mod swc;
+#[cfg(feature = "url")]
+mod url;
 pub use self::{
+    asset::{BytesTransformer, JsonTransformer, TextTransformer},
     builtin::BuiltinLoader,
+    bytecode::{Bundle, BundleModule, BytecodeLoader},
+    cjs::CjsTransformer,
     file::{FileLoader, Transformer},
+    memory::MemoryModules,
 };
 
 #[cfg(feature = "swc")]
@@ -12,3 +22,8 @@ pub use self::swc::{
     Compiler as SwcCompiler, CompilerOptions as SwcCompilerOptions, Decorators as SwcDecocators,
     SwcTransformer,
 };
+
+#[cfg(feature = "url")]
+pub(crate) use self::url::Redirects;
+#[cfg(feature = "url")]
+pub use self::url::{FetchedModule, Fetcher, LockFile, Prefetcher, UrlLoader};
@@ -1,6 +1,8 @@
 use anyhow::anyhow;
 use std::{path::Path, sync::Arc};
-use swc_common::{GLOBALS, Globals, Mark, source_map::DefaultSourceMapGenConfig};
+use swc_common::{
+    FileName, GLOBALS, Globals, Mark, SourceFile, source_map::DefaultSourceMapGenConfig, sync::Lrc,
+};
 use swc_ecma_ast::{EsVersion, Pass, Program};
 use swc_ecma_codegen::text_writer::JsWriter;
 use swc_ecma_parser::{Syntax, TsSyntax};
@@ -53,6 +55,7 @@ impl Decorators {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct CompilerOptions {
     pub decorators: Decorators,
     pub async_context: bool,
@@ -96,9 +99,24 @@ impl Compiler {
         }
     }
 
+    pub fn options(&self) -> &CompilerOptions {
+        &self.opts
+    }
+
     pub fn compile(&self, path: &Path) -> anyhow::Result<CodegenResult> {
         let fm = self.cm.load_file(path)?;
+        self.compile_file(fm)
+    }
+
+    /// Compile source that does not live on the file system. `name` is used in the source map
+    pub fn compile_source(&self, name: &str, source: String) -> anyhow::Result<CodegenResult> {
+        let fm = self
+            .cm
+            .new_source_file(Lrc::new(FileName::Custom(name.to_string())), source);
+        self.compile_file(fm)
+    }
 
+    fn compile_file(&self, fm: Lrc<SourceFile>) -> anyhow::Result<CodegenResult> {
         let mut errors = Vec::default();
 
         let mut program = swc_ecma_parser::parse_file_as_program(
@@ -8,6 +8,8 @@ use std::sync::Mutex;
 use klaver_core::{throw, throw_if};
 use rquickjs::{Ctx, Module};
 
+use crate::cache::{CacheEntry, CompileCache};
+use crate::environ::WeakEnviron;
 use crate::loaders::Transformer;
 use crate::source_map::{SourceMap, SourceMaps};
 
@@ -15,25 +17,83 @@ pub use self::compiler::*;
 
 pub struct SwcTransformer {
     compiler: Compiler,
-    cache: Mutex<HashMap<PathBuf, CodegenResult>>,
+    sourcemaps: Mutex<HashMap<PathBuf, swc_sourcemap::SourceMap>>,
+    keys: Mutex<HashMap<PathBuf, (CompileCache, String)>>,
 }
 
 impl SwcTransformer {
     pub fn new() -> SwcTransformer {
         SwcTransformer {
             compiler: Compiler::new(),
-            cache: Default::default(),
+            sourcemaps: Default::default(),
+            keys: Default::default(),
         }
     }
 
     pub fn new_with(opts: CompilerOptions) -> SwcTransformer {
         SwcTransformer {
             compiler: Compiler::new_with(opts),
-            cache: Default::default(),
+            sourcemaps: Default::default(),
+            keys: Default::default(),
         }
     }
 }
 
+impl SwcTransformer {
+    /// Compile `path`, reusing the output from a previous compilation of the same
+    /// source with the same compiler options.
+    /// The file is read once, so the bytes which are hashed are the bytes which are compiled
+    fn compile_cached(
+        &self,
+        ctx: &Ctx<'_>,
+        cache: &CompileCache,
+        path: &Path,
+    ) -> rquickjs::Result<(Vec<u8>, swc_sourcemap::SourceMap)> {
+        let content = throw_if!(ctx, std::fs::read(path));
+        let options = format!("{:?}", self.compiler.options());
+
+        let key = CompileCache::key([
+            content.as_slice(),
+            options.as_bytes(),
+            env!("CARGO_PKG_VERSION").as_bytes(),
+        ]);
+
+        self.keys
+            .lock()
+            .expect("Lock")
+            .insert(path.to_path_buf(), (cache.clone(), key.clone()));
+
+        if let Some(entry) = cache.get(&key) {
+            match swc_sourcemap::SourceMap::from_slice(&entry.source_map) {
+                Ok(sourcemap) => return Ok((entry.code, sourcemap)),
+                Err(err) => {
+                    tracing::warn!(path = %path.display(), error = %err, "Invalid cached source map")
+                }
+            }
+        }
+
+        let source = throw_if!(ctx, String::from_utf8(content));
+        let result = throw_if!(
+            ctx,
+            self.compiler
+                .compile_source(&path.display().to_string(), source)
+        );
+
+        let mut source_map = Vec::new();
+        throw_if!(ctx, result.sourcemap.to_writer(&mut source_map));
+
+        cache.insert(
+            &key,
+            CacheEntry {
+                code: result.code.clone(),
+                source_map,
+            },
+        );
+
+        Ok((result.code, result.sourcemap))
+    }
+}
+
 impl Transformer for SwcTransformer {
     fn transform<'js>(
         &self,
@@ -42,44 +102,61 @@ impl Transformer for SwcTransformer {
         path: &Path,
         _attributes: Option<rquickjs::loader::ImportAttributes<'js>>,
     ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
-        let result = throw_if!(ctx, self.compiler.compile(path));
+        let env = ctx
+            .userdata::<WeakEnviron>()
+            .and_then(|env| env.try_upgrade());
+
+        let (code, sourcemap) = match env.as_ref().and_then(|env| env.cache()) {
+            Some(cache) => self.compile_cached(ctx, cache, path)?,
+            None => {
+                let result = throw_if!(ctx, self.compiler.compile(path));
+                (result.code, result.sourcemap)
+            }
+        };
 
-        let source = throw_if!(ctx, String::from_utf8(result.code.clone()));
+        let source = throw_if!(ctx, String::from_utf8(code));
 
         let sourcmap = SourceMap::from_iter(
-            result
-                .sourcemap
+            sourcemap
                 .tokens()
                 .map(|token| (token.get_src(), token.get_dst())),
         );
 
         sourcemaps.insert(path.display().to_string(), sourcmap);
 
-        self.cache
+        self.sourcemaps
             .lock()
             .unwrap()
-            .insert(path.to_path_buf(), result);
+            .insert(path.to_path_buf(), sourcemap);
 
         Module::declare(ctx.clone(), path.to_string_lossy().as_ref(), source)
     }
 
     fn map(&self, path: &std::path::Path, line: usize, col: usize) -> Option<(usize, usize)> {
-        let lock = self.cache.lock().expect("Lock");
-        let entry = lock.get(path)?;
+        let lock = self.sourcemaps.lock().expect("Lock");
+        let sourcemap = lock.get(path)?;
 
-        let token = entry.sourcemap.lookup_token(line as u32, col as u32)?;
+        let token = sourcemap.lookup_token(line as u32, col as u32)?;
         let dst = token.get_src();
 
         Some((dst.0 as usize, dst.1 as usize))
     }
 
+    fn invalidate(&self, path: &Path) {
+        self.sourcemaps.lock().expect("Lock").remove(path);
+
+        if let Some((cache, key)) = self.keys.lock().expect("Lock").remove(path) {
+            cache.remove(&key);
+        }
+    }
+
     fn can_transform(
         &self,
         path: &Path,
         attributes: Option<&rquickjs::loader::ImportAttributes<'_>>,
     ) -> bool {
         if let Some(attrs) = attributes {
-            if attrs.get("swc").is_ok() {
+            if matches!(attrs.get("swc"), Ok(Some(_))) {
                 return true;
             }
         }
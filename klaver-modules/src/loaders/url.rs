@@ -0,0 +1,346 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use klaver_core::{RuntimeError, error::BoxError};
+use rquickjs::{AsyncContext, Ctx, Module, WriteOptions, loader::ImportAttributes};
+use sha2::{Digest, Sha256};
+use tracing::trace;
+
+use crate::{
+    Environ, Loader,
+    cache::to_hex,
+    loaders::bytecode::link,
+    resolvers::{UrlResolver, is_remote},
+    source_map::SourceMaps,
+};
+
+/// A module downloaded by a `Fetcher`
+pub struct FetchedModule {
+    /// The url the module was served from, after following redirects.
+    /// Imports of the module are resolved against it
+    pub url: String,
+    pub content: Vec<u8>,
+}
+
+/// Fetcher downloads remote modules for the `UrlLoader`.
+/// Loading modules is synchronous, so implementations are expected to block.
+/// Use a [`Prefetcher`] to download the static imports of a module before running it
+pub trait Fetcher {
+    fn fetch(&self, url: &str) -> Result<FetchedModule, BoxError>;
+}
+
+impl<F> Fetcher for F
+where
+    F: Fn(&str) -> Result<Vec<u8>, BoxError>,
+{
+    fn fetch(&self, url: &str) -> Result<FetchedModule, BoxError> {
+        Ok(FetchedModule {
+            url: url.to_string(),
+            content: (self)(url)?,
+        })
+    }
+}
+
+/// Final urls of redirected modules, keyed by the requested url
+pub(crate) type Redirects = Arc<RwLock<HashMap<String, String>>>;
+
+/// Urls missing from the cache while prefetching. They are recorded instead of fetched
+type Missing = Arc<Mutex<Option<BTreeSet<String>>>>;
+
+/// Integrity hashes of remote modules, stored as one `<url> <integrity>` pair per line
+pub struct LockFile {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<String, String>>,
+}
+
+impl LockFile {
+    /// Open a lock file. A missing file is treated as empty, and is created on the first insert
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<LockFile> {
+        let path = path.into();
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let entries = content
+            .lines()
+            .filter_map(|line| line.trim().split_once(' '))
+            .map(|(url, integrity)| (url.to_string(), integrity.trim().to_string()))
+            .collect();
+
+        Ok(LockFile {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, url: &str) -> Option<String> {
+        self.entries.lock().expect("Lock").get(url).cloned()
+    }
+
+    fn insert(&self, url: &str, integrity: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().expect("Lock");
+        entries.insert(url.to_string(), integrity.to_string());
+
+        let content = entries
+            .iter()
+            .map(|(url, integrity)| format!("{url} {integrity}\n"))
+            .collect::<String>();
+
+        std::fs::write(&self.path, content)
+    }
+}
+
+/// UrlLoader loads modules resolved by `UrlResolver`.
+/// Modules are cached on disk keyed by their url, and fetched only when missing from the cache.
+/// With a lock file, the content of every module is checked against its recorded hash
+pub struct UrlLoader {
+    cache_dir: PathBuf,
+    fetcher: Option<Box<dyn Fetcher + Send + Sync>>,
+    lock: Option<Arc<LockFile>>,
+    offline: bool,
+    redirects: Redirects,
+    missing: Missing,
+}
+
+impl UrlLoader {
+    pub fn new(cache_dir: impl Into<PathBuf>) -> UrlLoader {
+        UrlLoader {
+            cache_dir: cache_dir.into(),
+            fetcher: None,
+            lock: None,
+            offline: false,
+            redirects: Redirects::default(),
+            missing: Missing::default(),
+        }
+    }
+
+    pub fn fetcher<F: Fetcher + Send + Sync + 'static>(mut self, fetcher: F) -> Self {
+        self.fetcher = Some(Box::new(fetcher));
+        self
+    }
+
+    pub fn lock_file(mut self, lock: LockFile) -> Self {
+        self.lock = Some(Arc::new(lock));
+        self
+    }
+
+    /// Never fetch, and only serve modules from the cache
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// The resolver for the modules of this loader.
+    /// It resolves the imports of redirected modules against the url they were served from
+    pub fn resolver(&self) -> UrlResolver {
+        UrlResolver::with_redirects(self.redirects.clone())
+    }
+
+    /// Downloads modules into the cache of this loader, see [`Prefetcher::prefetch`]
+    pub fn prefetcher(&self) -> Prefetcher {
+        Prefetcher {
+            cache_dir: self.cache_dir.clone(),
+            lock: self.lock.clone(),
+            missing: self.missing.clone(),
+        }
+    }
+
+    fn read(&self, url: &str) -> Result<FetchedModule, BoxError> {
+        let path = cache_path(&self.cache_dir, url);
+
+        match std::fs::read(&path) {
+            Ok(content) => {
+                let url = std::fs::read_to_string(path.with_extension("url"))
+                    .unwrap_or_else(|_| url.to_string());
+                return Ok(FetchedModule { url, content });
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        if let Some(missing) = self.missing.lock().expect("Lock").as_mut() {
+            missing.insert(url.to_string());
+            return Err(format!("{url} is not cached, and is being prefetched").into());
+        }
+
+        if self.offline {
+            return Err(format!("{url} is not cached, and network access is disabled").into());
+        }
+
+        let Some(fetcher) = &self.fetcher else {
+            return Err(format!("{url} is not cached, and no fetcher is configured").into());
+        };
+
+        trace!(url = %url, "Fetching module");
+
+        let module = fetcher.fetch(url)?;
+        store(&self.cache_dir, url, &module)?;
+
+        Ok(module)
+    }
+}
+
+impl Loader for UrlLoader {
+    fn load<'js>(
+        &self,
+        _sourcemaps: &SourceMaps,
+        ctx: &Ctx<'js>,
+        path: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, rquickjs::module::Declared>> {
+        if !is_remote(path) {
+            return Err(rquickjs::Error::new_loading(path));
+        }
+
+        // Cached modules are checked too, so a tampered cache is caught
+        let module = self
+            .read(path)
+            .and_then(|module| {
+                verify(self.lock.as_deref(), path, &module.content)?;
+                Ok(module)
+            })
+            .map_err(|err| rquickjs::Error::new_loading_message(path, err.to_string()))?;
+
+        // The module keeps the requested url as its name, so every import of it shares one instance
+        if module.url != path {
+            self.redirects
+                .write()
+                .expect("Lock")
+                .insert(path.to_string(), module.url.clone());
+        }
+
+        let declared = Module::declare(ctx.clone(), path, module.content)?;
+        declared.meta()?.set("url", module.url)?;
+
+        Ok(declared)
+    }
+}
+
+/// Downloads the url modules imported by a module into the cache of a `UrlLoader`, ahead of running it.
+/// Loading is synchronous, so a module missing from the cache blocks the runtime while the
+/// `Fetcher` downloads it. Prefetching does the downloads asynchronously instead.
+/// Dynamic imports are not seen by the prefetcher, and are still fetched when they are loaded
+#[derive(Clone)]
+pub struct Prefetcher {
+    cache_dir: PathBuf,
+    lock: Option<Arc<LockFile>>,
+    missing: Missing,
+}
+
+impl Prefetcher {
+    /// Resolve the static imports of `entry` with the loaders of `env`, without evaluating them,
+    /// and download the url modules missing from the cache with `fetch`. Returns the number of
+    /// downloaded modules.
+    /// While prefetching, loading a module missing from the cache fails instead of fetching it,
+    /// so nothing else should run in `env` at the same time
+    pub async fn prefetch<F, Fut>(
+        &self,
+        env: &Environ,
+        entry: &str,
+        fetch: F,
+    ) -> Result<usize, RuntimeError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<FetchedModule, BoxError>>,
+    {
+        let mut fetched = 0;
+
+        loop {
+            let missing = self.missing(env, entry).await?;
+            if missing.is_empty() {
+                return Ok(fetched);
+            }
+
+            for url in missing {
+                trace!(url = %url, "Prefetching module");
+
+                let module = fetch(url.clone()).await?;
+                verify(self.lock.as_deref(), &url, &module.content)?;
+                store(&self.cache_dir, &url, &module).map_err(RuntimeError::new)?;
+
+                fetched += 1;
+            }
+        }
+    }
+
+    /// Link `entry` in a new runtime, recording the modules missing from the cache.
+    /// Linking stops at the first missing module, so the graph is walked again after downloading it
+    async fn missing(&self, env: &Environ, entry: &str) -> Result<BTreeSet<String>, RuntimeError> {
+        let runtime = env.create_runtime().await?;
+        let context = AsyncContext::full(&runtime).await?;
+        env.init(&context).await?;
+
+        *self.missing.lock().expect("Lock") = Some(BTreeSet::new());
+
+        context
+            .with(|ctx| {
+                // Any other error is reported when the entry runs
+                if let Err(err) = link_entry(&ctx, env, entry) {
+                    trace!(entry = %entry, error = %err, "Linking stopped while prefetching");
+                }
+            })
+            .await;
+
+        Ok(self
+            .missing
+            .lock()
+            .expect("Lock")
+            .take()
+            .unwrap_or_default())
+    }
+}
+
+fn link_entry(ctx: &Ctx<'_>, env: &Environ, entry: &str) -> rquickjs::Result<()> {
+    let mut modules = env.modules().clone();
+
+    let path = rquickjs::loader::Resolver::resolve(&mut modules, ctx, "", entry, None)?;
+    let module = rquickjs::loader::Loader::load(&mut modules, ctx, &path, None)?;
+
+    link(ctx, &module.write(WriteOptions::default())?)
+}
+
+fn cache_path(cache_dir: &Path, url: &str) -> PathBuf {
+    cache_dir.join(to_hex(&Sha256::digest(url.as_bytes())))
+}
+
+/// Write `module` to the cache, with the url it was served from when it was redirected
+fn store(cache_dir: &Path, url: &str, module: &FetchedModule) -> io::Result<()> {
+    let path = cache_path(cache_dir, url);
+
+    std::fs::create_dir_all(cache_dir)?;
+    std::fs::write(&path, &module.content)?;
+
+    if module.url != url {
+        std::fs::write(path.with_extension("url"), &module.url)?;
+    }
+
+    Ok(())
+}
+
+fn verify(lock: Option<&LockFile>, url: &str, content: &[u8]) -> Result<(), BoxError> {
+    let Some(lock) = lock else {
+        return Ok(());
+    };
+
+    let integrity = format!("sha256-{}", to_hex(&Sha256::digest(content)));
+
+    match lock.get(url) {
+        Some(expected) if expected == integrity => Ok(()),
+        Some(expected) => Err(format!(
+            "Integrity check failed for {url}: expected {expected}, got {integrity}"
+        )
+        .into()),
+        None => Ok(lock.insert(url, &integrity)?),
+    }
+}
@@ -1,4 +1,5 @@
-use crate::loader::Resolver;
+use crate::loader::{Resolver, reason};
+use klaver_core::error::ResolveAttempt;
 pub use oxc_resolver::ResolveOptions;
 use rquickjs::loader::ImportAttributes;
 use std::{
@@ -24,7 +25,37 @@ impl FileResolver {
     }
 }
 
-impl FileResolver {}
+impl FileResolver {
+    fn parent<'a>(&'a self, base: &'a str) -> Cow<'a, Path> {
+        if base.is_empty() {
+            self.work_dir.as_path().into()
+        } else {
+            let path = Path::new(base).parent().expect("parent");
+            if !path.is_absolute() {
+                self.work_dir.join(path).into()
+            } else {
+                path.into()
+            }
+        }
+    }
+
+    /// Paths looked up while resolving `name`, which did not exist
+    fn candidates(&self, base: &str, name: &str) -> Vec<String> {
+        let mut context = oxc_resolver::ResolveContext::default();
+        let _ = self
+            .resolver
+            .resolve_with_context(self.parent(base), name, None, &mut context);
+
+        let mut candidates = context
+            .missing_dependencies
+            .into_iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        candidates
+    }
+}
 
 impl Resolver for FileResolver {
     fn resolve<'js>(
@@ -34,16 +65,7 @@ impl Resolver for FileResolver {
         name: &str,
         _attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<String> {
-        let parent: Cow<'_, Path> = if base.is_empty() {
-            self.work_dir.as_path().into()
-        } else {
-            let path = Path::new(base).parent().expect("parent");
-            if !path.is_absolute() {
-                self.work_dir.join(path).into()
-            } else {
-                path.into()
-            }
-        };
+        let parent = self.parent(base);
 
         trace!(parent = ?parent, base = %base, path = %name, "Resolving path");
 
@@ -56,4 +78,21 @@ impl Resolver for FileResolver {
 
         Ok(resolution.full_path().display().to_string())
     }
+
+    fn name(&self) -> String {
+        format!("FileResolver({})", self.work_dir.display())
+    }
+
+    fn try_resolve<'js>(
+        &self,
+        ctx: &rquickjs::Ctx<'js>,
+        base: &str,
+        name: &str,
+        attributes: Option<ImportAttributes<'js>>,
+    ) -> Result<String, ResolveAttempt> {
+        self.resolve(ctx, base, name, attributes).map_err(|err| {
+            ResolveAttempt::new(self.name(), reason(&err))
+                .with_candidates(self.candidates(base, name))
+        })
+    }
 }
@@ -0,0 +1,193 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+type SpecifierMap = Vec<(String, String)>;
+
+/// ImportMapResolver rewrites specifiers with a standard import map, before
+/// they are handed to the resolvers, so bare names can be aliased to files.
+/// Relative addresses are resolved against the directory of the import map
+#[derive(Debug, Default, Clone)]
+pub struct ImportMapResolver {
+    imports: SpecifierMap,
+    scopes: Vec<(String, SpecifierMap)>,
+}
+
+impl ImportMapResolver {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<ImportMapResolver> {
+        let path = path.as_ref().canonicalize()?;
+        let content = std::fs::read_to_string(&path)?;
+        let dir = path.parent().unwrap_or(Path::new("/"));
+
+        ImportMapResolver::from_json(&content, dir)
+    }
+
+    pub fn from_json(json: &str, dir: &Path) -> io::Result<ImportMapResolver> {
+        let value: Value = serde_json::from_str(json).map_err(invalid)?;
+
+        let Value::Object(value) = value else {
+            return Err(invalid("Import map must be an object"));
+        };
+
+        let imports = match value.get("imports") {
+            Some(Value::Object(imports)) => specifier_map(imports, dir)?,
+            Some(_) => return Err(invalid("'imports' must be an object")),
+            None => Vec::new(),
+        };
+
+        let mut scopes = match value.get("scopes") {
+            Some(Value::Object(scopes)) => scopes
+                .iter()
+                .map(|(prefix, map)| match map {
+                    Value::Object(map) => Ok((address(prefix, dir), specifier_map(map, dir)?)),
+                    _ => Err(invalid(format!("Scope '{prefix}' must be an object"))),
+                })
+                .collect::<io::Result<Vec<_>>>()?,
+            Some(_) => return Err(invalid("'scopes' must be an object")),
+            None => Vec::new(),
+        };
+
+        // The most specific scope wins
+        scopes.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        Ok(ImportMapResolver { imports, scopes })
+    }
+
+    /// Rewrite `specifier` imported from `base`. Returns `None` when the import map has no entry for it
+    pub fn rewrite(&self, base: &str, specifier: &str) -> Option<String> {
+        self.scopes
+            .iter()
+            .filter(|(prefix, _)| base.starts_with(prefix.as_str()))
+            .find_map(|(_, map)| lookup(map, specifier))
+            .or_else(|| lookup(&self.imports, specifier))
+    }
+}
+
+fn lookup(map: &SpecifierMap, specifier: &str) -> Option<String> {
+    map.iter().find_map(|(key, target)| {
+        if key == specifier {
+            Some(target.clone())
+        } else if key.ends_with('/') {
+            specifier
+                .strip_prefix(key.as_str())
+                .map(|rest| format!("{target}{rest}"))
+        } else {
+            None
+        }
+    })
+}
+
+fn specifier_map(map: &Map<String, Value>, dir: &Path) -> io::Result<SpecifierMap> {
+    let mut out = map
+        .iter()
+        .map(|(key, target)| match target {
+            Value::String(target) => Ok((key.clone(), address(target, dir))),
+            _ => Err(invalid(format!("Address of '{key}' must be a string"))),
+        })
+        .collect::<io::Result<SpecifierMap>>()?;
+
+    // The longest prefix wins
+    out.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+    Ok(out)
+}
+
+/// Resolve relative addresses against the import map directory
+fn address(address: &str, dir: &Path) -> String {
+    if !(address.starts_with("./") || address.starts_with("../")) {
+        return address.to_string();
+    }
+
+    let mut path = PathBuf::from(dir);
+    for component in Path::new(address).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            component => path.push(component),
+        }
+    }
+
+    let mut out = path.to_string_lossy().to_string();
+    if address.ends_with('/') && !out.ends_with('/') {
+        out.push('/');
+    }
+
+    out
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import_map() -> ImportMapResolver {
+        ImportMapResolver::from_json(
+            r#"{
+                "imports": {
+                    "lodash": "https://cdn.example.com/lodash.js",
+                    "@app/": "./src/",
+                    "@app/config": "./config/default.js"
+                },
+                "scopes": {
+                    "./src/legacy/": { "lodash": "./vendor/lodash.js" }
+                }
+            }"#,
+            Path::new("/project"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rewrite_imports() {
+        let map = import_map();
+
+        assert_eq!(
+            map.rewrite("/project/main.js", "lodash").as_deref(),
+            Some("https://cdn.example.com/lodash.js")
+        );
+        assert_eq!(
+            map.rewrite("/project/main.js", "@app/util/index.js")
+                .as_deref(),
+            Some("/project/src/util/index.js")
+        );
+        // Exact matches take precedence over prefixes
+        assert_eq!(
+            map.rewrite("/project/main.js", "@app/config").as_deref(),
+            Some("/project/config/default.js")
+        );
+        assert_eq!(map.rewrite("/project/main.js", "./local.js"), None);
+    }
+
+    #[test]
+    fn rewrite_scoped_imports() {
+        let map = import_map();
+
+        assert_eq!(
+            map.rewrite("/project/src/legacy/index.js", "lodash")
+                .as_deref(),
+            Some("/project/vendor/lodash.js")
+        );
+        assert_eq!(
+            map.rewrite("/project/src/legacy/index.js", "@app/config")
+                .as_deref(),
+            Some("/project/config/default.js")
+        );
+    }
+
+    #[test]
+    fn reject_invalid_maps() {
+        assert!(ImportMapResolver::from_json("[]", Path::new("/")).is_err());
+        assert!(ImportMapResolver::from_json(r#"{ "imports": [] }"#, Path::new("/")).is_err());
+        assert!(
+            ImportMapResolver::from_json(r#"{ "imports": { "a": 1 } }"#, Path::new("/")).is_err()
+        );
+    }
+}
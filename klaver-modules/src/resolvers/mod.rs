@@ -1,8 +1,16 @@
 mod builtin;
+mod import_map;
 
 #[cfg(feature = "file-resolver")]
 mod file;
+#[cfg(feature = "url")]
+mod url;
 
-pub use self::builtin::BuiltinResolver;
 #[cfg(feature = "file-resolver")]
 pub use self::file::{FileResolver, ResolveOptions};
+#[cfg(feature = "url")]
+pub use self::url::UrlResolver;
+pub use self::{builtin::BuiltinResolver, import_map::ImportMapResolver};
+
+#[cfg(feature = "url")]
+pub(crate) use self::url::is_remote;
@@ -0,0 +1,55 @@
+use rquickjs::{Ctx, loader::ImportAttributes};
+use tracing::trace;
+use url::Url;
+
+use crate::{loader::Resolver, loaders::Redirects};
+
+pub(crate) fn is_remote(specifier: &str) -> bool {
+    specifier.starts_with("https://") || specifier.starts_with("http://")
+}
+
+/// UrlResolver resolves `http://` and `https://` specifiers, and relative or
+/// absolute paths imported from a remote module, like `/react@18/index.js` on esm.sh.
+/// Get it from `UrlLoader::resolver`, so imports of redirected modules are resolved
+/// against the url they were served from
+#[derive(Debug, Default, Clone)]
+pub struct UrlResolver {
+    redirects: Redirects,
+}
+
+impl UrlResolver {
+    pub(crate) fn with_redirects(redirects: Redirects) -> UrlResolver {
+        UrlResolver { redirects }
+    }
+}
+
+impl Resolver for UrlResolver {
+    fn resolve<'js>(
+        &self,
+        _ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<String> {
+        let redirected = self.redirects.read().expect("Lock").get(base).cloned();
+        let base = redirected.as_deref().unwrap_or(base);
+
+        let url = if is_remote(name) {
+            Url::parse(name).ok()
+        } else if is_remote(base)
+            && (name.starts_with("./") || name.starts_with("../") || name.starts_with('/'))
+        {
+            Url::parse(base).and_then(|base| base.join(name)).ok()
+        } else {
+            None
+        };
+
+        match url {
+            Some(url) => {
+                trace!(base = %base, name = %name, url = %url, "Resolved url module");
+                Ok(url.into())
+            }
+            None => Err(rquickjs::Error::new_resolving(base, name)),
+        }
+    }
+}
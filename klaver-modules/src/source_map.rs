@@ -15,6 +15,14 @@ impl SourceMaps {
         self.source_maps.write().expect("Lock").insert(path, map);
     }
 
+    pub fn get(&self, path: &str) -> Option<SourceMap> {
+        self.source_maps.read().expect("Lock").get(path).cloned()
+    }
+
+    pub fn remove(&self, path: &str) -> Option<SourceMap> {
+        self.source_maps.write().expect("Lock").remove(path)
+    }
+
     pub fn lookup(&self, path: &str, line: u32, col: u32) -> Option<(u32, u32)> {
         let lock = self.source_maps.read().expect("Lock");
 
@@ -28,7 +36,7 @@ impl SourceMaps {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Mapping {
     pub src_line: u32,
     pub src_col: u32,
@@ -36,11 +44,17 @@ pub struct Mapping {
     pub dst_col: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SourceMap {
     map: Vec<Mapping>,
 }
 
+impl SourceMap {
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.map
+    }
+}
+
 impl FromIterator<((u32, u32), (u32, u32))> for SourceMap {
     fn from_iter<T: IntoIterator<Item = ((u32, u32), (u32, u32))>>(iter: T) -> Self {
         SourceMap {
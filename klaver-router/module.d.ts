@@ -0,0 +1,27 @@
+export interface RouteContext {
+  params: Record<string, string>;
+}
+
+export type RouteHandler = (
+  req: Request,
+  context: RouteContext,
+) => Response | Promise<Response>;
+
+export type Next = (req?: Request) => Promise<Response>;
+
+export type Middleware = (
+  req: Request,
+  next: Next,
+) => Response | Promise<Response>;
+
+export class Router {
+  route(method: string, path: string, handler: RouteHandler): void;
+  get(path: string, handler: RouteHandler): void;
+  post(path: string, handler: RouteHandler): void;
+  put(path: string, handler: RouteHandler): void;
+  patch(path: string, handler: RouteHandler): void;
+  delete(path: string, handler: RouteHandler): void;
+  any(path: string, handler: RouteHandler): void;
+  use(middleware: Middleware): void;
+  fetch(req: Request): Promise<Response>;
+}
@@ -0,0 +1,9 @@
+mod module;
+mod router;
+mod tree;
+
+pub use self::{
+    module::Module,
+    router::Router,
+    tree::{Match, RouteError, RouteTree},
+};
@@ -0,0 +1,23 @@
+use klaver_modules::module_info;
+use rquickjs::{Class, class::JsClass, module::ModuleDef};
+
+use crate::router::Router;
+
+pub struct Module;
+
+module_info!("@klaver/router" @types: include_str!("../module.d.ts") => Module);
+
+impl ModuleDef for Module {
+    fn declare<'js>(decl: &rquickjs::module::Declarations<'js>) -> rquickjs::Result<()> {
+        decl.declare(Router::NAME)?;
+        Ok(())
+    }
+
+    fn evaluate<'js>(
+        ctx: &rquickjs::Ctx<'js>,
+        exports: &rquickjs::module::Exports<'js>,
+    ) -> rquickjs::Result<()> {
+        exports.export(Router::NAME, Class::<Router>::create_constructor(ctx)?)?;
+        Ok(())
+    }
+}
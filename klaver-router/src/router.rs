@@ -0,0 +1,307 @@
+use std::rc::Rc;
+
+use futures::future::LocalBoxFuture;
+use http::{StatusCode, Uri, header::ALLOW};
+use klaver_core::{throw, throw_if, value::StringRef};
+use klaver_wintertc::fetch::{Body, Method, Request, Response};
+use rquickjs::{
+    Class, Ctx, Function, JsLifetime, Object, Value,
+    class::Trace,
+    prelude::{Async, Opt, This},
+};
+
+use crate::tree::RouteTree;
+
+#[derive(Clone)]
+struct Route<'js> {
+    // None matches any method
+    method: Option<http::Method>,
+    handler: Function<'js>,
+}
+
+impl<'js> Route<'js> {
+    fn accepts(&self, method: &http::Method) -> bool {
+        match &self.method {
+            Some(m) => m == method,
+            None => true,
+        }
+    }
+}
+
+#[rquickjs::class]
+pub struct Router<'js> {
+    tree: RouteTree<Route<'js>>,
+    middlewares: Vec<Function<'js>>,
+}
+
+unsafe impl<'js> JsLifetime<'js> for Router<'js> {
+    type Changed<'to> = Router<'to>;
+}
+
+impl<'js> Trace<'js> for Router<'js> {
+    fn trace<'a>(&self, tracer: rquickjs::class::Tracer<'a, 'js>) {
+        self.tree.visit(|route| route.handler.trace(tracer));
+        self.middlewares.trace(tracer);
+    }
+}
+
+impl<'js> Router<'js> {
+    fn route_inner(
+        &mut self,
+        ctx: &Ctx<'js>,
+        method: Option<http::Method>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        throw_if!(
+            ctx,
+            self.tree.insert(path.as_str(), Route { method, handler })
+        );
+        Ok(())
+    }
+
+    fn resolve(
+        &self,
+        ctx: &Ctx<'js>,
+        req: &Class<'js, Request<'js>>,
+    ) -> rquickjs::Result<Chain<'js>> {
+        let Method(method) = req.get("method")?;
+        let url: String = req.get("url")?;
+        let uri = throw_if!(@type ctx, url.parse::<Uri>());
+
+        // A route with another method doesn't stop less specific routes from matching
+        let endpoint = match self.tree.find(uri.path(), |route| route.accepts(&method)) {
+            Some(found) => Endpoint::Route {
+                handler: found.value().handler.clone(),
+                params: found
+                    .params()
+                    .map(|(name, value)| {
+                        let value = urlencoding::decode(value)
+                            .map(|value| value.into_owned())
+                            .unwrap_or_else(|_| value.to_string());
+                        (name.to_string(), value)
+                    })
+                    .collect(),
+            },
+            None => {
+                let routes = self.tree.matches(uri.path());
+                if routes.is_empty() {
+                    Endpoint::NotFound
+                } else {
+                    let mut allow = Vec::<&str>::new();
+                    for method in routes.iter().filter_map(|route| route.method.as_ref()) {
+                        if !allow.contains(&method.as_str()) {
+                            allow.push(method.as_str());
+                        }
+                    }
+                    Endpoint::MethodNotAllowed(allow.join(", "))
+                }
+            }
+        };
+
+        Ok(Chain {
+            middlewares: self.middlewares.clone().into(),
+            endpoint: Rc::new(endpoint),
+        })
+    }
+}
+
+#[rquickjs::methods]
+impl<'js> Router<'js> {
+    #[qjs(constructor)]
+    pub fn new() -> Router<'js> {
+        Router {
+            tree: RouteTree::new(),
+            middlewares: Vec::default(),
+        }
+    }
+
+    pub fn route(
+        &mut self,
+        ctx: Ctx<'js>,
+        method: StringRef<'js>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        let method = match method.as_str() {
+            "*" => None,
+            method => Some(throw_if!(
+                @type ctx,
+                http::Method::from_bytes(method.to_uppercase().as_bytes())
+            )),
+        };
+        self.route_inner(&ctx, method, path, handler)
+    }
+
+    pub fn get(
+        &mut self,
+        ctx: Ctx<'js>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        self.route_inner(&ctx, Some(http::Method::GET), path, handler)
+    }
+
+    pub fn post(
+        &mut self,
+        ctx: Ctx<'js>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        self.route_inner(&ctx, Some(http::Method::POST), path, handler)
+    }
+
+    pub fn put(
+        &mut self,
+        ctx: Ctx<'js>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        self.route_inner(&ctx, Some(http::Method::PUT), path, handler)
+    }
+
+    pub fn patch(
+        &mut self,
+        ctx: Ctx<'js>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        self.route_inner(&ctx, Some(http::Method::PATCH), path, handler)
+    }
+
+    pub fn delete(
+        &mut self,
+        ctx: Ctx<'js>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        self.route_inner(&ctx, Some(http::Method::DELETE), path, handler)
+    }
+
+    pub fn any(
+        &mut self,
+        ctx: Ctx<'js>,
+        path: StringRef<'js>,
+        handler: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        self.route_inner(&ctx, None, path, handler)
+    }
+
+    #[qjs(rename = "use")]
+    pub fn middleware(&mut self, middleware: Function<'js>) {
+        self.middlewares.push(middleware);
+    }
+
+    pub async fn fetch(
+        This(this): This<Class<'js, Self>>,
+        ctx: Ctx<'js>,
+        req: Class<'js, Request<'js>>,
+    ) -> rquickjs::Result<Class<'js, Response<'js>>> {
+        // Release the borrow before calling into script, so handlers are free to add routes
+        let chain = this.borrow().resolve(&ctx, &req)?;
+        chain.run(ctx, 0, req).await
+    }
+}
+
+enum Endpoint<'js> {
+    Route {
+        handler: Function<'js>,
+        params: Vec<(String, String)>,
+    },
+    MethodNotAllowed(String),
+    NotFound,
+}
+
+impl<'js> Endpoint<'js> {
+    async fn call(
+        &self,
+        ctx: &Ctx<'js>,
+        req: Class<'js, Request<'js>>,
+    ) -> rquickjs::Result<Class<'js, Response<'js>>> {
+        let (status, allow) = match self {
+            Endpoint::Route { handler, params } => {
+                let object = Object::new(ctx.clone())?;
+                for (name, value) in params {
+                    object.set(name.as_str(), value.as_str())?;
+                }
+
+                let context = Object::new(ctx.clone())?;
+                context.set("params", object)?;
+
+                let ret = handler.call::<_, Value>((req, context))?;
+                return into_response(ctx, ret).await;
+            }
+            Endpoint::MethodNotAllowed(allow) => (StatusCode::METHOD_NOT_ALLOWED, Some(allow)),
+            Endpoint::NotFound => (StatusCode::NOT_FOUND, None),
+        };
+
+        let mut builder = http::Response::builder().status(status);
+        if let Some(allow) = allow {
+            builder = builder.header(ALLOW, allow.as_str());
+        }
+
+        let resp = throw_if!(ctx, builder.body(Body::empty()));
+
+        Class::instance(ctx.clone(), Response::from_native(ctx, resp)?)
+    }
+}
+
+#[derive(Clone)]
+struct Chain<'js> {
+    middlewares: Rc<[Function<'js>]>,
+    endpoint: Rc<Endpoint<'js>>,
+}
+
+impl<'js> Chain<'js> {
+    fn run(
+        self,
+        ctx: Ctx<'js>,
+        index: usize,
+        req: Class<'js, Request<'js>>,
+    ) -> LocalBoxFuture<'js, rquickjs::Result<Class<'js, Response<'js>>>> {
+        Box::pin(async move {
+            let Some(middleware) = self.middlewares.get(index).cloned() else {
+                return self.endpoint.call(&ctx, req).await;
+            };
+
+            let next = self.next(&ctx, index + 1, req.clone())?;
+            let ret = middleware.call::<_, Value>((req, next))?;
+
+            into_response(&ctx, ret).await
+        })
+    }
+
+    fn next(
+        &self,
+        ctx: &Ctx<'js>,
+        index: usize,
+        req: Class<'js, Request<'js>>,
+    ) -> rquickjs::Result<Function<'js>> {
+        let chain = self.clone();
+        Function::new(
+            ctx.clone(),
+            Async(
+                move |ctx: Ctx<'js>, Opt(next): Opt<Class<'js, Request<'js>>>| {
+                    // Middlewares can pass on a new request, otherwise the current one is forwarded
+                    let req = next.unwrap_or_else(|| req.clone());
+                    chain.clone().run(ctx, index, req)
+                },
+            ),
+        )
+    }
+}
+
+async fn into_response<'js>(
+    ctx: &Ctx<'js>,
+    mut ret: Value<'js>,
+) -> rquickjs::Result<Class<'js, Response<'js>>> {
+    if let Some(promise) = ret.clone().into_promise() {
+        ret = promise.into_future().await?;
+    }
+
+    let Ok(resp) = Class::<Response>::from_value(&ret) else {
+        throw!(@type ctx, "Expected handler to return a Response")
+    };
+
+    Ok(resp)
+}
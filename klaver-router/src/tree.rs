@@ -0,0 +1,342 @@
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    EmptyParam(String),
+    WildcardNotLast(String),
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyParam(path) => write!(f, "Route '{path}' has a parameter without a name"),
+            Self::WildcardNotLast(path) => {
+                write!(
+                    f,
+                    "Route '{path}' has a wildcard that is not the last segment"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+enum Segment<'a> {
+    Static(&'a str),
+    Param(&'a str),
+    Wildcard(&'a str),
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn parse(path: &str) -> Result<Vec<Segment<'_>>, RouteError> {
+    let mut output = Vec::new();
+    let mut iter = segments(path).peekable();
+
+    while let Some(segment) = iter.next() {
+        let segment = if let Some(name) = segment.strip_prefix(':') {
+            if name.is_empty() {
+                return Err(RouteError::EmptyParam(path.to_string()));
+            }
+            Segment::Param(name)
+        } else if let Some(name) = segment.strip_prefix('*') {
+            if iter.peek().is_some() {
+                return Err(RouteError::WildcardNotLast(path.to_string()));
+            }
+            Segment::Wildcard(if name.is_empty() { "*" } else { name })
+        } else {
+            Segment::Static(segment)
+        };
+
+        output.push(segment);
+    }
+
+    Ok(output)
+}
+
+struct Leaf<T> {
+    names: Vec<String>,
+    value: T,
+}
+
+struct Node<T> {
+    statics: BTreeMap<String, Node<T>>,
+    param: Option<Box<Node<T>>>,
+    wildcard: Vec<Leaf<T>>,
+    leaves: Vec<Leaf<T>>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Node {
+            statics: BTreeMap::default(),
+            param: None,
+            wildcard: Vec::default(),
+            leaves: Vec::default(),
+        }
+    }
+}
+
+impl<T> Node<T> {
+    /// Calls `f` with every set of leaves matching `path`, in order of precedence,
+    /// until it returns a value
+    fn find<'a, R>(
+        &'a self,
+        path: &[&str],
+        captures: &mut Vec<String>,
+        f: &mut impl FnMut(&'a [Leaf<T>], &[String]) -> Option<R>,
+    ) -> Option<R> {
+        let Some((first, rest)) = path.split_first() else {
+            if !self.leaves.is_empty()
+                && let Some(found) = f(&self.leaves, captures)
+            {
+                return Some(found);
+            }
+            if !self.wildcard.is_empty() {
+                captures.push(String::new());
+                if let Some(found) = f(&self.wildcard, captures) {
+                    return Some(found);
+                }
+                captures.pop();
+            }
+            return None;
+        };
+
+        if let Some(found) = self
+            .statics
+            .get(*first)
+            .and_then(|child| child.find(rest, captures, f))
+        {
+            return Some(found);
+        }
+
+        if let Some(child) = &self.param {
+            let len = captures.len();
+            captures.push(first.to_string());
+            if let Some(found) = child.find(rest, captures, f) {
+                return Some(found);
+            }
+            captures.truncate(len);
+        }
+
+        if !self.wildcard.is_empty() {
+            captures.push(path.join("/"));
+            if let Some(found) = f(&self.wildcard, captures) {
+                return Some(found);
+            }
+            captures.pop();
+        }
+
+        None
+    }
+
+    fn visit<'a>(&'a self, f: &mut impl FnMut(&'a T)) {
+        self.leaves
+            .iter()
+            .chain(self.wildcard.iter())
+            .for_each(|leaf| f(&leaf.value));
+
+        for child in self.statics.values() {
+            child.visit(f);
+        }
+
+        if let Some(child) = &self.param {
+            child.visit(f);
+        }
+    }
+}
+
+/// A path tree supporting static segments, `:param` segments and a trailing `*` wildcard.
+///
+/// Static segments take precedence over params, which take precedence over wildcards.
+/// Several values can be registered on the same path, they are returned in insertion order.
+pub struct RouteTree<T> {
+    root: Node<T>,
+}
+
+impl<T> Default for RouteTree<T> {
+    fn default() -> Self {
+        RouteTree {
+            root: Node::default(),
+        }
+    }
+}
+
+impl<T> RouteTree<T> {
+    pub fn new() -> RouteTree<T> {
+        RouteTree::default()
+    }
+
+    pub fn insert(&mut self, path: &str, value: T) -> Result<(), RouteError> {
+        let mut node = &mut self.root;
+        let mut names = Vec::new();
+
+        for segment in parse(path)? {
+            match segment {
+                Segment::Static(segment) => {
+                    node = node.statics.entry(segment.to_string()).or_default();
+                }
+                Segment::Param(name) => {
+                    names.push(name.to_string());
+                    node = node.param.get_or_insert_with(Default::default);
+                }
+                Segment::Wildcard(name) => {
+                    names.push(name.to_string());
+                    node.wildcard.push(Leaf { names, value });
+                    return Ok(());
+                }
+            }
+        }
+
+        node.leaves.push(Leaf { names, value });
+
+        Ok(())
+    }
+
+    /// Returns the first value matching `path` accepted by `predicate`, in order of precedence.
+    /// Paths without an accepted value are skipped, so a less specific path can still match
+    pub fn find(&self, path: &str, mut predicate: impl FnMut(&T) -> bool) -> Option<Match<'_, T>> {
+        let path = segments(path).collect::<Vec<_>>();
+        self.root
+            .find(&path, &mut Vec::new(), &mut |leaves, captures| {
+                let leaf = leaves.iter().find(|leaf| predicate(&leaf.value))?;
+                Some(Match {
+                    leaf,
+                    captures: captures.to_vec(),
+                })
+            })
+    }
+
+    /// Returns every value matching `path`
+    pub fn matches(&self, path: &str) -> Vec<&T> {
+        let path = segments(path).collect::<Vec<_>>();
+        let mut output = Vec::new();
+        self.root.find(&path, &mut Vec::new(), &mut |leaves, _| {
+            output.extend(leaves.iter().map(|leaf| &leaf.value));
+            None::<()>
+        });
+        output
+    }
+
+    pub fn visit<'a>(&'a self, mut f: impl FnMut(&'a T)) {
+        self.root.visit(&mut f)
+    }
+}
+
+pub struct Match<'a, T> {
+    leaf: &'a Leaf<T>,
+    captures: Vec<String>,
+}
+
+impl<'a, T> Match<'a, T> {
+    pub fn value(&self) -> &'a T {
+        &self.leaf.value
+    }
+
+    /// The params captured for the value
+    pub fn params(&self) -> impl Iterator<Item = (&'a str, &str)> {
+        self.leaf
+            .names
+            .iter()
+            .map(|name| name.as_str())
+            .zip(self.captures.iter().map(|value| value.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(tree: &RouteTree<u32>, path: &str) -> Option<(u32, Vec<(String, String)>)> {
+        lookup_by(tree, path, |_| true)
+    }
+
+    fn lookup_by(
+        tree: &RouteTree<u32>,
+        path: &str,
+        predicate: impl FnMut(&u32) -> bool,
+    ) -> Option<(u32, Vec<(String, String)>)> {
+        let found = tree.find(path, predicate)?;
+        let params = found
+            .params()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Some((*found.value(), params))
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn match_routes() {
+        let mut tree = RouteTree::new();
+        tree.insert("/", 0).unwrap();
+        tree.insert("/users", 1).unwrap();
+        tree.insert("/users/me", 2).unwrap();
+        tree.insert("/users/:id", 3).unwrap();
+        tree.insert("/users/:id/posts/:post", 4).unwrap();
+        tree.insert("/files/*path", 5).unwrap();
+        tree.insert("/users/:name/*", 6).unwrap();
+
+        assert_eq!(lookup(&tree, "/"), Some((0, params(&[]))));
+        assert_eq!(lookup(&tree, "/users/"), Some((1, params(&[]))));
+        assert_eq!(lookup(&tree, "/users/me"), Some((2, params(&[]))));
+        assert_eq!(
+            lookup(&tree, "/users/42"),
+            Some((3, params(&[("id", "42")])))
+        );
+        assert_eq!(
+            lookup(&tree, "/users/42/posts/7"),
+            Some((4, params(&[("id", "42"), ("post", "7")])))
+        );
+        assert_eq!(
+            lookup(&tree, "/files/a/b.txt"),
+            Some((5, params(&[("path", "a/b.txt")])))
+        );
+        assert_eq!(lookup(&tree, "/files"), Some((5, params(&[("path", "")]))));
+        assert_eq!(
+            lookup(&tree, "/users/42/posts"),
+            Some((6, params(&[("name", "42"), ("*", "posts")])))
+        );
+        assert_eq!(lookup(&tree, "/posts"), None);
+    }
+
+    #[test]
+    fn skip_rejected_routes() {
+        let mut tree = RouteTree::new();
+        tree.insert("/users/me", 0).unwrap();
+        tree.insert("/users/:id", 1).unwrap();
+        tree.insert("/users/*", 2).unwrap();
+
+        assert_eq!(
+            lookup_by(&tree, "/users/me", |value| *value != 0),
+            Some((1, params(&[("id", "me")])))
+        );
+        assert_eq!(
+            lookup_by(&tree, "/users/me", |value| *value == 2),
+            Some((2, params(&[("*", "me")])))
+        );
+        assert_eq!(lookup_by(&tree, "/users/me", |_| false), None);
+        assert_eq!(tree.matches("/users/me"), vec![&0, &1, &2]);
+    }
+
+    #[test]
+    fn invalid_routes() {
+        let mut tree = RouteTree::new();
+        assert_eq!(
+            tree.insert("/users/:", 0),
+            Err(RouteError::EmptyParam("/users/:".to_string()))
+        );
+        assert_eq!(
+            tree.insert("/files/*/edit", 0),
+            Err(RouteError::WildcardNotLast("/files/*/edit".to_string()))
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use futures::FutureExt;
 use klaver_core::{
     RuntimeError,
-    rquickjs::{self, AsyncContext, CatchResultExt, FromJs, markers::ParallelSend},
+    rquickjs::{self, AsyncContext, CatchResultExt, markers::ParallelSend},
 };
 
 use crate::{AsyncState, Context};
@@ -20,7 +20,6 @@ impl<T> EventLoop<T> {
         T: ParallelSend,
         T: for<'js> Runner<'js, Output = R>,
         R: ParallelSend + 'static,
-        R: for<'js> FromJs<'js>,
     {
         let work = rquickjs::async_with!(context => |ctx| {
 
@@ -44,6 +43,6 @@ impl<T> EventLoop<T> {
 }
 
 pub trait Runner<'js> {
-    type Output: FromJs<'js>;
+    type Output;
     fn run(self, ctx: Context<'js>) -> impl Future<Output = rquickjs::Result<Self::Output>>;
 }
@@ -1,5 +1,5 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     rc::Rc,
     task::{Poll, ready},
 };
@@ -98,6 +98,14 @@ impl<'js> TaskExecutor<'js> {
 
         self.manager.set_current(id);
 
+        let cancel = CancelGuard {
+            manager: &self.manager,
+            hooks: &self.hooks,
+            ctx,
+            id,
+            armed: Cell::new(true),
+        };
+
         let work_future = (runner)(context);
 
         let ret = futures::select! {
@@ -117,6 +125,7 @@ impl<'js> TaskExecutor<'js> {
         };
 
         let cleanup = || {
+            cancel.armed.set(false);
             self.manager.destroy_task(id, ctx, &self.hooks, true)?;
             rquickjs::Result::Ok(())
         };
@@ -484,6 +493,32 @@ impl Future for WaitKilled {
     }
 }
 
+/// Kills the tasks of a `run_async` call whose future is dropped before it finishes,
+/// like when it is raced against a deadline
+struct CancelGuard<'a, 'js> {
+    manager: &'a TaskManager,
+    hooks: &'a Class<'js, HookListeners<'js>>,
+    ctx: &'a Ctx<'js>,
+    id: AsyncId,
+    armed: Cell<bool>,
+}
+
+impl Drop for CancelGuard<'_, '_> {
+    fn drop(&mut self) {
+        if !self.armed.get() {
+            return;
+        }
+
+        if let Some(state) = self.manager.task_status(self.id) {
+            state.set(TaskStatus::Killed);
+        }
+
+        self.manager
+            .destroy_task(self.id, self.ctx, self.hooks, true)
+            .ok();
+    }
+}
+
 #[rquickjs::class(crate = "rquickjs")]
 pub struct JsSnapshot<'js> {
     context: Option<Context<'js>>,
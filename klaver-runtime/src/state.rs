@@ -1,4 +1,4 @@
-use rquickjs::{self, Ctx, FromJs};
+use rquickjs::{self, Ctx};
 
 use crate::{
     context::Context,
@@ -21,7 +21,6 @@ impl AsyncState {
     pub async fn run_async<'js, T, R>(ctx: &Ctx<'js>, runner: T) -> rquickjs::Result<R>
     where
         T: AsyncFnOnce(Context<'js>) -> rquickjs::Result<R>,
-        R: FromJs<'js>,
     {
         Self::run_async_with(
             ctx,
@@ -41,7 +40,6 @@ impl AsyncState {
     ) -> rquickjs::Result<R>
     where
         T: AsyncFnOnce(Context<'js>) -> rquickjs::Result<R>,
-        R: FromJs<'js>,
     {
         let executor = TaskExecutor::from_ctx(ctx)?;
         executor.run_async(ctx, execution, runner).await
@@ -50,7 +48,6 @@ impl AsyncState {
     pub fn run<'js, T, R>(ctx: &Ctx<'js>, runner: T) -> rquickjs::Result<R>
     where
         T: FnOnce(Context<'js>) -> rquickjs::Result<R>,
-        R: FromJs<'js>,
     {
         Self::run_with(
             ctx,
@@ -69,9 +66,14 @@ impl AsyncState {
     ) -> rquickjs::Result<R>
     where
         T: FnOnce(Context<'js>) -> rquickjs::Result<R>,
-        R: FromJs<'js>,
     {
         let executor = TaskExecutor::from_ctx(ctx)?;
         executor.run(ctx, execution, runner)
     }
+
+    /// Number of tasks still alive in the context, not counting internal ones
+    pub fn pending_tasks(ctx: &Ctx<'_>) -> rquickjs::Result<usize> {
+        let executor = TaskExecutor::from_ctx(ctx)?;
+        Ok(executor.manager().pending_count())
+    }
 }
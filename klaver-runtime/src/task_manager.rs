@@ -211,6 +211,16 @@ impl TaskManager {
         Ok(true)
     }
 
+    /// Number of live tasks, not counting internal ones
+    pub fn pending_count(&self) -> usize {
+        self.0
+            .borrow()
+            .tasks
+            .values()
+            .filter(|task| !task.internal)
+            .count()
+    }
+
     pub fn trigger_async_id(&self) -> AsyncId {
         self.0.borrow().trigger_id
     }
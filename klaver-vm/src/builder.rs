@@ -1,13 +1,18 @@
+use std::{path::PathBuf, time::Duration};
 
 use klaver_core::RuntimeError;
-use klaver_modules::{Environ, GlobalInfo, Loader, ModuleInfo, Resolver};
+use klaver_modules::{
+    Environ, Global, GlobalInfo, Loader, ModuleInfo, Resolver, resolvers::ImportMapResolver,
+};
 
-use crate::{Vm, VmOptions};
+use crate::{Snapshot, Vm, VmOptions};
 
 pub struct Options {
     pub builder: klaver_modules::Builder,
     pub max_stack_size: Option<usize>,
     pub memory_limit: Option<usize>,
+    pub time_limit: Option<Duration>,
+    pub snapshot: Option<Snapshot>,
 }
 
 impl Default for Options {
@@ -16,6 +21,8 @@ impl Default for Options {
             builder: klaver_modules::Builder::default(),
             max_stack_size: None,
             memory_limit: None,
+            time_limit: None,
+            snapshot: None,
         }
     }
 }
@@ -35,6 +42,36 @@ impl Options {
         self
     }
 
+    /// Rewrite specifiers with an import map, before they are resolved
+    pub fn import_map(mut self, import_map: ImportMapResolver) -> Self {
+        self.builder = self.builder.import_map(import_map);
+        self
+    }
+
+    /// Fail calls running longer than `limit` with `RuntimeError::Timeout`
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Restore `snapshot` in every context, instead of loading its modules from the loaders
+    pub fn snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Cache compiled modules in memory
+    pub fn cache(mut self, on: bool) -> Self {
+        self.builder = self.builder.cache(on);
+        self
+    }
+
+    /// Cache compiled modules in memory and in `dir`
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.builder = self.builder.cache_dir(dir);
+        self
+    }
+
     pub fn module<M: ModuleInfo>(self) -> Self {
         Options {
             builder: self.builder.module::<M>(),
@@ -49,19 +86,29 @@ impl Options {
         }
     }
 
+    /// Define `global` in every context, including the ones created after a reload or reset
+    pub fn define_global<G: Global + Send + Sync + 'static>(self, global: G) -> Self {
+        Options {
+            builder: self.builder.define_global(global),
+            ..self
+        }
+    }
+
     pub fn build_environ(self) -> Environ {
         self.builder.build()
     }
 
     pub async fn build(self) -> Result<Vm, RuntimeError> {
         let env = self.builder.build();
-        Vm::new(
-            &env,
-            VmOptions {
-                max_stack_size: self.max_stack_size,
-                memory_limit: self.memory_limit,
-            },
-        )
-        .await
+        let options = VmOptions {
+            max_stack_size: self.max_stack_size,
+            memory_limit: self.memory_limit,
+            time_limit: self.time_limit,
+        };
+
+        match self.snapshot {
+            Some(snapshot) => Vm::from_snapshot(&env, options, snapshot).await,
+            None => Vm::new(&env, options).await,
+        }
     }
 }
@@ -1,29 +1,106 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, pin::pin, sync::Mutex, time::Duration};
+
+use futures::future::{Either, select};
 
 use klaver_core::RuntimeError;
-use klaver_modules::Environ;
+use klaver_modules::{Environ, LoadedModules, ResolutionErrors};
 use klaver_runtime::{EventLoop, Runner};
 use rquickjs::{
     AsyncContext, AsyncRuntime, Ctx, FromJs, Function, Module, Object, Value,
     markers::ParallelSend, prelude::IntoArgs,
 };
 
-use crate::update_locations;
+use crate::{interrupt::InterruptHandle, snapshot::Snapshot, update_locations};
 
 // use crate::update_locations;
 
 pub struct Context {
-    pub(crate) context: AsyncContext,
+    runtime: AsyncRuntime,
+    current: Mutex<Current>,
     pub(crate) env: Environ,
+    pub(crate) interrupt: InterruptHandle,
+    pub(crate) time_limit: Option<Duration>,
+    pub(crate) snapshot: Option<Snapshot>,
+}
+
+struct Current {
+    context: AsyncContext,
+    loaded: Option<LoadedModules>,
+    errors: Option<ResolutionErrors>,
 }
 
 impl Context {
+    pub(crate) async fn new(
+        runtime: &AsyncRuntime,
+        env: &Environ,
+        interrupt: InterruptHandle,
+        time_limit: Option<Duration>,
+        snapshot: Option<Snapshot>,
+    ) -> Result<Context, RuntimeError> {
+        Ok(Context {
+            runtime: runtime.clone(),
+            current: Mutex::new(Current::new(runtime, env, snapshot.as_ref()).await?),
+            env: env.clone(),
+            interrupt,
+            time_limit,
+            snapshot,
+        })
+    }
+
     pub fn env(&self) -> &Environ {
         &self.env
     }
 
     pub fn runtime(&self) -> &AsyncRuntime {
-        self.context.runtime()
+        &self.runtime
+    }
+
+    fn context(&self) -> AsyncContext {
+        self.current.lock().expect("Lock").context.clone()
+    }
+
+    /// A handle to stop the running call from another thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// The time limit of every call, unless overridden with `run_with_limit` or `call_export_with_limit`
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.time_limit
+    }
+
+    fn map_error(&self, err: RuntimeError) -> RuntimeError {
+        match self.interrupt.take_error() {
+            Some(err) => err,
+            None => {
+                let errors = self.current.lock().expect("Lock").errors.clone();
+                update_locations(&self.env, errors.as_ref(), err)
+            }
+        }
+    }
+
+    /// Invalidate `path` and every module importing it. Returns the invalidated modules.
+    ///
+    /// QuickJS never unloads a module, so every context that loaded one of them is reset:
+    /// its next call, like `run`, `with` or `call_export`, runs in a fresh context, which loads the new code.
+    /// Globals and module state of the old context are lost. Contexts sharing the environment,
+    /// which never loaded the invalidated modules, are kept
+    pub fn reload(&self, path: &str) -> Vec<String> {
+        self.env.modules().reload(path)
+    }
+
+    /// Replace the context if any module it loaded was reloaded since
+    async fn refresh(&self) -> Result<(), RuntimeError> {
+        let loaded = self.current.lock().expect("Lock").loaded.clone();
+
+        if !loaded.is_some_and(|loaded| loaded.is_stale(self.env.modules().graph())) {
+            return Ok(());
+        }
+
+        let current = Current::new(&self.runtime, &self.env, self.snapshot.as_ref()).await?;
+        *self.current.lock().expect("Lock") = current;
+
+        Ok(())
     }
 
     pub async fn with<F, R>(&self, f: F) -> Result<R, RuntimeError>
@@ -31,10 +108,14 @@ impl Context {
         F: for<'js> FnOnce(Ctx<'js>) -> Result<R, RuntimeError> + ParallelSend,
         R: ParallelSend,
     {
-        self.context
+        self.refresh().await?;
+
+        let _guard = self.interrupt.begin(self.time_limit);
+
+        self.context()
             .with(|ctx| f(ctx))
             .await
-            .map_err(|err| update_locations(&self.env, err))
+            .map_err(|err| self.map_error(err))
     }
 
     pub async fn async_with<'a, F, R>(&self, f: F) -> Result<R, RuntimeError>
@@ -42,25 +123,52 @@ impl Context {
         F: for<'js> AsyncFnOnce(Ctx<'js>) -> Result<R, RuntimeError> + ParallelSend,
         R: ParallelSend + 'static,
     {
-        let ret = self
-            .context
-            .async_with(f)
-            .await
-            .map_err(|err| update_locations(&self.env, err))?;
-        Ok(ret)
+        self.refresh().await?;
+
+        let guard = self.interrupt.begin(self.time_limit);
+
+        let context = self.context();
+        let work = pin!(context.async_with(f));
+
+        match select(work, pin!(guard.expired())).await {
+            Either::Left((ret, _)) => ret.map_err(|err| self.map_error(err)),
+            Either::Right((err, _)) => Err(err),
+        }
     }
 
     pub async fn run<T, R>(&self, task: T) -> Result<R, RuntimeError>
     where
         T: ParallelSend,
         T: for<'js> Runner<'js, Output = R>,
-        R: for<'js> FromJs<'js>,
         R: 'static + ParallelSend,
     {
-        EventLoop::new(task)
-            .run(&self.context)
-            .await
-            .map_err(|err| update_locations(&self.env, err))
+        self.run_with_limit(task, self.time_limit).await
+    }
+
+    /// Run `task`, failing with `RuntimeError::Timeout` if it runs longer than `limit`.
+    /// The limit is wall clock time, including time spent awaiting timers or requests
+    pub async fn run_with_limit<T, R>(
+        &self,
+        task: T,
+        limit: Option<Duration>,
+    ) -> Result<R, RuntimeError>
+    where
+        T: ParallelSend,
+        T: for<'js> Runner<'js, Output = R>,
+        R: 'static + ParallelSend,
+    {
+        self.refresh().await?;
+
+        let guard = self.interrupt.begin(limit);
+
+        let context = self.context();
+        let work = pin!(EventLoop::new(task).run(&context));
+
+        // Dropping the event loop kills the tasks the call started
+        match select(work, pin!(guard.expired())).await {
+            Either::Left((ret, _)) => ret.map_err(|err| self.map_error(err)),
+            Either::Right((err, _)) => Err(err),
+        }
     }
 
     pub async fn idle(&self) {
@@ -82,6 +190,24 @@ impl Context {
         export: &str,
         args: A,
     ) -> Result<R, RuntimeError>
+    where
+        A: for<'js> IntoArgs<'js>,
+        R: for<'js> FromJs<'js>,
+        A: ParallelSend,
+        R: ParallelSend,
+    {
+        self.call_export_with_limit(module, export, args, self.time_limit)
+            .await
+    }
+
+    /// Like `call_export`, with a time limit for this call only
+    pub async fn call_export_with_limit<A, R: 'static>(
+        &self,
+        module: &str,
+        export: &str,
+        args: A,
+        limit: Option<Duration>,
+    ) -> Result<R, RuntimeError>
     where
         A: for<'js> IntoArgs<'js>,
         R: for<'js> FromJs<'js>,
@@ -95,7 +221,37 @@ impl Context {
             ret: PhantomData,
         };
 
-        self.run(export).await
+        self.run_with_limit(export, limit).await
+    }
+}
+
+impl Current {
+    async fn new(
+        runtime: &AsyncRuntime,
+        env: &Environ,
+        snapshot: Option<&Snapshot>,
+    ) -> Result<Current, RuntimeError> {
+        let context = AsyncContext::full(runtime).await?;
+        env.init(&context).await?;
+
+        if let Some(snapshot) = snapshot {
+            snapshot.restore(env, &context).await?;
+        }
+
+        let (loaded, errors) = context
+            .with(|ctx| {
+                (
+                    LoadedModules::from_ctx(&ctx),
+                    ResolutionErrors::from_ctx(&ctx),
+                )
+            })
+            .await;
+
+        Ok(Current {
+            context,
+            loaded,
+            errors,
+        })
     }
 }
 
@@ -0,0 +1,155 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use futures_timer::Delay;
+use klaver_core::RuntimeError;
+use rquickjs::AsyncRuntime;
+
+#[derive(Debug, Clone, Copy)]
+enum Reason {
+    Interrupted,
+    Timeout(Duration),
+}
+
+#[derive(Default)]
+struct State {
+    requested: AtomicBool,
+    /// Calls running, including nested ones
+    active: AtomicUsize,
+    deadline: Mutex<Option<(Instant, Duration)>>,
+    reason: Mutex<Option<Reason>>,
+}
+
+/// InterruptHandle stops the javascript running in a `Vm`, from any thread.
+/// It is checked through the QuickJS interrupt handler, which raises an uncatchable
+/// exception, so scripts can not swallow it
+#[derive(Clone, Default)]
+pub struct InterruptHandle(Arc<State>);
+
+impl InterruptHandle {
+    pub(crate) async fn attach(runtime: &AsyncRuntime) -> InterruptHandle {
+        let handle = InterruptHandle::default();
+
+        let state = handle.0.clone();
+        runtime
+            .set_interrupt_handler(Some(Box::new(move || state.check())))
+            .await;
+
+        handle
+    }
+
+    /// Interrupt the running call. Does nothing if no call is running
+    pub fn interrupt(&self) {
+        self.0.requested.store(true, Ordering::Release);
+    }
+
+    /// Start a call limited to `limit`, or by the deadline of the call running it when that is earlier.
+    /// The previous deadline is restored when the guard is dropped
+    pub(crate) fn begin(&self, limit: Option<Duration>) -> LimitGuard<'_> {
+        self.0.reason.lock().expect("Lock").take();
+
+        // An interrupt requested while nothing ran, or which the last call finished before
+        // seeing, is not meant for this call
+        if self.0.active.fetch_add(1, Ordering::AcqRel) == 0 {
+            self.0.requested.store(false, Ordering::Release);
+        }
+
+        let mut current = self.0.deadline.lock().expect("Lock");
+
+        // A nested call can shorten the budget of the call running it, but not extend it
+        let deadline = match (*current, limit.map(|limit| (Instant::now() + limit, limit))) {
+            (Some(previous), Some(next)) if previous.0 <= next.0 => Some(previous),
+            (previous, next) => next.or(previous),
+        };
+        let previous = std::mem::replace(&mut *current, deadline);
+        drop(current);
+
+        LimitGuard {
+            handle: self,
+            deadline,
+            previous,
+        }
+    }
+
+    /// The error for an interrupt which fired since the last call
+    pub(crate) fn take_error(&self) -> Option<RuntimeError> {
+        match self.0.reason.lock().expect("Lock").take()? {
+            Reason::Interrupted => Some(RuntimeError::Interrupted),
+            Reason::Timeout(limit) => Some(RuntimeError::Timeout(limit)),
+        }
+    }
+}
+
+impl State {
+    fn check(&self) -> bool {
+        let reason = if self.requested.swap(false, Ordering::AcqRel) {
+            Reason::Interrupted
+        } else {
+            match *self.deadline.lock().expect("Lock") {
+                Some((deadline, limit)) if Instant::now() >= deadline => Reason::Timeout(limit),
+                _ => return false,
+            }
+        };
+
+        *self.reason.lock().expect("Lock") = Some(reason);
+
+        true
+    }
+}
+
+pub(crate) struct LimitGuard<'a> {
+    handle: &'a InterruptHandle,
+    deadline: Option<(Instant, Duration)>,
+    previous: Option<(Instant, Duration)>,
+}
+
+impl LimitGuard<'_> {
+    /// Resolves with the timeout error when the deadline of the call passes, never if it has none.
+    /// The interrupt handler only runs while javascript executes, so a call awaiting
+    /// a timer or a request is raced against this instead
+    pub(crate) async fn expired(&self) -> RuntimeError {
+        let Some((deadline, limit)) = self.deadline else {
+            return futures::future::pending().await;
+        };
+
+        Delay::new(deadline.saturating_duration_since(Instant::now())).await;
+
+        RuntimeError::Timeout(limit)
+    }
+}
+
+impl Drop for LimitGuard<'_> {
+    fn drop(&mut self) {
+        *self.handle.0.deadline.lock().expect("Lock") = self.previous.take();
+        self.handle.0.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_limits() {
+        let handle = InterruptHandle::default();
+
+        let outer = handle.begin(Some(Duration::ZERO));
+
+        for limit in [Some(Duration::from_secs(60)), None] {
+            let _inner = handle.begin(limit);
+            assert!(handle.0.check());
+            assert!(matches!(
+                handle.take_error(),
+                Some(RuntimeError::Timeout(limit)) if limit == Duration::ZERO
+            ));
+        }
+
+        drop(outer);
+        assert!(!handle.0.check());
+    }
+}
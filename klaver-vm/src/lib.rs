@@ -3,9 +3,11 @@ mod macros;
 mod bindings;
 mod builder;
 mod context;
+mod interrupt;
 mod module;
 #[cfg(feature = "pool")]
 pub mod pool;
+mod snapshot;
 mod util;
 mod vm;
 #[cfg(feature = "worker")]
@@ -13,4 +15,6 @@ mod worker;
 
 #[cfg(feature = "worker")]
 pub use self::worker::*;
-pub use self::{builder::*, module::*, util::*, vm::*};
+pub use self::{
+    builder::*, interrupt::InterruptHandle, module::*, snapshot::Snapshot, util::*, vm::*,
+};
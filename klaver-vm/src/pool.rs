@@ -1,11 +1,22 @@
-use std::{future::Future, option, pin::Pin, sync::Arc};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
-use deadpool::managed::{Metrics, RecycleResult};
+use deadpool::managed::{Metrics, RecycleError, RecycleResult};
+use klaver_core::RuntimeError;
 use klaver_modules::Environ;
-use klaver_util::RuntimeError;
-use rquickjs::{AsyncContext, AsyncRuntime, Ctx, runtime::MemoryUsage};
+use rquickjs::{Ctx, runtime::MemoryUsage};
 
-use crate::{Options, Vm, VmOptions, worker::Worker};
+use crate::{
+    Options, Snapshot, Vm, VmOptions,
+    worker::{Worker, WorkerRuntime},
+};
 
 pub type CustomizeFn = Arc<
     dyn for<'a> Fn(
@@ -22,13 +33,49 @@ pub type PoolError = deadpool::managed::PoolError<RuntimeError>;
 pub struct Manager {
     init: Option<CustomizeFn>,
     options: VmPoolOptions,
+    counters: Arc<Counters>,
 }
 
 pub struct VmPoolOptions {
     pub max_stack_size: Option<usize>,
     pub memory_limit: Option<usize>,
+    pub time_limit: Option<Duration>,
     pub modules: Environ,
-    pub worker_thread: bool,
+    /// Run every vm on a dedicated thread, driven by this runtime
+    pub worker_thread: Option<WorkerRuntime>,
+    pub recycle: RecyclePolicy,
+    /// Create every vm from this snapshot
+    pub snapshot: Option<Snapshot>,
+}
+
+/// When a vm returned to the pool is discarded instead of reused
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RecyclePolicy {
+    /// Discard the vm after it has been checked out this many times
+    pub max_uses: Option<usize>,
+    /// Discard the vm if it uses more memory than this after a garbage collection
+    pub max_memory: Option<usize>,
+    /// Discard the vm when it is older than this
+    pub max_age: Option<Duration>,
+    /// Discard the vm if it still has pending jobs or tasks
+    pub require_idle: bool,
+    /// Give every checkout a fresh context, so no javascript state leaks between users
+    pub fresh_context: bool,
+}
+
+/// Counts of vms handled by a pool
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PoolMetrics {
+    pub created: usize,
+    pub recycled: usize,
+    pub discarded: usize,
+}
+
+#[derive(Default)]
+struct Counters {
+    created: AtomicUsize,
+    recycled: AtomicUsize,
+    discarded: AtomicUsize,
 }
 
 impl VmPoolOptions {
@@ -36,8 +83,11 @@ impl VmPoolOptions {
         Ok(VmPoolOptions {
             max_stack_size: options.max_stack_size,
             memory_limit: options.memory_limit,
+            time_limit: options.time_limit,
+            snapshot: options.snapshot.clone(),
             modules: options.build_environ(),
-            worker_thread: false,
+            worker_thread: None,
+            recycle: RecyclePolicy::default(),
         })
     }
 }
@@ -47,11 +97,76 @@ impl Manager {
         Ok(Manager {
             init: None,
             options,
+            counters: Arc::default(),
         })
     }
 
-    pub fn use_worker_thread(mut self) -> Self {
-        self.options.worker_thread = true;
+    pub fn snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.options.snapshot = Some(snapshot);
+        self
+    }
+
+    pub fn recycle_policy(mut self, policy: RecyclePolicy) -> Self {
+        self.options.recycle = policy;
+        self
+    }
+
+    /// Metrics of the pool, reachable through `Pool::manager`
+    pub fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            created: self.counters.created.load(Ordering::Relaxed),
+            recycled: self.counters.recycled.load(Ordering::Relaxed),
+            discarded: self.counters.discarded.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn check(&self, vm: &mut PooledVm, metrics: &Metrics) -> RecycleResult<RuntimeError> {
+        let policy = &self.options.recycle;
+
+        if let Some(max_uses) = policy.max_uses
+            && metrics.recycle_count + 1 >= max_uses
+        {
+            return Err(RecycleError::message(format!(
+                "Vm reached max uses of {max_uses}"
+            )));
+        }
+
+        if let Some(max_age) = policy.max_age
+            && metrics.age() >= max_age
+        {
+            return Err(RecycleError::message(format!(
+                "Vm reached max age of {max_age:?}"
+            )));
+        }
+
+        if policy.require_idle && !vm.is_idle().await? {
+            return Err(RecycleError::message("Vm is not idle"));
+        }
+
+        // The fresh context is set up like a new vm
+        if policy.fresh_context {
+            vm.reset().await?;
+
+            if let Some(init) = &self.init {
+                init(vm).await?;
+            }
+        }
+
+        if let Some(max_memory) = policy.max_memory {
+            vm.run_gc().await?;
+            let used = vm.memory_usage().await?.memory_used_size as usize;
+            if used > max_memory {
+                return Err(RecycleError::message(format!(
+                    "Vm uses {used} bytes, more than the max of {max_memory}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn use_worker_thread(mut self, runtime: WorkerRuntime) -> Self {
+        self.options.worker_thread = Some(runtime);
         self
     }
 
@@ -73,53 +188,53 @@ impl deadpool::managed::Manager for Manager {
 
     fn create(&self) -> impl std::future::Future<Output = Result<Self::Type, Self::Error>> + Send {
         async move {
-            // let vm = if self.options.worker_thread {
-            //     let vm = Worker::new(
-            //         self.options.modules.clone(),
-            //         VmOptions {
-            //             max_stack_size: self.options.max_stack_size,
-            //             memory_limit: self.options.memory_limit,
-            //         },
-            //     )
-            //     .await?;
-            //     PooledVm::Worker(vm)
-            // } else {
-            //     let vm = Vm::new(
-            //         &self.options.modules,
-            //         VmOptions {
-            //             max_stack_size: self.options.max_stack_size,
-            //             memory_limit: self.options.memory_limit,
-            //         },
-            //     )
-            //     .await?;
-
-            //     PooledVm::Vm(vm)
-            // };
-            let vm = Vm::new(
-                &self.options.modules,
-                VmOptions {
-                    max_stack_size: self.options.max_stack_size,
-                    memory_limit: self.options.memory_limit,
-                },
-            )
-            .await?;
-
-            let vm = PooledVm::Vm(vm);
+            let options = VmOptions {
+                max_stack_size: self.options.max_stack_size,
+                memory_limit: self.options.memory_limit,
+                time_limit: self.options.time_limit,
+            };
+
+            let env = &self.options.modules;
+
+            let vm = match (self.options.worker_thread, self.options.snapshot.clone()) {
+                (Some(runtime), Some(snapshot)) => PooledVm::Worker(
+                    Worker::from_snapshot(env.clone(), options, snapshot, runtime).await?,
+                ),
+                (Some(runtime), None) => {
+                    PooledVm::Worker(Worker::new(env.clone(), options, runtime).await?)
+                }
+                (None, Some(snapshot)) => {
+                    PooledVm::Vm(Vm::from_snapshot(env, options, snapshot).await?)
+                }
+                (None, None) => PooledVm::Vm(Vm::new(env, options).await?),
+            };
 
             if let Some(init) = &self.init {
                 init(&vm).await?;
             }
 
+            self.counters.created.fetch_add(1, Ordering::Relaxed);
+
             Ok(vm)
         }
     }
 
     fn recycle(
         &self,
-        _obj: &mut Self::Type,
-        _metrics: &Metrics,
+        obj: &mut Self::Type,
+        metrics: &Metrics,
     ) -> impl std::future::Future<Output = RecycleResult<Self::Error>> + Send {
-        async move { Ok(()) }
+        async move {
+            let ret = self.check(obj, metrics).await;
+
+            let counter = match ret {
+                Ok(_) => &self.counters.recycled,
+                Err(_) => &self.counters.discarded,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+
+            ret
+        }
     }
 }
 
@@ -143,7 +258,7 @@ impl PooledVm {
 
     pub async fn async_with<T, R>(&self, func: T) -> Result<R, RuntimeError>
     where
-        T: Send,
+        T: Send + 'static,
         for<'js> T:
             FnOnce(Ctx<'js>) -> Pin<Box<dyn Future<Output = Result<R, RuntimeError>> + 'js + Send>>,
         R: Send + 'static,
@@ -154,9 +269,12 @@ impl PooledVm {
         }
     }
 
-    pub async fn run_gc(&self) {
+    pub async fn run_gc(&self) -> Result<(), RuntimeError> {
         match self {
-            PooledVm::Vm(vm) => vm.run_gc().await,
+            PooledVm::Vm(vm) => {
+                vm.run_gc().await;
+                Ok(())
+            }
             PooledVm::Worker(worker) => worker.run_gc().await,
         }
     }
@@ -170,11 +288,29 @@ impl PooledVm {
 
     pub async fn idle(&self) -> Result<(), RuntimeError> {
         match self {
-            PooledVm::Vm(vm) => Ok(vm.idle().await),
+            PooledVm::Vm(vm) => {
+                vm.idle().await;
+                Ok(())
+            }
             PooledVm::Worker(worker) => worker.idle().await,
         }
     }
 
+    pub async fn is_idle(&self) -> Result<bool, RuntimeError> {
+        match self {
+            PooledVm::Vm(vm) => vm.is_idle().await,
+            PooledVm::Worker(worker) => worker.is_idle().await,
+        }
+    }
+
+    /// Replace the javascript context of the vm with a fresh one
+    pub async fn reset(&mut self) -> Result<(), RuntimeError> {
+        match self {
+            PooledVm::Vm(vm) => vm.reset().await,
+            PooledVm::Worker(worker) => worker.reset().await,
+        }
+    }
+
     pub fn env(&self) -> &Environ {
         match self {
             PooledVm::Vm(vm) => vm.env(),
@@ -0,0 +1,126 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use klaver_core::RuntimeError;
+use klaver_modules::{
+    Environ, ResolutionErrors,
+    loaders::{Bundle, BundleModule},
+};
+use klaver_runtime::{EventLoop, Runner};
+use rquickjs::{AsyncContext, CatchResultExt, Ctx, Module};
+
+use crate::update_locations;
+
+/// Snapshot is a template of an initialized vm, used to create new contexts faster.
+/// QuickJS can not serialize its heap, so instead the snapshot holds the bytecode of the modules
+/// evaluated at startup, and an init script. Every context created from it skips transforming
+/// and parsing those modules, but still evaluates them.
+/// Modules in the snapshot are not affected by `Context::reload`
+#[derive(Clone)]
+pub struct Snapshot {
+    entries: Vec<String>,
+    modules: Arc<BTreeMap<String, BundleModule>>,
+    script: Option<String>,
+}
+
+impl Snapshot {
+    /// Compile `entries` and their static imports with the loaders and resolvers of `env`.
+    /// Entries are resolved like `run_module`, so the environment using the snapshot
+    /// must resolve them to the same paths
+    pub async fn capture(env: &Environ, entries: &[&str]) -> Result<Snapshot, RuntimeError> {
+        let mut modules = BTreeMap::new();
+
+        for entry in entries {
+            let bundle = Bundle::compile(env, entry).await?;
+            for (name, module) in bundle.modules() {
+                modules
+                    .entry(name.to_string())
+                    .or_insert_with(|| BundleModule {
+                        bytecode: module.bytecode.clone(),
+                        source_map: module.source_map.clone(),
+                    });
+            }
+        }
+
+        Ok(Snapshot {
+            entries: entries.iter().map(|entry| entry.to_string()).collect(),
+            modules: Arc::new(modules),
+            script: None,
+        })
+    }
+
+    /// Evaluate `script` in every new context, before the entries are imported
+    pub fn with_script(mut self, script: impl Into<String>) -> Snapshot {
+        self.script = Some(script.into());
+        self
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Paths of the compiled modules
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.modules.keys().map(|name| &**name)
+    }
+
+    /// Declare the compiled modules in `context`, then run the script and import the entries
+    pub(crate) async fn restore(
+        &self,
+        env: &Environ,
+        context: &AsyncContext,
+    ) -> Result<(), RuntimeError> {
+        let errors = context.with(|ctx| ResolutionErrors::from_ctx(&ctx)).await;
+
+        context
+            .with(|ctx| {
+                self.declare(env, &ctx)
+                    .catch(&ctx)
+                    .map_err(RuntimeError::from)
+            })
+            .await
+            .map_err(|err| update_locations(env, errors.as_ref(), err))?;
+
+        EventLoop::new(Restore(self.clone()))
+            .run(context)
+            .await
+            .map_err(|err| update_locations(env, errors.as_ref(), err))
+    }
+
+    fn declare<'js>(&self, env: &Environ, ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+        for (path, module) in self.modules.iter() {
+            if let Some(source_map) = &module.source_map {
+                env.modules()
+                    .source_maps()
+                    .insert(path.clone(), source_map.clone());
+            }
+
+            // Safety: the bytecode was written by `Module::write` when the snapshot was captured.
+            // QuickJS references the buffer instead of copying it, so every context restored
+            // from the snapshot keeps it alive for as long as the runtime exists.
+            // Declared modules are found by name, so imports of them never reach the loaders
+            let module = unsafe { Module::load(ctx.clone(), &module.bytecode)? };
+            module.meta()?.set("url", format!("file://{}", path))?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Restore(Snapshot);
+
+impl<'js> Runner<'js> for Restore {
+    type Output = ();
+
+    async fn run(self, ctx: klaver_runtime::Context<'js>) -> rquickjs::Result<Self::Output> {
+        if let Some(script) = &self.0.script {
+            ctx.eval::<(), _>(script.as_str())?;
+        }
+
+        for entry in &self.0.entries {
+            let promise = Module::import(&ctx, entry.as_str())?;
+            let _ = promise.into_future::<()>().await?;
+        }
+
+        Ok(())
+    }
+}
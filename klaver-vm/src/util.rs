@@ -1,7 +1,6 @@
-
 use klaver_core::CaugthException;
 pub use klaver_core::RuntimeError;
-use klaver_modules::Environ;
+use klaver_modules::{Environ, ResolutionErrors};
 
 pub type Result<T> = core::result::Result<T, RuntimeError>;
 
@@ -24,11 +23,24 @@ pub const fn Ok<T>(value: T) -> Result<T> {
 //     async fn run<T: Runnerable + 'static>(&self, task: T) -> Result<()>;
 // }
 
-pub(crate) fn update_locations(env: &Environ, mut err: RuntimeError) -> RuntimeError {
-    let RuntimeError::Exception(CaugthException { stack, .. }) = &mut err else {
+pub(crate) fn update_locations(
+    env: &Environ,
+    errors: Option<&ResolutionErrors>,
+    mut err: RuntimeError,
+) -> RuntimeError {
+    let RuntimeError::Exception(CaugthException { message, stack }) = &mut err else {
         return err;
     };
 
+    // Failed imports of the context are reported with the attempt of every resolver
+    if let Some(error) = message
+        .as_deref()
+        .zip(errors)
+        .and_then(|(message, errors)| errors.take(message))
+    {
+        return RuntimeError::Resolution(error);
+    }
+
     let _sourcemaps = env.modules().source_maps();
     for trace in stack {
         let Some((line, col)) =
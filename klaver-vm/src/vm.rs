@@ -1,13 +1,18 @@
+use std::time::Duration;
+
 use klaver_core::RuntimeError;
 use klaver_modules::Environ;
-use rquickjs::{AsyncContext, runtime::MemoryUsage};
+use klaver_runtime::AsyncState;
+use rquickjs::runtime::MemoryUsage;
 
-use crate::context::Context;
+use crate::{context::Context, interrupt::InterruptHandle, snapshot::Snapshot};
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct VmOptions {
     pub max_stack_size: Option<usize>,
     pub memory_limit: Option<usize>,
+    /// Time limit of every call, like `run_module` or `call_export`
+    pub time_limit: Option<Duration>,
 }
 
 pub struct Vm {
@@ -16,6 +21,24 @@ pub struct Vm {
 
 impl Vm {
     pub async fn new(env: &Environ, options: VmOptions) -> Result<Vm, RuntimeError> {
+        Vm::create(env, options, None).await
+    }
+
+    /// Create a vm with the modules and script of `snapshot` already evaluated.
+    /// The snapshot is restored again whenever the context is replaced
+    pub async fn from_snapshot(
+        env: &Environ,
+        options: VmOptions,
+        snapshot: Snapshot,
+    ) -> Result<Vm, RuntimeError> {
+        Vm::create(env, options, Some(snapshot)).await
+    }
+
+    async fn create(
+        env: &Environ,
+        options: VmOptions,
+        snapshot: Option<Snapshot>,
+    ) -> Result<Vm, RuntimeError> {
         let runtime = env.create_runtime().await?;
 
         if let Some(ss) = options.max_stack_size {
@@ -26,15 +49,10 @@ impl Vm {
             runtime.set_memory_limit(mm).await;
         }
 
-        let context = AsyncContext::full(&runtime).await?;
-
-        env.init(&context).await?;
+        let interrupt = InterruptHandle::attach(&runtime).await;
 
         Ok(Vm {
-            context: Context {
-                context,
-                env: env.clone(),
-            },
+            context: Context::new(&runtime, env, interrupt, options.time_limit, snapshot).await?,
         })
     }
 
@@ -50,14 +68,36 @@ impl Vm {
         self.context.runtime().idle().await
     }
 
-    pub async fn create_context(&self) -> Result<Context, RuntimeError> {
-        let context = AsyncContext::full(&self.context.runtime()).await?;
-        self.context.env.init(&context).await?;
+    /// Whether the runtime has no pending jobs and the context no running tasks
+    pub async fn is_idle(&self) -> Result<bool, RuntimeError> {
+        if self.context.runtime().is_job_pending().await {
+            return Ok(false);
+        }
 
-        Ok(Context {
-            context,
-            env: self.context.env.clone(),
-        })
+        let pending = self
+            .context
+            .with(|ctx| Ok(AsyncState::pending_tasks(&ctx)?))
+            .await?;
+
+        Ok(pending == 0)
+    }
+
+    /// Replace the context with a fresh one, dropping all javascript state.
+    /// The runtime, and with it the memory limit and interrupt handle, is kept
+    pub async fn reset(&mut self) -> Result<(), RuntimeError> {
+        self.context = self.create_context().await?;
+        Ok(())
+    }
+
+    pub async fn create_context(&self) -> Result<Context, RuntimeError> {
+        Context::new(
+            self.context.runtime(),
+            &self.context.env,
+            self.context.interrupt.clone(),
+            self.context.time_limit,
+            self.context.snapshot.clone(),
+        )
+        .await
     }
 }
 
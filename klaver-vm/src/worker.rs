@@ -4,9 +4,9 @@ use futures::channel::oneshot;
 use klaver_core::RuntimeError;
 use klaver_modules::Environ;
 use klaver_runtime::Runner;
-use rquickjs::{Ctx, FromJs};
+use rquickjs::{Ctx, FromJs, runtime::MemoryUsage};
 
-use crate::{Vm, VmOptions};
+use crate::{Snapshot, Vm, VmOptions};
 
 pub trait AsyncRuntimeTrait {
     fn block_on<F>(&self, future: F)
@@ -48,6 +48,33 @@ impl AsyncRuntimeTrait for CompioRuntime {
     }
 }
 
+/// The async runtime a worker thread runs its vm on, selectable at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerRuntime {
+    #[cfg(feature = "tokio")]
+    Tokio,
+    #[cfg(feature = "compio")]
+    Compio,
+}
+
+impl AsyncRuntimeTrait for WorkerRuntime {
+    #[cfg_attr(
+        not(any(feature = "tokio", feature = "compio")),
+        allow(unused_variables)
+    )]
+    fn block_on<F>(&self, future: F)
+    where
+        F: std::future::Future<Output = ()> + 'static,
+    {
+        match *self {
+            #[cfg(feature = "tokio")]
+            WorkerRuntime::Tokio => ToktioRuntime.block_on(future),
+            #[cfg(feature = "compio")]
+            WorkerRuntime::Compio => CompioRuntime.block_on(future),
+        }
+    }
+}
+
 enum Request {
     WithAsync {
         function: Box<
@@ -69,10 +96,16 @@ enum Request {
         >,
         returns: oneshot::Sender<Result<Box<dyn Any + Send>, RuntimeError>>,
     },
+    Reset {
+        returns: oneshot::Sender<Result<(), RuntimeError>>,
+    },
 }
 
+/// Worker runs a `Vm` on a dedicated thread, so the non-`Send` QuickJS runtime
+/// can be driven from a multi-threaded executor
 pub struct Worker {
     sx: flume::Sender<Request>,
+    env: Environ,
 }
 
 impl Worker {
@@ -80,16 +113,50 @@ impl Worker {
         environ: Environ,
         options: VmOptions,
         runtime: T,
+    ) -> Result<Self, RuntimeError> {
+        Worker::create(environ, options, None, runtime).await
+    }
+
+    /// Create a worker with a vm restored from `snapshot`. See `Vm::from_snapshot`
+    pub async fn from_snapshot<T: AsyncRuntimeTrait + Send + 'static>(
+        environ: Environ,
+        options: VmOptions,
+        snapshot: Snapshot,
+        runtime: T,
+    ) -> Result<Self, RuntimeError> {
+        Worker::create(environ, options, Some(snapshot), runtime).await
+    }
+
+    async fn create<T: AsyncRuntimeTrait + Send + 'static>(
+        environ: Environ,
+        options: VmOptions,
+        snapshot: Option<Snapshot>,
+        runtime: T,
     ) -> Result<Self, RuntimeError> {
         let (sx, rx) = oneshot::channel();
 
-        create_worker_thread(environ, options, runtime, sx);
+        create_worker_thread(environ.clone(), options, snapshot, runtime, sx);
 
         let worker_sx = rx
             .await
             .map_err(|_| RuntimeError::new("Worker thread panicked"))??;
 
-        Ok(Self { sx: worker_sx })
+        Ok(Self {
+            sx: worker_sx,
+            env: environ,
+        })
+    }
+
+    pub fn env(&self) -> &Environ {
+        &self.env
+    }
+
+    pub async fn with<F, R>(&self, f: F) -> Result<R, RuntimeError>
+    where
+        F: for<'js> FnOnce(Ctx<'js>) -> Result<R, RuntimeError> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.exec(async move |vm| vm.with(f).await).await
     }
 
     pub async fn async_with<'a, F, R>(&self, f: F) -> Result<R, RuntimeError>
@@ -141,6 +208,42 @@ impl Worker {
             .await
     }
 
+    pub async fn memory_usage(&self) -> Result<MemoryUsage, RuntimeError> {
+        self.exec(async |vm| Ok(vm.memory_usage().await)).await
+    }
+
+    pub async fn run_gc(&self) -> Result<(), RuntimeError> {
+        self.exec(async |vm| {
+            vm.run_gc().await;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn idle(&self) -> Result<(), RuntimeError> {
+        self.exec(async |vm| {
+            vm.idle().await;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn is_idle(&self) -> Result<bool, RuntimeError> {
+        self.exec(async |vm| vm.is_idle().await).await
+    }
+
+    /// Replace the context of the vm with a fresh one. See `Vm::reset`
+    pub async fn reset(&self) -> Result<(), RuntimeError> {
+        let (sx, rx) = oneshot::channel();
+
+        self.sx
+            .send_async(Request::Reset { returns: sx })
+            .await
+            .map_err(|err| RuntimeError::new(err.to_string()))?;
+
+        rx.await.map_err(|_| RuntimeError::new("Worker dropped"))?
+    }
+
     async fn exec<F, R>(&self, f: F) -> Result<R, RuntimeError>
     where
         F: for<'js> AsyncFnOnce(&'js Vm) -> Result<R, RuntimeError> + Send + 'static,
@@ -178,6 +281,7 @@ impl Worker {
 fn create_worker_thread<T: AsyncRuntimeTrait + Send + 'static>(
     environ: Environ,
     options: VmOptions,
+    snapshot: Option<Snapshot>,
     runtime: T,
     sx: oneshot::Sender<Result<flume::Sender<Request>, RuntimeError>>,
 ) {
@@ -185,7 +289,12 @@ fn create_worker_thread<T: AsyncRuntimeTrait + Send + 'static>(
         runtime.block_on(async move {
             let (worker_sx, worker_rx) = flume::bounded(10);
 
-            let vm = match Vm::new(&environ, options).await {
+            let vm = match snapshot {
+                Some(snapshot) => Vm::from_snapshot(&environ, options, snapshot).await,
+                None => Vm::new(&environ, options).await,
+            };
+
+            let vm = match vm {
                 Ok(ret) => {
                     let _ = sx.send(Ok(worker_sx));
                     ret
@@ -201,7 +310,7 @@ fn create_worker_thread<T: AsyncRuntimeTrait + Send + 'static>(
     });
 }
 
-async fn worker(rx: flume::Receiver<Request>, vm: Vm) {
+async fn worker(rx: flume::Receiver<Request>, mut vm: Vm) {
     while let Ok(req) = rx.recv_async().await {
         match req {
             Request::WithAsync { function, returns } => {
@@ -212,6 +321,9 @@ async fn worker(rx: flume::Receiver<Request>, vm: Vm) {
                 let ret = function(&vm).await;
                 let _ = returns.send(ret);
             }
+            Request::Reset { returns } => {
+                let _ = returns.send(vm.reset().await);
+            }
         }
     }
 }
@@ -0,0 +1,156 @@
+#![cfg(feature = "pool")]
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use klaver_vm::{
+    Options, RuntimeError,
+    pool::{Manager, Pool, PoolMetrics, PooledVm, RecyclePolicy, VmPoolOptions},
+};
+
+fn pool(policy: RecyclePolicy) -> Pool {
+    let manager = Manager::new(VmPoolOptions::from(Options::default()).unwrap())
+        .unwrap()
+        .recycle_policy(policy)
+        .init(init);
+
+    Pool::builder(manager).max_size(1).build().unwrap()
+}
+
+fn init<'a>(
+    vm: &'a PooledVm,
+) -> Pin<Box<dyn Future<Output = Result<(), RuntimeError>> + Send + 'a>> {
+    Box::pin(async move {
+        vm.with(|ctx| {
+            ctx.globals().set("ready", true)?;
+            Ok(())
+        })
+        .await
+    })
+}
+
+fn metrics(pool: &Pool) -> PoolMetrics {
+    pool.manager().metrics()
+}
+
+#[compio::test]
+async fn max_uses() {
+    let pool = pool(RecyclePolicy {
+        max_uses: Some(2),
+        ..Default::default()
+    });
+
+    for _ in 0..3 {
+        drop(pool.get().await.unwrap());
+    }
+
+    // The second checkout reuses the vm, the third replaces it
+    assert_eq!(
+        metrics(&pool),
+        PoolMetrics {
+            created: 2,
+            recycled: 1,
+            discarded: 1,
+        }
+    );
+}
+
+#[compio::test]
+async fn max_age() {
+    let pool = pool(RecyclePolicy {
+        max_age: Some(Duration::from_millis(50)),
+        ..Default::default()
+    });
+
+    drop(pool.get().await.unwrap());
+    drop(pool.get().await.unwrap());
+
+    std::thread::sleep(Duration::from_millis(100));
+    drop(pool.get().await.unwrap());
+
+    assert_eq!(
+        metrics(&pool),
+        PoolMetrics {
+            created: 2,
+            recycled: 1,
+            discarded: 1,
+        }
+    );
+}
+
+#[compio::test]
+async fn fresh_context() {
+    let pool = pool(RecyclePolicy {
+        fresh_context: true,
+        ..Default::default()
+    });
+
+    let vm = pool.get().await.unwrap();
+    vm.with(|ctx| {
+        ctx.globals().set("leak", true)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+    drop(vm);
+
+    // State of the last checkout is gone, but the init hook ran again
+    let vm = pool.get().await.unwrap();
+    let (leak, ready) = vm
+        .with(|ctx| {
+            let globals = ctx.globals();
+            Ok((
+                globals.contains_key("leak")?,
+                globals.get::<_, bool>("ready")?,
+            ))
+        })
+        .await
+        .unwrap();
+    drop(vm);
+
+    assert!(!leak);
+    assert!(ready);
+
+    assert_eq!(
+        metrics(&pool),
+        PoolMetrics {
+            created: 1,
+            recycled: 1,
+            discarded: 0,
+        }
+    );
+}
+
+#[cfg(any(feature = "tokio", feature = "compio"))]
+#[compio::test]
+async fn worker_thread() {
+    use klaver_vm::WorkerRuntime;
+
+    let runtimes = [
+        #[cfg(feature = "tokio")]
+        WorkerRuntime::Tokio,
+        #[cfg(feature = "compio")]
+        WorkerRuntime::Compio,
+    ];
+
+    for runtime in runtimes {
+        let manager = Manager::new(VmPoolOptions::from(Options::default()).unwrap())
+            .unwrap()
+            .use_worker_thread(runtime)
+            .init(init);
+        let pool = Pool::builder(manager).max_size(1).build().unwrap();
+
+        let vm = pool.get().await.unwrap();
+        assert!(matches!(*vm, PooledVm::Worker(_)));
+
+        let (ret, ready) = vm
+            .with(|ctx| {
+                let ret = ctx.eval::<i32, _>("1 + 2")?;
+                Ok((ret, ctx.globals().get::<_, bool>("ready")?))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(ret, 3, "{runtime:?}");
+        assert!(ready, "{runtime:?}");
+    }
+}
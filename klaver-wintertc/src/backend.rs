@@ -13,6 +13,7 @@ impl Backend for () {
 #[cfg(feature = "tokio")]
 mod tokio_backend {
     use futures::future::LocalBoxFuture;
+    use klaver_core::throw_if;
     use relative_path::RelativePath;
     use rquickjs::Ctx;
     use vfs::{VFS, VPathExt, boxed::LocalBoxVPath};
@@ -20,23 +21,42 @@ mod tokio_backend {
     use crate::{
         Settings,
         backend::Backend,
+        fetch::FetchSettings,
         fs::{FileSystemBackend, FileSystemSettings},
         timers::TimerBackend,
     };
+    #[cfg(feature = "serve")]
+    use {
+        crate::serve::{Connection, Listener, ServerBackend},
+        std::{io, net::SocketAddr},
+    };
 
     #[derive(Default)]
     pub struct TokioBackend;
 
     impl Backend for TokioBackend {
-        fn init(&self, _ctx: &Ctx<'_>, settings: &mut Settings) -> rquickjs::Result<()> {
+        fn init(&self, ctx: &Ctx<'_>, settings: &mut Settings) -> rquickjs::Result<()> {
             settings.set_timers(TokioBackend);
             settings
                 .set_file_system(FileSystemSettings::new().with_backend(Box::new(TokioBackend)));
-            settings.set_http_client(reqwest::Client::new());
+            settings.set_http_client(throw_if!(ctx, http_client(&settings.fetch()).build()));
+            #[cfg(feature = "serve")]
+            settings.set_server(TokioBackend);
             Ok(())
         }
     }
 
+    fn http_client(fetch: &FetchSettings) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(timeout) = fetch.connect_timeout() {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        // Redirects are followed by `Client`, so every hop is checked against the permissions
+        builder.redirect(reqwest::redirect::Policy::none())
+    }
+
     impl TimerBackend for TokioBackend {
         type Timer = tokio::time::Sleep;
 
@@ -62,6 +82,34 @@ mod tokio_backend {
             })
         }
     }
+
+    #[cfg(feature = "serve")]
+    impl ServerBackend for TokioBackend {
+        fn bind(&self, addr: SocketAddr) -> io::Result<Box<dyn Listener>> {
+            let listener = std::net::TcpListener::bind(addr)?;
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            Ok(Box::new(TokioListener(listener)))
+        }
+    }
+
+    #[cfg(feature = "serve")]
+    struct TokioListener(tokio::net::TcpListener);
+
+    #[cfg(feature = "serve")]
+    impl Listener for TokioListener {
+        fn local_addr(&self) -> io::Result<SocketAddr> {
+            self.0.local_addr()
+        }
+
+        fn accept(&self) -> LocalBoxFuture<'_, io::Result<(Box<dyn Connection>, SocketAddr)>> {
+            Box::pin(async move {
+                let (stream, addr) = self.0.accept().await?;
+                let io: Box<dyn Connection> = Box::new(hyper_util::rt::TokioIo::new(stream));
+                Ok((io, addr))
+            })
+        }
+    }
 }
 use rquickjs::Ctx;
 #[cfg(feature = "tokio")]
@@ -71,7 +119,7 @@ pub use tokio_backend::TokioBackend;
 mod compio_backend {
 
     use futures::future::LocalBoxFuture;
-    use klaver_core::throw_if;
+    use klaver_core::{throw, throw_if};
     use relative_path::RelativePath;
     use rquickjs::Ctx;
     use vfs::{VFS, VPathExt, boxed::LocalBoxVPath};
@@ -82,6 +130,11 @@ mod compio_backend {
         fs::{FileSystemBackend, FileSystemSettings},
         timers::TimerBackend,
     };
+    #[cfg(feature = "serve")]
+    use {
+        crate::serve::{Connection, Listener, ServerBackend},
+        std::{io, net::SocketAddr},
+    };
 
     #[derive(Default)]
     pub struct CompioBackend;
@@ -91,7 +144,13 @@ mod compio_backend {
             settings.set_timers(CompioBackend);
             settings
                 .set_file_system(FileSystemSettings::new().with_backend(Box::new(CompioBackend)));
+            // cyper has no connect timeout. Redirects are followed by `Client` for every backend
+            if settings.fetch().connect_timeout().is_some() {
+                throw!(ctx, "The compio backend does not support a connect timeout")
+            }
             settings.set_local_http_client(throw_if!(ctx, cyper::Client::new()));
+            #[cfg(feature = "serve")]
+            settings.set_server(CompioBackend);
 
             Ok(())
         }
@@ -123,6 +182,33 @@ mod compio_backend {
             })
         }
     }
+
+    #[cfg(feature = "serve")]
+    impl ServerBackend for CompioBackend {
+        fn bind(&self, addr: SocketAddr) -> io::Result<Box<dyn Listener>> {
+            let listener = std::net::TcpListener::bind(addr)?;
+            let listener = compio::net::TcpListener::from_std(listener)?;
+            Ok(Box::new(CompioListener(listener)))
+        }
+    }
+
+    #[cfg(feature = "serve")]
+    struct CompioListener(compio::net::TcpListener);
+
+    #[cfg(feature = "serve")]
+    impl Listener for CompioListener {
+        fn local_addr(&self) -> io::Result<SocketAddr> {
+            self.0.local_addr()
+        }
+
+        fn accept(&self) -> LocalBoxFuture<'_, io::Result<(Box<dyn Connection>, SocketAddr)>> {
+            Box::pin(async move {
+                let (stream, addr) = self.0.accept().await?;
+                let io: Box<dyn Connection> = Box::new(cyper_core::HyperStream::new_plain(stream));
+                Ok((io, addr))
+            })
+        }
+    }
 }
 
 #[cfg(feature = "compio")]
@@ -8,11 +8,12 @@ use klaver_core::{
     },
 };
 use rquickjs::{
-    ArrayBuffer, Class, Ctx, FromJs, JsLifetime, Object, String,
+    ArrayBuffer, Class, Ctx, FromJs, JsLifetime, Object, String, Value,
     class::{JsClass, Trace},
-    prelude::Opt,
+    prelude::{Opt, This},
 };
 
+use crate::file::File;
 #[cfg(feature = "streams")]
 use crate::streams::{QueuingStrategy, ReadableStream, readable::One};
 
@@ -51,36 +52,61 @@ impl<'js> Blob<'js> {
     }
 
     #[qjs(rename = "arrayBuffer")]
-    pub async fn array_buffer(&self, _ctx: Ctx<'js>) -> rquickjs::Result<ArrayBuffer<'js>> {
-        Ok(self.buffer.clone())
+    pub async fn array_buffer(
+        this: This<Value<'js>>,
+        ctx: Ctx<'js>,
+    ) -> rquickjs::Result<ArrayBuffer<'js>> {
+        Ok(Blob::from_this(&ctx, this.0)?.buffer)
     }
 
-    pub async fn bytes(&self) -> rquickjs::Result<rquickjs::TypedArray<'js, u8>> {
-        rquickjs::TypedArray::from_arraybuffer(self.buffer.clone())
+    pub async fn bytes(
+        this: This<Value<'js>>,
+        ctx: Ctx<'js>,
+    ) -> rquickjs::Result<rquickjs::TypedArray<'js, u8>> {
+        rquickjs::TypedArray::from_arraybuffer(Blob::from_this(&ctx, this.0)?.buffer)
     }
 
-    pub async fn text(&self, ctx: Ctx<'js>) -> rquickjs::Result<std::string::String> {
-        let Some(bytes) = self.buffer.as_bytes() else {
+    pub async fn text(
+        this: This<Value<'js>>,
+        ctx: Ctx<'js>,
+    ) -> rquickjs::Result<std::string::String> {
+        let blob = Blob::from_this(&ctx, this.0)?;
+        let Some(bytes) = blob.buffer.as_bytes() else {
             throw!(@type ctx, "Buffer is detached")
         };
         Ok(throw_if!(ctx, str::from_utf8(bytes).map(|m| m.to_string())))
     }
 
     pub fn stream(
-        &self,
+        this: This<Value<'js>>,
         ctx: Ctx<'js>,
         strategy: Option<QueuingStrategy<'js>>,
     ) -> rquickjs::Result<ReadableStream<'js>> {
-        ReadableStream::from_native(
-            &ctx,
-            One::new(Buffer::ArrayBuffer(self.buffer.clone())),
-            strategy,
-        )
+        let blob = Blob::from_this(&ctx, this.0)?;
+        ReadableStream::from_native(&ctx, One::new(Buffer::ArrayBuffer(blob.buffer)), strategy)
     }
 
     #[qjs(get, enumerable)]
-    pub fn size(&self) -> usize {
-        self.buffer.len()
+    pub fn size(this: This<Value<'js>>, ctx: Ctx<'js>) -> rquickjs::Result<usize> {
+        Ok(Blob::from_this(&ctx, this.0)?.buffer.len())
+    }
+}
+
+impl<'js> Blob<'js> {
+    /// The blob the methods are called on. Subclasses like `File` inherit the methods
+    /// of the prototype, so it is a blob sharing their data for them
+    fn from_this(ctx: &Ctx<'js>, this: Value<'js>) -> rquickjs::Result<Blob<'js>> {
+        if let Ok(blob) = Class::<Blob<'js>>::from_js(ctx, this.clone()) {
+            let blob = blob.borrow();
+            Ok(Blob {
+                buffer: blob.buffer.clone(),
+                ty: blob.ty.clone(),
+            })
+        } else if let Ok(file) = Class::<File<'js>>::from_js(ctx, this) {
+            Ok(file.borrow().to_blob())
+        } else {
+            throw!(@type ctx, "Expected a Blob")
+        }
     }
 }
 
@@ -130,6 +156,11 @@ impl<'js> FromJs<'js> for BlobInit<'js> {
     fn from_js(ctx: &Ctx<'js>, value: rquickjs::Value<'js>) -> rquickjs::Result<Self> {
         if let Ok(blob) = Class::<Blob<'js>>::from_js(ctx, value.clone()) {
             Ok(Self::Blob(blob))
+        } else if let Ok(file) = Class::<File<'js>>::from_js(ctx, value.clone()) {
+            Ok(Self::Blob(Class::instance(
+                ctx.clone(),
+                file.borrow().to_blob(),
+            )?))
         } else if let Ok(buffer) = Buffer::from_js(ctx, value.clone()) {
             Ok(Self::Buffer(buffer))
         } else if let Ok(string) = StringRef::from_js(ctx, value) {
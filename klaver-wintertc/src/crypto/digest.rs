@@ -1,8 +1,15 @@
+#[cfg(feature = "streams")]
+use klaver_core::value::StringRef;
 use klaver_core::{create_export, throw, value::Buffer};
+#[cfg(feature = "streams")]
+use rquickjs::Class;
 use rquickjs::{ArrayBuffer, Ctx, FromJs, IntoJs, class::Trace};
 use sha1::{Sha1, digest::Digest as _};
 use sha2::{Sha256, Sha384, Sha512};
 
+#[cfg(feature = "streams")]
+use crate::streams::ReadableStream;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Trace)]
 pub enum Algo {
     Sha1,
@@ -20,6 +27,30 @@ impl Algo {
             Algo::Sha512 => DigestImpl::Sha512(Sha512::new()),
         }
     }
+
+    /// The WebCrypto name of the algorithm
+    pub fn name(self) -> &'static str {
+        match self {
+            Algo::Sha1 => "SHA-1",
+            Algo::Sha256 => "SHA-256",
+            Algo::Sha384 => "SHA-384",
+            Algo::Sha512 => "SHA-512",
+        }
+    }
+
+    /// Block size in bits, used as the default HMAC key length
+    pub fn block_size(self) -> usize {
+        match self {
+            Algo::Sha1 | Algo::Sha256 => 512,
+            Algo::Sha384 | Algo::Sha512 => 1024,
+        }
+    }
+
+    pub fn hash(self, data: &[u8]) -> Vec<u8> {
+        let mut inner = self.to_impl();
+        inner.update(data);
+        inner.digest()
+    }
 }
 
 impl<'js> FromJs<'js> for Algo {
@@ -27,14 +58,18 @@ impl<'js> FromJs<'js> for Algo {
         ctx: &rquickjs::prelude::Ctx<'js>,
         value: rquickjs::Value<'js>,
     ) -> rquickjs::Result<Self> {
-        let str = String::from_js(ctx, value)?;
-
-        let algo = match &*str {
-            "sha1" | "SHA-1" => Algo::Sha1,
-            "sha256" | "sha2" | "SHA-256" => Algo::Sha256,
-            "sha384" | "SHA-384" => Algo::Sha384,
-            "sha512" | "SHA-512" => Algo::Sha512,
-            _ => return Err(rquickjs::Error::new_from_js("string", "algo")),
+        // An algorithm identifier is either a name or a dictionary with a name member
+        let str = match value.as_object() {
+            Some(obj) => obj.get::<_, String>("name")?,
+            None => String::from_js(ctx, value)?,
+        };
+
+        let algo = match &*str.to_ascii_uppercase() {
+            "SHA1" | "SHA-1" => Algo::Sha1,
+            "SHA256" | "SHA2" | "SHA-256" => Algo::Sha256,
+            "SHA384" | "SHA-384" => Algo::Sha384,
+            "SHA512" | "SHA-512" => Algo::Sha512,
+            _ => throw!(@type ctx, format!("Unsupported hash algorithm: {str}")),
         };
 
         Ok(algo)
@@ -114,3 +149,29 @@ impl<'js> Trace<'js> for Digest {
 }
 
 create_export!(Digest);
+
+/// Hash a stream chunk by chunk, without buffering it in memory
+#[cfg(feature = "streams")]
+pub async fn digest_stream<'js>(
+    ctx: Ctx<'js>,
+    algo: Algo,
+    stream: Class<'js, ReadableStream<'js>>,
+) -> rquickjs::Result<ArrayBuffer<'js>> {
+    let reader = stream.borrow().get_reader(ctx.clone())?;
+    let mut inner = algo.to_impl();
+
+    while let Some(chunk) = reader.read_native(&ctx).await? {
+        if chunk.is_string() {
+            let chunk = StringRef::from_js(&ctx, chunk)?;
+            inner.update(chunk.as_bytes());
+        } else {
+            let buffer = Buffer::from_js(&ctx, chunk)?;
+            let Some(bytes) = buffer.as_raw() else {
+                throw!(ctx, "buffer is detached")
+            };
+            inner.update(bytes.slice());
+        }
+    }
+
+    ArrayBuffer::new(ctx, inner.digest())
+}
@@ -0,0 +1,169 @@
+use p256::{
+    ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier},
+    elliptic_curve::sec1::ToEncodedPoint,
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey},
+};
+
+use super::key::NamedCurve;
+
+/// Runs `$body` with `$curve` aliased to the crate implementing the named curve
+macro_rules! with_curve {
+    ($named: expr, $curve: ident => $body: expr) => {
+        match $named {
+            NamedCurve::P256 => {
+                use p256 as $curve;
+                $body
+            }
+            NamedCurve::P384 => {
+                use p384 as $curve;
+                $body
+            }
+        }
+    };
+}
+
+macro_rules! dispatch {
+    ($value: expr, $key: ident => $body: expr) => {
+        match $value {
+            Self::P256($key) => $body,
+            Self::P384($key) => $body,
+        }
+    };
+    ($value: expr, $key: ident, $curve: ident => $body: expr) => {
+        match $value {
+            Self::P256($key) => {
+                use p256 as $curve;
+                $body
+            }
+            Self::P384($key) => {
+                use p384 as $curve;
+                $body
+            }
+        }
+    };
+}
+
+pub enum EcPrivateKey {
+    P256(p256::SecretKey),
+    P384(p384::SecretKey),
+}
+
+impl From<p256::SecretKey> for EcPrivateKey {
+    fn from(value: p256::SecretKey) -> Self {
+        EcPrivateKey::P256(value)
+    }
+}
+
+impl From<p384::SecretKey> for EcPrivateKey {
+    fn from(value: p384::SecretKey) -> Self {
+        EcPrivateKey::P384(value)
+    }
+}
+
+impl EcPrivateKey {
+    pub fn from_pkcs8(named: NamedCurve, der: &[u8]) -> Option<EcPrivateKey> {
+        with_curve!(named, curve => curve::SecretKey::from_pkcs8_der(der).ok().map(Into::into))
+    }
+
+    pub fn from_scalar(named: NamedCurve, d: &[u8]) -> Option<EcPrivateKey> {
+        with_curve!(named, curve => curve::SecretKey::from_slice(d).ok().map(Into::into))
+    }
+
+    pub fn curve(&self) -> NamedCurve {
+        match self {
+            EcPrivateKey::P256(_) => NamedCurve::P256,
+            EcPrivateKey::P384(_) => NamedCurve::P384,
+        }
+    }
+
+    pub fn to_pkcs8(&self) -> Option<Vec<u8>> {
+        dispatch!(self, key => key.to_pkcs8_der().ok().map(|m| m.as_bytes().to_vec()))
+    }
+
+    pub fn scalar(&self) -> Vec<u8> {
+        dispatch!(self, key => key.to_bytes().to_vec())
+    }
+
+    pub fn public_key(&self) -> EcPublicKey {
+        dispatch!(self, key => key.public_key().into())
+    }
+
+    /// Sign a message digest. The signature is the concatenation of `r` and `s`, as used by WebCrypto
+    pub fn sign_prehash(&self, prehash: &[u8]) -> Option<Vec<u8>> {
+        dispatch!(self, key, curve => {
+            let signature: curve::ecdsa::Signature =
+                curve::ecdsa::SigningKey::from(key).sign_prehash(prehash).ok()?;
+            Some(signature.to_bytes().to_vec())
+        })
+    }
+}
+
+pub enum EcPublicKey {
+    P256(p256::PublicKey),
+    P384(p384::PublicKey),
+}
+
+impl From<p256::PublicKey> for EcPublicKey {
+    fn from(value: p256::PublicKey) -> Self {
+        EcPublicKey::P256(value)
+    }
+}
+
+impl From<p384::PublicKey> for EcPublicKey {
+    fn from(value: p384::PublicKey) -> Self {
+        EcPublicKey::P384(value)
+    }
+}
+
+impl EcPublicKey {
+    pub fn from_spki(named: NamedCurve, der: &[u8]) -> Option<EcPublicKey> {
+        with_curve!(named, curve => curve::PublicKey::from_public_key_der(der).ok().map(Into::into))
+    }
+
+    pub fn from_sec1(named: NamedCurve, bytes: &[u8]) -> Option<EcPublicKey> {
+        with_curve!(named, curve => curve::PublicKey::from_sec1_bytes(bytes).ok().map(Into::into))
+    }
+
+    /// Build a key from the affine coordinates, as found in a JWK
+    pub fn from_coordinates(named: NamedCurve, x: &[u8], y: &[u8]) -> Option<EcPublicKey> {
+        let mut sec1 = Vec::with_capacity(1 + x.len() + y.len());
+        sec1.push(0x04);
+        sec1.extend_from_slice(x);
+        sec1.extend_from_slice(y);
+        Self::from_sec1(named, &sec1)
+    }
+
+    pub fn curve(&self) -> NamedCurve {
+        match self {
+            EcPublicKey::P256(_) => NamedCurve::P256,
+            EcPublicKey::P384(_) => NamedCurve::P384,
+        }
+    }
+
+    pub fn to_spki(&self) -> Option<Vec<u8>> {
+        dispatch!(self, key => key.to_public_key_der().ok().map(|m| m.as_bytes().to_vec()))
+    }
+
+    /// Uncompressed SEC1 encoding of the point
+    pub fn to_sec1(&self) -> Vec<u8> {
+        dispatch!(self, key => key.to_encoded_point(false).as_bytes().to_vec())
+    }
+
+    /// The affine coordinates of the point
+    pub fn coordinates(&self) -> (Vec<u8>, Vec<u8>) {
+        let sec1 = self.to_sec1();
+        let (x, y) = sec1[1..].split_at((sec1.len() - 1) / 2);
+        (x.to_vec(), y.to_vec())
+    }
+
+    pub fn verify_prehash(&self, prehash: &[u8], signature: &[u8]) -> bool {
+        dispatch!(self, key, curve => {
+            let Ok(signature) = curve::ecdsa::Signature::from_slice(signature) else {
+                return false;
+            };
+            curve::ecdsa::VerifyingKey::from(key)
+                .verify_prehash(prehash, &signature)
+                .is_ok()
+        })
+    }
+}
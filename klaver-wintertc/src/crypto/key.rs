@@ -0,0 +1,186 @@
+use klaver_core::{create_export, throw};
+use rquickjs::{Ctx, FromJs, JsLifetime, Object, class::Trace};
+
+use super::{
+    digest::Algo,
+    ec::{EcPrivateKey, EcPublicKey},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Secret,
+    Public,
+    Private,
+}
+
+impl KeyType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyType::Secret => "secret",
+            KeyType::Public => "public",
+            KeyType::Private => "private",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamedCurve {
+    P256,
+    P384,
+}
+
+impl NamedCurve {
+    pub fn name(self) -> &'static str {
+        match self {
+            NamedCurve::P256 => "P-256",
+            NamedCurve::P384 => "P-384",
+        }
+    }
+}
+
+impl<'js> FromJs<'js> for NamedCurve {
+    fn from_js(ctx: &Ctx<'js>, value: rquickjs::Value<'js>) -> rquickjs::Result<Self> {
+        let name = String::from_js(ctx, value)?;
+        match &*name {
+            "P-256" => Ok(NamedCurve::P256),
+            "P-384" => Ok(NamedCurve::P384),
+            _ => throw!(@type ctx, format!("Unsupported named curve: {name}")),
+        }
+    }
+}
+
+/// The algorithm a key was imported or derived for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    Hmac { hash: Algo, length: usize },
+    AesGcm { length: usize },
+    AesCbc { length: usize },
+    Ecdsa { curve: NamedCurve },
+    Ed25519,
+    Pbkdf2,
+    Hkdf,
+}
+
+impl KeyAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyAlgorithm::Hmac { .. } => "HMAC",
+            KeyAlgorithm::AesGcm { .. } => "AES-GCM",
+            KeyAlgorithm::AesCbc { .. } => "AES-CBC",
+            KeyAlgorithm::Ecdsa { .. } => "ECDSA",
+            KeyAlgorithm::Ed25519 => "Ed25519",
+            KeyAlgorithm::Pbkdf2 => "PBKDF2",
+            KeyAlgorithm::Hkdf => "HKDF",
+        }
+    }
+
+    fn to_object<'js>(self, ctx: &Ctx<'js>) -> rquickjs::Result<Object<'js>> {
+        let obj = Object::new(ctx.clone())?;
+        obj.set("name", self.name())?;
+
+        match self {
+            KeyAlgorithm::Hmac { hash, length } => {
+                let hash_obj = Object::new(ctx.clone())?;
+                hash_obj.set("name", hash.name())?;
+                obj.set("hash", hash_obj)?;
+                obj.set("length", length)?;
+            }
+            KeyAlgorithm::AesGcm { length } | KeyAlgorithm::AesCbc { length } => {
+                obj.set("length", length)?;
+            }
+            KeyAlgorithm::Ecdsa { curve } => {
+                obj.set("namedCurve", curve.name())?;
+            }
+            KeyAlgorithm::Ed25519 | KeyAlgorithm::Pbkdf2 | KeyAlgorithm::Hkdf => {}
+        }
+
+        Ok(obj)
+    }
+}
+
+pub enum KeyMaterial {
+    Secret(Vec<u8>),
+    EcPrivate(EcPrivateKey),
+    EcPublic(EcPublicKey),
+    Ed25519Private(ed25519_dalek::SigningKey),
+    Ed25519Public(ed25519_dalek::VerifyingKey),
+}
+
+impl KeyMaterial {
+    pub fn kind(&self) -> KeyType {
+        match self {
+            KeyMaterial::Secret(_) => KeyType::Secret,
+            KeyMaterial::EcPrivate(_) | KeyMaterial::Ed25519Private(_) => KeyType::Private,
+            KeyMaterial::EcPublic(_) | KeyMaterial::Ed25519Public(_) => KeyType::Public,
+        }
+    }
+}
+
+#[derive(JsLifetime)]
+#[rquickjs::class]
+pub struct CryptoKey {
+    pub algorithm: KeyAlgorithm,
+    pub extractable: bool,
+    pub usages: Vec<String>,
+    pub material: KeyMaterial,
+}
+
+impl CryptoKey {
+    pub fn ensure_usage(
+        &self,
+        ctx: &Ctx<'_>,
+        algorithm: &str,
+        usage: &str,
+    ) -> rquickjs::Result<()> {
+        if !self.algorithm.name().eq_ignore_ascii_case(algorithm) {
+            throw!(@type ctx, format!("Key is not a {algorithm} key"))
+        }
+
+        if !self.usages.iter().any(|m| m == usage) {
+            throw!(@type ctx, format!("Key does not support the '{usage}' operation"))
+        }
+
+        Ok(())
+    }
+
+    pub fn secret(&self, ctx: &Ctx<'_>) -> rquickjs::Result<&[u8]> {
+        match &self.material {
+            KeyMaterial::Secret(secret) => Ok(secret),
+            _ => throw!(@type ctx, "Key is not a secret key"),
+        }
+    }
+}
+
+#[rquickjs::methods]
+impl CryptoKey {
+    #[qjs(constructor)]
+    pub fn new(ctx: Ctx<'_>) -> rquickjs::Result<CryptoKey> {
+        throw!(@type ctx, "CryptoKey cannot be constructed")
+    }
+
+    #[qjs(get, rename = "type")]
+    pub fn ty(&self) -> &'static str {
+        self.material.kind().as_str()
+    }
+
+    #[qjs(get)]
+    pub fn extractable(&self) -> bool {
+        self.extractable
+    }
+
+    #[qjs(get)]
+    pub fn algorithm<'js>(&self, ctx: Ctx<'js>) -> rquickjs::Result<Object<'js>> {
+        self.algorithm.to_object(&ctx)
+    }
+
+    #[qjs(get)]
+    pub fn usages(&self) -> Vec<String> {
+        self.usages.clone()
+    }
+}
+
+impl<'js> Trace<'js> for CryptoKey {
+    fn trace<'a>(&self, _tracer: rquickjs::class::Tracer<'a, 'js>) {}
+}
+
+create_export!(CryptoKey);
@@ -1,5 +1,8 @@
 pub mod digest;
+mod ec;
+pub mod key;
 mod module;
 pub mod random;
+mod subtle;
 
 pub use self::module::CryptoModule;
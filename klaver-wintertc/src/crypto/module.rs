@@ -2,11 +2,15 @@ use klaver_core::value::Buffer;
 use klaver_core::{Exportable, Registry};
 use rquickjs::{
     Ctx, Object,
+    class::JsClass,
     module::ModuleDef,
     prelude::{Async, Func},
 };
 
-use super::digest::{Algo, Digest};
+use super::{
+    digest::{Algo, Digest},
+    key::CryptoKey,
+};
 
 pub struct CryptoModule;
 
@@ -15,6 +19,7 @@ impl ModuleDef for CryptoModule {
         decl.declare("randomUUID")?;
         decl.declare("randomValues")?;
         decl.declare("subtle")?;
+        decl.declare(CryptoKey::NAME)?;
         Ok(())
     }
 
@@ -51,6 +56,23 @@ impl<'js> Exportable<'js> for CryptoModule {
             )),
         )?;
 
+        #[cfg(feature = "streams")]
+        subtle.set(
+            "digestStream",
+            Func::new(Async(super::digest::digest_stream)),
+        )?;
+
+        subtle.set("importKey", Func::new(Async(super::subtle::import_key)))?;
+        subtle.set("exportKey", Func::new(Async(super::subtle::export_key)))?;
+        subtle.set("sign", Func::new(Async(super::subtle::sign)))?;
+        subtle.set("verify", Func::new(Async(super::subtle::verify)))?;
+        subtle.set("encrypt", Func::new(Async(super::subtle::encrypt)))?;
+        subtle.set("decrypt", Func::new(Async(super::subtle::decrypt)))?;
+        subtle.set("deriveBits", Func::new(Async(super::subtle::derive_bits)))?;
+        subtle.set("deriveKey", Func::new(Async(super::subtle::derive_key)))?;
+
+        CryptoKey::export(ctx, registry, target)?;
+
         target.set(ctx, "randomUUID", Func::new(super::random::random_uuid))?;
         target.set(ctx, "randomValues", Func::new(super::random::random_values))?;
 
@@ -0,0 +1,737 @@
+use aes_gcm::{
+    AesGcm, KeyInit,
+    aead::{Aead, Nonce, Payload, consts::U12},
+};
+use base64::prelude::*;
+use cbc::cipher::{BlockModeDecrypt, BlockModeEncrypt, KeyIvInit, block_padding::Pkcs7};
+use ed25519_dalek::{
+    Signer, SigningKey, Verifier, VerifyingKey,
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey},
+};
+use hmac::{EagerHash, Hmac, Mac};
+use klaver_core::{throw, throw_if, value::Buffer};
+use rquickjs::{ArrayBuffer, Class, Ctx, FromJs, Object, Value};
+
+use super::{
+    digest::Algo,
+    ec::{EcPrivateKey, EcPublicKey},
+    key::{CryptoKey, KeyAlgorithm, KeyMaterial, KeyType, NamedCurve},
+};
+
+macro_rules! with_hash {
+    ($algo: expr, $func: ident ( $($arg: expr),* )) => {
+        match $algo {
+            Algo::Sha1 => $func::<sha1::Sha1>($($arg),*),
+            Algo::Sha256 => $func::<sha2::Sha256>($($arg),*),
+            Algo::Sha384 => $func::<sha2::Sha384>($($arg),*),
+            Algo::Sha512 => $func::<sha2::Sha512>($($arg),*),
+        }
+    };
+}
+
+/// An algorithm identifier, either the name of the algorithm or a dictionary with its parameters
+pub struct Algorithm<'js> {
+    name: String,
+    params: Option<Object<'js>>,
+}
+
+impl<'js> FromJs<'js> for Algorithm<'js> {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> rquickjs::Result<Self> {
+        if let Some(name) = value.as_string() {
+            return Ok(Algorithm {
+                name: name.to_string()?.to_ascii_uppercase(),
+                params: None,
+            });
+        }
+
+        let params = Object::from_js(ctx, value)?;
+        let name: String = params.get("name")?;
+
+        Ok(Algorithm {
+            name: name.to_ascii_uppercase(),
+            params: Some(params),
+        })
+    }
+}
+
+impl<'js> Algorithm<'js> {
+    fn get<T: FromJs<'js>>(&self, key: &str) -> rquickjs::Result<Option<T>> {
+        match &self.params {
+            Some(params) => params.get(key),
+            None => Ok(None),
+        }
+    }
+
+    fn required<T: FromJs<'js>>(&self, ctx: &Ctx<'js>, key: &str) -> rquickjs::Result<T> {
+        match self.get(key)? {
+            Some(value) => Ok(value),
+            None => throw!(@type ctx, format!("{} requires the '{key}' parameter", self.name)),
+        }
+    }
+
+    fn bytes(&self, ctx: &Ctx<'js>, key: &str) -> rquickjs::Result<Option<Vec<u8>>> {
+        self.get::<Buffer>(key)?
+            .map(|buffer| to_bytes(ctx, &buffer))
+            .transpose()
+    }
+
+    fn required_bytes(&self, ctx: &Ctx<'js>, key: &str) -> rquickjs::Result<Vec<u8>> {
+        let buffer: Buffer = self.required(ctx, key)?;
+        to_bytes(ctx, &buffer)
+    }
+
+    fn hash(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Algo> {
+        self.required(ctx, "hash")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    Raw,
+    Jwk,
+    Pkcs8,
+    Spki,
+}
+
+impl<'js> FromJs<'js> for KeyFormat {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> rquickjs::Result<Self> {
+        let format = String::from_js(ctx, value)?;
+        match &*format {
+            "raw" => Ok(KeyFormat::Raw),
+            "jwk" => Ok(KeyFormat::Jwk),
+            "pkcs8" => Ok(KeyFormat::Pkcs8),
+            "spki" => Ok(KeyFormat::Spki),
+            _ => throw!(@type ctx, format!("Unsupported key format: {format}")),
+        }
+    }
+}
+
+enum KeyData<'js> {
+    Raw(Vec<u8>),
+    Pkcs8(Vec<u8>),
+    Spki(Vec<u8>),
+    Jwk(Object<'js>),
+}
+
+impl<'js> KeyData<'js> {
+    fn from_js(ctx: &Ctx<'js>, format: KeyFormat, value: Value<'js>) -> rquickjs::Result<Self> {
+        if format == KeyFormat::Jwk {
+            return Ok(KeyData::Jwk(Object::from_js(ctx, value)?));
+        }
+
+        let bytes = to_bytes(ctx, &Buffer::from_js(ctx, value)?)?;
+
+        Ok(match format {
+            KeyFormat::Pkcs8 => KeyData::Pkcs8(bytes),
+            KeyFormat::Spki => KeyData::Spki(bytes),
+            _ => KeyData::Raw(bytes),
+        })
+    }
+
+    fn secret(self, ctx: &Ctx<'js>) -> rquickjs::Result<Vec<u8>> {
+        match self {
+            KeyData::Raw(bytes) => Ok(bytes),
+            KeyData::Jwk(jwk) => {
+                expect_kty(ctx, &jwk, "oct")?;
+                jwk_bytes(ctx, &jwk, "k")
+            }
+            _ => throw!(@type ctx, "Secret keys can only be imported in the raw or jwk format"),
+        }
+    }
+}
+
+fn to_bytes<'js>(ctx: &Ctx<'js>, buffer: &Buffer<'js>) -> rquickjs::Result<Vec<u8>> {
+    let Some(raw) = buffer.as_raw() else {
+        throw!(ctx, "buffer is detached")
+    };
+
+    Ok(raw.slice().to_vec())
+}
+
+fn expect_kty<'js>(ctx: &Ctx<'js>, jwk: &Object<'js>, kty: &str) -> rquickjs::Result<()> {
+    let found: Option<String> = jwk.get("kty")?;
+    if found.as_deref() != Some(kty) {
+        throw!(@type ctx, format!("Expected a JWK with kty '{kty}'"))
+    }
+    Ok(())
+}
+
+fn jwk_optional_bytes<'js>(
+    ctx: &Ctx<'js>,
+    jwk: &Object<'js>,
+    member: &str,
+) -> rquickjs::Result<Option<Vec<u8>>> {
+    let Some(value) = jwk.get::<_, Option<String>>(member)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        throw_if!(@type ctx, BASE64_URL_SAFE_NO_PAD.decode(value)),
+    ))
+}
+
+fn jwk_bytes<'js>(ctx: &Ctx<'js>, jwk: &Object<'js>, member: &str) -> rquickjs::Result<Vec<u8>> {
+    match jwk_optional_bytes(ctx, jwk, member)? {
+        Some(bytes) => Ok(bytes),
+        None => throw!(@type ctx, format!("JWK is missing the '{member}' member")),
+    }
+}
+
+fn create_key(
+    ctx: &Ctx<'_>,
+    algorithm: KeyAlgorithm,
+    material: KeyMaterial,
+    extractable: bool,
+    usages: Vec<String>,
+) -> rquickjs::Result<CryptoKey> {
+    let kind = material.kind();
+
+    let allowed: &[&str] = match (algorithm, kind) {
+        (KeyAlgorithm::Hmac { .. }, _) => &["sign", "verify"],
+        (KeyAlgorithm::AesGcm { .. } | KeyAlgorithm::AesCbc { .. }, _) => &["encrypt", "decrypt"],
+        (KeyAlgorithm::Ecdsa { .. } | KeyAlgorithm::Ed25519, KeyType::Private) => &["sign"],
+        (KeyAlgorithm::Ecdsa { .. } | KeyAlgorithm::Ed25519, _) => &["verify"],
+        (KeyAlgorithm::Pbkdf2 | KeyAlgorithm::Hkdf, _) => &["deriveBits", "deriveKey"],
+    };
+
+    if let Some(usage) = usages.iter().find(|m| !allowed.contains(&m.as_str())) {
+        throw!(@type ctx, format!("Unsupported key usage for a {} key: {usage}", algorithm.name()))
+    }
+
+    if usages.is_empty() && kind != KeyType::Public {
+        throw!(@type ctx, "Key usages must not be empty")
+    }
+
+    if extractable && matches!(algorithm, KeyAlgorithm::Pbkdf2 | KeyAlgorithm::Hkdf) {
+        throw!(@type ctx, format!("{} keys cannot be extractable", algorithm.name()))
+    }
+
+    Ok(CryptoKey {
+        algorithm,
+        extractable,
+        usages,
+        material,
+    })
+}
+
+fn import_material<'js>(
+    ctx: &Ctx<'js>,
+    algorithm: &Algorithm<'js>,
+    data: KeyData<'js>,
+) -> rquickjs::Result<(KeyAlgorithm, KeyMaterial)> {
+    match &*algorithm.name {
+        "HMAC" => {
+            let hash = algorithm.hash(ctx)?;
+            let secret = data.secret(ctx)?;
+
+            let length = match algorithm.get::<usize>("length")? {
+                Some(length) if length == 0 || length.div_ceil(8) != secret.len() => {
+                    throw!(@type ctx, "HMAC key length does not match the key data")
+                }
+                Some(length) => length,
+                None => secret.len() * 8,
+            };
+
+            if secret.is_empty() {
+                throw!(@type ctx, "HMAC key must not be empty")
+            }
+
+            Ok((
+                KeyAlgorithm::Hmac { hash, length },
+                KeyMaterial::Secret(secret),
+            ))
+        }
+        "AES-GCM" | "AES-CBC" => {
+            let secret = data.secret(ctx)?;
+            let length = secret.len() * 8;
+
+            if !matches!(length, 128 | 192 | 256) {
+                throw!(@type ctx, "AES key must be 128, 192 or 256 bits")
+            }
+
+            let key_algorithm = if algorithm.name == "AES-GCM" {
+                KeyAlgorithm::AesGcm { length }
+            } else {
+                KeyAlgorithm::AesCbc { length }
+            };
+
+            Ok((key_algorithm, KeyMaterial::Secret(secret)))
+        }
+        "ECDSA" => {
+            let curve: NamedCurve = algorithm.required(ctx, "namedCurve")?;
+
+            let material = match data {
+                KeyData::Raw(bytes) => {
+                    EcPublicKey::from_sec1(curve, &bytes).map(KeyMaterial::EcPublic)
+                }
+                KeyData::Spki(der) => {
+                    EcPublicKey::from_spki(curve, &der).map(KeyMaterial::EcPublic)
+                }
+                KeyData::Pkcs8(der) => {
+                    EcPrivateKey::from_pkcs8(curve, &der).map(KeyMaterial::EcPrivate)
+                }
+                KeyData::Jwk(jwk) => {
+                    expect_kty(ctx, &jwk, "EC")?;
+
+                    let crv: NamedCurve = jwk.get("crv")?;
+                    if crv != curve {
+                        throw!(@type ctx, "JWK curve does not match the namedCurve parameter")
+                    }
+
+                    match jwk_optional_bytes(ctx, &jwk, "d")? {
+                        Some(d) => EcPrivateKey::from_scalar(curve, &d).map(KeyMaterial::EcPrivate),
+                        None => {
+                            let x = jwk_bytes(ctx, &jwk, "x")?;
+                            let y = jwk_bytes(ctx, &jwk, "y")?;
+                            EcPublicKey::from_coordinates(curve, &x, &y).map(KeyMaterial::EcPublic)
+                        }
+                    }
+                }
+            };
+
+            let Some(material) = material else {
+                throw!(@type ctx, "Invalid ECDSA key data")
+            };
+
+            Ok((KeyAlgorithm::Ecdsa { curve }, material))
+        }
+        "ED25519" => {
+            let material = match data {
+                KeyData::Raw(bytes) => VerifyingKey::try_from(&*bytes)
+                    .ok()
+                    .map(KeyMaterial::Ed25519Public),
+                KeyData::Spki(der) => VerifyingKey::from_public_key_der(&der)
+                    .ok()
+                    .map(KeyMaterial::Ed25519Public),
+                KeyData::Pkcs8(der) => SigningKey::from_pkcs8_der(&der)
+                    .ok()
+                    .map(KeyMaterial::Ed25519Private),
+                KeyData::Jwk(jwk) => {
+                    expect_kty(ctx, &jwk, "OKP")?;
+
+                    let crv: Option<String> = jwk.get("crv")?;
+                    if crv.as_deref() != Some("Ed25519") {
+                        throw!(@type ctx, "Expected a JWK with crv 'Ed25519'")
+                    }
+
+                    match jwk_optional_bytes(ctx, &jwk, "d")? {
+                        Some(d) => SigningKey::try_from(&*d)
+                            .ok()
+                            .map(KeyMaterial::Ed25519Private),
+                        None => VerifyingKey::try_from(&*jwk_bytes(ctx, &jwk, "x")?)
+                            .ok()
+                            .map(KeyMaterial::Ed25519Public),
+                    }
+                }
+            };
+
+            let Some(material) = material else {
+                throw!(@type ctx, "Invalid Ed25519 key data")
+            };
+
+            Ok((KeyAlgorithm::Ed25519, material))
+        }
+        "PBKDF2" | "HKDF" => {
+            let KeyData::Raw(secret) = data else {
+                throw!(@type ctx, format!("{} keys can only be imported in the raw format", algorithm.name))
+            };
+
+            let key_algorithm = if algorithm.name == "PBKDF2" {
+                KeyAlgorithm::Pbkdf2
+            } else {
+                KeyAlgorithm::Hkdf
+            };
+
+            Ok((key_algorithm, KeyMaterial::Secret(secret)))
+        }
+        name => throw!(@type ctx, format!("Unsupported algorithm: {name}")),
+    }
+}
+
+pub async fn import_key<'js>(
+    ctx: Ctx<'js>,
+    format: KeyFormat,
+    key_data: Value<'js>,
+    algorithm: Algorithm<'js>,
+    extractable: bool,
+    usages: Vec<String>,
+) -> rquickjs::Result<CryptoKey> {
+    let data = KeyData::from_js(&ctx, format, key_data)?;
+    let (key_algorithm, material) = import_material(&ctx, &algorithm, data)?;
+    create_key(&ctx, key_algorithm, material, extractable, usages)
+}
+
+fn export_jwk<'js>(ctx: &Ctx<'js>, key: &CryptoKey) -> rquickjs::Result<Object<'js>> {
+    let jwk = Object::new(ctx.clone())?;
+
+    let set_ec_public = |public: &EcPublicKey| -> rquickjs::Result<()> {
+        let (x, y) = public.coordinates();
+        jwk.set("kty", "EC")?;
+        jwk.set("crv", public.curve().name())?;
+        jwk.set("x", BASE64_URL_SAFE_NO_PAD.encode(x))?;
+        jwk.set("y", BASE64_URL_SAFE_NO_PAD.encode(y))?;
+        Ok(())
+    };
+
+    match &key.material {
+        KeyMaterial::Secret(secret) => {
+            jwk.set("kty", "oct")?;
+            jwk.set("k", BASE64_URL_SAFE_NO_PAD.encode(secret))?;
+        }
+        KeyMaterial::EcPrivate(private) => {
+            set_ec_public(&private.public_key())?;
+            jwk.set("d", BASE64_URL_SAFE_NO_PAD.encode(private.scalar()))?;
+        }
+        KeyMaterial::EcPublic(public) => set_ec_public(public)?,
+        KeyMaterial::Ed25519Private(private) => {
+            jwk.set("kty", "OKP")?;
+            jwk.set("crv", "Ed25519")?;
+            jwk.set(
+                "x",
+                BASE64_URL_SAFE_NO_PAD.encode(private.verifying_key().to_bytes()),
+            )?;
+            jwk.set("d", BASE64_URL_SAFE_NO_PAD.encode(private.to_bytes()))?;
+        }
+        KeyMaterial::Ed25519Public(public) => {
+            jwk.set("kty", "OKP")?;
+            jwk.set("crv", "Ed25519")?;
+            jwk.set("x", BASE64_URL_SAFE_NO_PAD.encode(public.to_bytes()))?;
+        }
+    }
+
+    let alg = match key.algorithm {
+        KeyAlgorithm::Hmac { hash, .. } => {
+            Some(format!("HS{}", hash.name().trim_start_matches("SHA-")))
+        }
+        KeyAlgorithm::AesGcm { length } => Some(format!("A{length}GCM")),
+        KeyAlgorithm::AesCbc { length } => Some(format!("A{length}CBC")),
+        _ => None,
+    };
+
+    if let Some(alg) = alg {
+        jwk.set("alg", alg)?;
+    }
+
+    jwk.set("key_ops", key.usages.clone())?;
+    jwk.set("ext", key.extractable)?;
+
+    Ok(jwk)
+}
+
+pub async fn export_key<'js>(
+    ctx: Ctx<'js>,
+    format: KeyFormat,
+    key: Class<'js, CryptoKey>,
+) -> rquickjs::Result<Value<'js>> {
+    let key = key.borrow();
+
+    if !key.extractable {
+        throw!(@type ctx, "Key is not extractable")
+    }
+
+    let bytes = match (format, &key.material) {
+        (KeyFormat::Jwk, _) => return export_jwk(&ctx, &key).map(|m| m.into_value()),
+        (KeyFormat::Raw, KeyMaterial::Secret(secret)) => Some(secret.clone()),
+        (KeyFormat::Raw, KeyMaterial::EcPublic(public)) => Some(public.to_sec1()),
+        (KeyFormat::Raw, KeyMaterial::Ed25519Public(public)) => Some(public.to_bytes().to_vec()),
+        (KeyFormat::Spki, KeyMaterial::EcPublic(public)) => public.to_spki(),
+        (KeyFormat::Spki, KeyMaterial::Ed25519Public(public)) => public
+            .to_public_key_der()
+            .ok()
+            .map(|m| m.as_bytes().to_vec()),
+        (KeyFormat::Pkcs8, KeyMaterial::EcPrivate(private)) => private.to_pkcs8(),
+        (KeyFormat::Pkcs8, KeyMaterial::Ed25519Private(private)) => {
+            private.to_pkcs8_der().ok().map(|m| m.as_bytes().to_vec())
+        }
+        _ => throw!(@type ctx, "Key cannot be exported in this format"),
+    };
+
+    let Some(bytes) = bytes else {
+        throw!(ctx, "Failed to export key")
+    };
+
+    Ok(ArrayBuffer::new(ctx, bytes)?.into_value())
+}
+
+fn hmac_sign<D: EagerHash>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<D> as hmac::KeyInit>::new_from_slice(key)
+        .expect("HMAC can be initialized with any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hmac_verify<D: EagerHash>(key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+    let mut mac = <Hmac<D> as hmac::KeyInit>::new_from_slice(key)
+        .expect("HMAC can be initialized with any key length");
+    mac.update(data);
+    mac.verify_slice(signature).is_ok()
+}
+
+pub async fn sign<'js>(
+    ctx: Ctx<'js>,
+    algorithm: Algorithm<'js>,
+    key: Class<'js, CryptoKey>,
+    data: Buffer<'js>,
+) -> rquickjs::Result<ArrayBuffer<'js>> {
+    let key = key.borrow();
+    key.ensure_usage(&ctx, &algorithm.name, "sign")?;
+
+    let data = to_bytes(&ctx, &data)?;
+
+    let signature = match (&key.algorithm, &key.material) {
+        (KeyAlgorithm::Hmac { hash, .. }, KeyMaterial::Secret(secret)) => {
+            with_hash!(hash, hmac_sign(secret, &data))
+        }
+        (KeyAlgorithm::Ecdsa { .. }, KeyMaterial::EcPrivate(private)) => {
+            let hash = algorithm.hash(&ctx)?;
+            let Some(signature) = private.sign_prehash(&hash.hash(&data)) else {
+                throw!(ctx, "Failed to sign data")
+            };
+            signature
+        }
+        (KeyAlgorithm::Ed25519, KeyMaterial::Ed25519Private(private)) => {
+            private.sign(&data).to_bytes().to_vec()
+        }
+        _ => throw!(@type ctx, "Key cannot be used for signing"),
+    };
+
+    ArrayBuffer::new(ctx, signature)
+}
+
+pub async fn verify<'js>(
+    ctx: Ctx<'js>,
+    algorithm: Algorithm<'js>,
+    key: Class<'js, CryptoKey>,
+    signature: Buffer<'js>,
+    data: Buffer<'js>,
+) -> rquickjs::Result<bool> {
+    let key = key.borrow();
+    key.ensure_usage(&ctx, &algorithm.name, "verify")?;
+
+    let signature = to_bytes(&ctx, &signature)?;
+    let data = to_bytes(&ctx, &data)?;
+
+    let valid = match (&key.algorithm, &key.material) {
+        (KeyAlgorithm::Hmac { hash, .. }, KeyMaterial::Secret(secret)) => {
+            with_hash!(hash, hmac_verify(secret, &data, &signature))
+        }
+        (KeyAlgorithm::Ecdsa { .. }, KeyMaterial::EcPublic(public)) => {
+            let hash = algorithm.hash(&ctx)?;
+            public.verify_prehash(&hash.hash(&data), &signature)
+        }
+        (KeyAlgorithm::Ed25519, KeyMaterial::Ed25519Public(public)) => {
+            match ed25519_dalek::Signature::from_slice(&signature) {
+                Ok(signature) => public.verify(&data, &signature).is_ok(),
+                Err(_) => false,
+            }
+        }
+        _ => throw!(@type ctx, "Key cannot be used for verification"),
+    };
+
+    Ok(valid)
+}
+
+type Aes192Gcm = AesGcm<aes::Aes192, U12>;
+
+fn aes_gcm_cipher<C: Aead + KeyInit>(
+    key: &[u8],
+    iv: &[u8],
+    payload: Payload<'_, '_>,
+    encrypt: bool,
+) -> Option<Vec<u8>> {
+    let cipher = C::new_from_slice(key).ok()?;
+    let nonce = Nonce::<C>::try_from(iv).ok()?;
+
+    if encrypt {
+        cipher.encrypt(&nonce, payload).ok()
+    } else {
+        cipher.decrypt(&nonce, payload).ok()
+    }
+}
+
+fn aes_cbc_cipher<C>(key: &[u8], iv: &[u8], data: &[u8], encrypt: bool) -> Option<Vec<u8>>
+where
+    cbc::Encryptor<C>: KeyIvInit + BlockModeEncrypt,
+    cbc::Decryptor<C>: KeyIvInit + BlockModeDecrypt,
+{
+    if encrypt {
+        let cipher = cbc::Encryptor::<C>::new_from_slices(key, iv).ok()?;
+        Some(cipher.encrypt_padded_vec::<Pkcs7>(data))
+    } else {
+        let cipher = cbc::Decryptor::<C>::new_from_slices(key, iv).ok()?;
+        cipher.decrypt_padded_vec::<Pkcs7>(data).ok()
+    }
+}
+
+fn aes_cipher<'js>(
+    ctx: &Ctx<'js>,
+    algorithm: &Algorithm<'js>,
+    key: &CryptoKey,
+    data: &[u8],
+    encrypt: bool,
+) -> rquickjs::Result<Vec<u8>> {
+    let secret = key.secret(ctx)?;
+    let iv = algorithm.required_bytes(ctx, "iv")?;
+
+    let output = match key.algorithm {
+        KeyAlgorithm::AesGcm { .. } => {
+            if algorithm
+                .get::<usize>("tagLength")?
+                .is_some_and(|m| m != 128)
+            {
+                throw!(@type ctx, "Only a tag length of 128 bits is supported")
+            }
+
+            if iv.len() != 12 {
+                throw!(@type ctx, "AES-GCM requires a 96 bit iv")
+            }
+
+            let aad = algorithm.bytes(ctx, "additionalData")?.unwrap_or_default();
+            let payload = Payload {
+                msg: data,
+                aad: &aad,
+            };
+
+            match secret.len() {
+                16 => aes_gcm_cipher::<aes_gcm::Aes128Gcm>(secret, &iv, payload, encrypt),
+                24 => aes_gcm_cipher::<Aes192Gcm>(secret, &iv, payload, encrypt),
+                _ => aes_gcm_cipher::<aes_gcm::Aes256Gcm>(secret, &iv, payload, encrypt),
+            }
+        }
+        KeyAlgorithm::AesCbc { .. } => {
+            if iv.len() != 16 {
+                throw!(@type ctx, "AES-CBC requires a 128 bit iv")
+            }
+
+            match secret.len() {
+                16 => aes_cbc_cipher::<aes::Aes128>(secret, &iv, data, encrypt),
+                24 => aes_cbc_cipher::<aes::Aes192>(secret, &iv, data, encrypt),
+                _ => aes_cbc_cipher::<aes::Aes256>(secret, &iv, data, encrypt),
+            }
+        }
+        _ => throw!(@type ctx, "Key cannot be used for encryption"),
+    };
+
+    match output {
+        Some(output) => Ok(output),
+        None if encrypt => throw!(ctx, "Encryption failed"),
+        None => throw!(ctx, "Decryption failed"),
+    }
+}
+
+pub async fn encrypt<'js>(
+    ctx: Ctx<'js>,
+    algorithm: Algorithm<'js>,
+    key: Class<'js, CryptoKey>,
+    data: Buffer<'js>,
+) -> rquickjs::Result<ArrayBuffer<'js>> {
+    let key = key.borrow();
+    key.ensure_usage(&ctx, &algorithm.name, "encrypt")?;
+
+    let data = to_bytes(&ctx, &data)?;
+    let output = aes_cipher(&ctx, &algorithm, &key, &data, true)?;
+
+    ArrayBuffer::new(ctx, output)
+}
+
+pub async fn decrypt<'js>(
+    ctx: Ctx<'js>,
+    algorithm: Algorithm<'js>,
+    key: Class<'js, CryptoKey>,
+    data: Buffer<'js>,
+) -> rquickjs::Result<ArrayBuffer<'js>> {
+    let key = key.borrow();
+    key.ensure_usage(&ctx, &algorithm.name, "decrypt")?;
+
+    let data = to_bytes(&ctx, &data)?;
+    let output = aes_cipher(&ctx, &algorithm, &key, &data, false)?;
+
+    ArrayBuffer::new(ctx, output)
+}
+
+fn pbkdf2_derive<D: EagerHash>(password: &[u8], salt: &[u8], rounds: u32, output: &mut [u8]) {
+    pbkdf2::pbkdf2_hmac::<D>(password, salt, rounds, output)
+}
+
+fn hkdf_derive<D: EagerHash>(ikm: &[u8], salt: &[u8], info: &[u8], output: &mut [u8]) -> bool {
+    hkdf::Hkdf::<D>::new(Some(salt), ikm)
+        .expand(info, output)
+        .is_ok()
+}
+
+fn derive<'js>(
+    ctx: &Ctx<'js>,
+    algorithm: &Algorithm<'js>,
+    key: &CryptoKey,
+    length: usize,
+) -> rquickjs::Result<Vec<u8>> {
+    if length == 0 || length % 8 != 0 {
+        throw!(@type ctx, "Length must be a non-zero multiple of 8")
+    }
+
+    let secret = key.secret(ctx)?;
+    let hash = algorithm.hash(ctx)?;
+    let salt = algorithm.required_bytes(ctx, "salt")?;
+
+    let mut output = vec![0; length / 8];
+
+    match key.algorithm {
+        KeyAlgorithm::Pbkdf2 => {
+            let iterations: u32 = algorithm.required(ctx, "iterations")?;
+            if iterations == 0 {
+                throw!(@type ctx, "PBKDF2 iterations must be greater than zero")
+            }
+
+            with_hash!(hash, pbkdf2_derive(secret, &salt, iterations, &mut output));
+        }
+        KeyAlgorithm::Hkdf => {
+            let info = algorithm.required_bytes(ctx, "info")?;
+            if !with_hash!(hash, hkdf_derive(secret, &salt, &info, &mut output)) {
+                throw!(ctx, "HKDF output length is too large")
+            }
+        }
+        _ => throw!(@type ctx, "Key cannot be used for key derivation"),
+    }
+
+    Ok(output)
+}
+
+pub async fn derive_bits<'js>(
+    ctx: Ctx<'js>,
+    algorithm: Algorithm<'js>,
+    base_key: Class<'js, CryptoKey>,
+    length: usize,
+) -> rquickjs::Result<ArrayBuffer<'js>> {
+    let key = base_key.borrow();
+    key.ensure_usage(&ctx, &algorithm.name, "deriveBits")?;
+
+    let bits = derive(&ctx, &algorithm, &key, length)?;
+
+    ArrayBuffer::new(ctx, bits)
+}
+
+pub async fn derive_key<'js>(
+    ctx: Ctx<'js>,
+    algorithm: Algorithm<'js>,
+    base_key: Class<'js, CryptoKey>,
+    derived_key_type: Algorithm<'js>,
+    extractable: bool,
+    usages: Vec<String>,
+) -> rquickjs::Result<CryptoKey> {
+    let key = base_key.borrow();
+    key.ensure_usage(&ctx, &algorithm.name, "deriveKey")?;
+
+    let length = match &*derived_key_type.name {
+        "AES-GCM" | "AES-CBC" => derived_key_type.required(&ctx, "length")?,
+        "HMAC" => match derived_key_type.get::<usize>("length")? {
+            Some(length) => length,
+            None => derived_key_type.hash(&ctx)?.block_size(),
+        },
+        name => throw!(@type ctx, format!("Unsupported derived key algorithm: {name}")),
+    };
+
+    let bits = derive(&ctx, &algorithm, &key, length)?;
+    let (key_algorithm, material) = import_material(&ctx, &derived_key_type, KeyData::Raw(bits))?;
+
+    create_key(&ctx, key_algorithm, material, extractable, usages)
+}
@@ -13,7 +13,10 @@ use std::{
     task::{Poll, ready},
 };
 
-use super::body_static::{Body, to_bytes};
+use super::{
+    FormData,
+    body_static::{Body, to_bytes},
+};
 
 pub enum BodyState<'js> {
     Empty,
@@ -102,44 +105,56 @@ impl<'js> BodyMixin<'js> {
         }
     }
 
-    pub async fn to_bytes(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Vec<u8>> {
-        let mut state = self.state.borrow_mut();
-
-        let bytes = match &mut *state {
-            BodyState::Empty => {
-                throw!(ctx, "Body is None")
-            }
-            BodyState::HttpBody(body) => {
-                let Some(body) = body.take() else {
-                    throw!(ctx, "Body is None")
-                };
+    /// Split the body in two, so it can be consumed twice.
+    /// Streaming bodies are teed, and this instance keeps one of the branches.
+    pub fn tee(&self, ctx: &Ctx<'js>) -> rquickjs::Result<BodyMixin<'js>> {
+        if let BodyState::Empty = &*self.state.borrow() {
+            return Ok(BodyMixin::empty());
+        }
 
-                let bytes = throw_if!(ctx, to_bytes(body).await);
+        if self.body_read() {
+            throw!(@type ctx, "Body has already been consumed")
+        }
 
-                drop(state);
+        if let BodyState::HttpBody(_) = &*self.state.borrow() {
+            // Turn the native body into a ReadableStream, so it can be teed below
+            self.body(ctx)?;
+        }
 
-                self.state.replace(BodyState::Empty);
+        let mut state = self.state.borrow_mut();
 
-                bytes.to_vec()
-            }
+        match &mut *state {
+            BodyState::Empty | BodyState::HttpBody(_) => Ok(BodyMixin::empty()),
             BodyState::Bytes(bytes) => {
                 let Some(data) = bytes.as_bytes() else {
                     throw!(ctx, "ArrayBuffer detached")
                 };
 
-                let data = data.to_vec();
-
-                drop(state);
-                self.state.replace(BodyState::Empty);
-
-                data
+                Ok(ArrayBuffer::new_copy(ctx.clone(), data)?.into())
             }
             BodyState::ReadableStream(stream) => {
-                let bytes = stream.borrow().to_bytes(ctx).await?;
-                drop(state);
-                self.state.replace(BodyState::Empty);
-                bytes
+                let (first, second) = stream.borrow().tee_native(ctx)?;
+                *state = BodyState::ReadableStream(first);
+                Ok(second.into())
+            }
+        }
+    }
+
+    pub async fn to_bytes(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Vec<u8>> {
+        // Take the state before awaiting, so the body can be inspected while it is read
+        let bytes = match self.state.replace(BodyState::Empty) {
+            BodyState::Empty | BodyState::HttpBody(None) => {
+                throw!(ctx, "Body is None")
+            }
+            BodyState::HttpBody(Some(body)) => throw_if!(ctx, to_bytes(body).await).to_vec(),
+            BodyState::Bytes(bytes) => {
+                let Some(data) = bytes.as_bytes() else {
+                    throw!(ctx, "ArrayBuffer detached")
+                };
+
+                data.to_vec()
             }
+            BodyState::ReadableStream(stream) => stream.borrow().to_bytes(ctx).await?,
         };
 
         Ok(bytes)
@@ -153,37 +168,17 @@ impl<'js> BodyMixin<'js> {
     }
 
     pub async fn array_buffer(&self, ctx: &Ctx<'js>) -> rquickjs::Result<ArrayBuffer<'js>> {
-        let mut state = self.state.borrow_mut();
-
-        match &mut *state {
-            BodyState::Empty => {
+        match self.state.replace(BodyState::Empty) {
+            BodyState::Empty | BodyState::HttpBody(None) => {
                 throw!(ctx, "Body is None")
             }
-            BodyState::HttpBody(body) => {
-                let Some(body) = body.take() else {
-                    throw!(ctx, "Body is None")
-                };
-
+            BodyState::HttpBody(Some(body)) => {
                 let bytes = throw_if!(ctx, to_bytes(body).await);
-
-                drop(state);
-
-                self.state.replace(BodyState::Empty);
-
                 ArrayBuffer::new(ctx.clone(), bytes.to_vec())
             }
-            BodyState::Bytes(bytes) => {
-                let bytes = bytes.clone();
-                drop(state);
-                self.state.replace(BodyState::Empty);
-
-                Ok(bytes)
-            }
+            BodyState::Bytes(bytes) => Ok(bytes),
             BodyState::ReadableStream(stream) => {
                 let bytes = stream.borrow().to_bytes(ctx).await?;
-                drop(state);
-                self.state.replace(BodyState::Empty);
-
                 ArrayBuffer::new(ctx.clone(), bytes)
             }
         }
@@ -209,6 +204,21 @@ impl<'js> BodyMixin<'js> {
         })
     }
 
+    pub async fn form_data(
+        &self,
+        ctx: &Ctx<'js>,
+        content_type: Option<String<'js>>,
+    ) -> rquickjs::Result<FormData<'js>> {
+        let Some(content_type) = content_type else {
+            throw!(@type ctx, "Missing content-type")
+        };
+
+        let content_type = content_type.to_string()?;
+        let bytes = self.to_bytes(ctx).await?;
+
+        FormData::from_body(ctx, &content_type, bytes).await
+    }
+
     // TODO: Take fast path, if body isnt a ReadableStream
     pub fn to_native_body(&self, ctx: &Ctx<'js>) -> rquickjs::Result<JsBody<'js>> {
         match self.body(ctx)? {
@@ -332,6 +342,16 @@ pin_project! {
 }
 
 impl<'js> JsBody<'js> {
+    pub fn empty() -> Self {
+        JsBody {
+            inner: JsBodyState::Empty,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self.inner, JsBodyState::Empty)
+    }
+
     pub fn new<S: Stream>(stream: S) -> Self
     where
         S: Stream<Item = Result<Vec<u8>, RuntimeError>> + 'js,
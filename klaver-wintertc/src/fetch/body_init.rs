@@ -1,14 +1,15 @@
-use crate::{blob::Blob, streams::ReadableStream};
+use crate::{blob::Blob, file::File, streams::ReadableStream};
 use klaver_core::value::{Buffer, StringExt};
 use rquickjs::{ArrayBuffer, Class, Coerced, Ctx, FromJs, String, class::Trace};
 
-use super::{Headers, URLSearchParams, body::BodyMixin};
+use super::{FormData, Headers, URLSearchParams, body::BodyMixin};
 
 #[derive(Trace)]
 pub enum BodyInit<'js> {
     Buffer(Buffer<'js>),
     String(rquickjs::String<'js>),
     UrlSearchParam(Class<'js, URLSearchParams<'js>>),
+    FormData(Class<'js, FormData<'js>>),
     Blob(Class<'js, Blob<'js>>),
     Stream(Class<'js, ReadableStream<'js>>),
 }
@@ -42,6 +43,18 @@ impl<'js> BodyInit<'js> {
                 }
                 Ok(buffer.into())
             }
+            BodyInit::FormData(form) => {
+                let (ty, body) = form.borrow().encode()?;
+                let buffer = ArrayBuffer::new(ctx.clone(), body)?;
+                if !headers.borrow().has(ctx.clone(), content_type.clone())? {
+                    headers.borrow_mut().append(
+                        ctx.clone(),
+                        content_type,
+                        Coerced(String::from_str(ctx.clone(), &ty)?),
+                    )?;
+                }
+                Ok(buffer.into())
+            }
             BodyInit::Blob(blob) => {
                 let buffer = blob.borrow().buffer.clone();
                 if let Some(ty) = blob.borrow().ty.clone() {
@@ -75,8 +88,12 @@ impl<'js> FromJs<'js> for BodyInit<'js> {
             BodyInit::Stream(Class::<ReadableStream>::from_js(ctx, value)?)
         } else if let Ok(params) = value.get::<Class<'js, URLSearchParams<'js>>>() {
             BodyInit::UrlSearchParam(params)
+        } else if let Ok(form) = value.get::<Class<'js, FormData<'js>>>() {
+            BodyInit::FormData(form)
         } else if let Ok(blob) = value.get::<Class<'js, Blob<'js>>>() {
             BodyInit::Blob(blob)
+        } else if let Ok(file) = value.get::<Class<'js, File<'js>>>() {
+            BodyInit::Blob(Class::instance(ctx.clone(), file.borrow().to_blob())?)
         } else {
             return Err(rquickjs::Error::new_from_js("value", "string or buffer"));
         };
@@ -3,16 +3,16 @@ use std::{cell::RefCell, rc::Rc};
 use futures::future::LocalBoxFuture;
 use klaver_runtime::{AsyncState, Resource, ResourceId};
 
-use http::{Request, Response, Uri};
-use klaver_core::{throw, throw_if};
-use rquickjs::{Ctx, JsLifetime};
+use http::{Method, Request, Response, StatusCode, Uri};
+use klaver_core::{Core, throw, throw_if};
+use rquickjs::{Class, Ctx, Function, JsLifetime, Value, prelude::Func};
 
 use crate::settings::WinterTcInstance;
 
 use super::{
-    RemoteBodyProducer,
+    FetchSettings, RemoteBodyProducer,
     body::{JsBody, RemoteBody},
-    body_static::Body,
+    body_static::{Body, to_bytes},
 };
 
 pub trait LocalClient {
@@ -57,9 +57,9 @@ where
 }
 
 struct ClientState {
-    local: Option<Box<dyn LocalClient>>,
-    shared: Option<Box<dyn SharedClient>>,
-    base_url: Uri,
+    local: Option<Rc<dyn LocalClient>>,
+    shared: Option<Rc<dyn SharedClient>>,
+    settings: FetchSettings,
 }
 
 #[derive(JsLifetime, Clone)]
@@ -73,7 +73,7 @@ impl Client {
             state: Rc::new(RefCell::new(ClientState {
                 local: None,
                 shared: None,
-                base_url: Uri::from_static("http://localhost:3000/"),
+                settings: FetchSettings::default(),
             })),
         }
     }
@@ -86,33 +86,178 @@ impl Client {
     }
 
     pub fn base_url(&self) -> Uri {
-        self.state.borrow().base_url.clone()
+        self.state.borrow().settings.base_url().clone()
+    }
+
+    /// Resolve a, possibly relative, url against the base url of the settings
+    pub fn resolve_url(&self, url: &str) -> Result<String, url::ParseError> {
+        self.state.borrow().settings.resolve_url(url)
+    }
+
+    pub fn settings(&self) -> FetchSettings {
+        self.state.borrow().settings.clone()
+    }
+
+    pub fn set_settings(&self, settings: FetchSettings) {
+        self.state.borrow_mut().settings = settings;
     }
 
     pub fn set_local_client<T>(&self, client: T)
     where
         T: LocalClient + 'static,
     {
-        self.state.borrow_mut().local = Some(Box::new(client));
+        self.state.borrow_mut().local = Some(Rc::new(client));
     }
 
     pub fn set_shared_client<T>(&self, client: T)
     where
         T: SharedClient + 'static,
     {
-        self.state.borrow_mut().shared = Some(Box::new(client));
+        self.state.borrow_mut().shared = Some(Rc::new(client));
     }
 
     pub async fn send<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        mut req: Request<JsBody<'js>>,
+    ) -> rquickjs::Result<Response<Body>> {
+        let settings = self.settings();
+
+        *req.uri_mut() = throw_if!(@type ctx, settings.resolve(req.uri()));
+
+        for (key, value) in settings.default_headers() {
+            if !req.headers().contains_key(key) {
+                req.headers_mut().insert(key.clone(), value.clone());
+            }
+        }
+
+        #[cfg(feature = "timers")]
+        if let Some(timeout) = settings.timeout() {
+            use futures::FutureExt;
+
+            let timer = WinterTcInstance::from_ctx(ctx)?
+                .borrow()
+                .settings()
+                .timers()
+                .create_timer(ctx, std::time::Instant::now() + timeout)?;
+
+            return futures::select! {
+                ret = self.follow(ctx, &settings, req).fuse() => ret,
+                _ = timer.fuse() => throw!(ctx, "Request timed out"),
+            };
+        }
+
+        self.follow(ctx, &settings, req).await
+    }
+
+    /// Send the request and follow redirects, checking the host lists and
+    /// net permissions before every hop
+    async fn follow<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        settings: &FetchSettings,
+        mut req: Request<JsBody<'js>>,
+    ) -> rquickjs::Result<Response<Body>> {
+        let max_redirects = settings.max_redirects().unwrap_or(DEFAULT_MAX_REDIRECTS);
+        let mut redirects = 0;
+
+        loop {
+            self.check_uri(ctx, settings, req.uri())?;
+
+            let method = req.method().clone();
+            let uri = req.uri().clone();
+            let version = req.version();
+            let headers = req.headers().clone();
+            let empty = req.body().is_empty();
+
+            let resp = self.dispatch(ctx, req).await?;
+
+            let status = resp.status();
+            if !status.is_redirection() || max_redirects == 0 {
+                return Ok(resp);
+            }
+
+            let Some(location) = resp.headers().get(http::header::LOCATION) else {
+                return Ok(resp);
+            };
+
+            redirects += 1;
+            if redirects > max_redirects {
+                throw!(@type ctx, "Too many redirects")
+            }
+
+            let location = throw_if!(@type ctx, location.to_str());
+            let next = throw_if!(@type ctx, resolve_location(&uri, location));
+
+            let (method, body) = match status {
+                StatusCode::SEE_OTHER if method != Method::HEAD => (Method::GET, JsBody::empty()),
+                StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND if method == Method::POST => {
+                    (Method::GET, JsBody::empty())
+                }
+                _ if empty => (method, JsBody::empty()),
+                _ => throw!(@type ctx, "Can not follow a redirect with a request body"),
+            };
+
+            req = Request::new(body);
+            *req.method_mut() = method;
+            *req.uri_mut() = next;
+            *req.version_mut() = version;
+            *req.headers_mut() = headers;
+
+            if req.method() == Method::GET {
+                for name in [
+                    http::header::CONTENT_TYPE,
+                    http::header::CONTENT_LENGTH,
+                    http::header::CONTENT_ENCODING,
+                ] {
+                    req.headers_mut().remove(name);
+                }
+            }
+
+            // Don't leak credentials to other origins
+            if req.uri().authority() != uri.authority() || req.uri().scheme() != uri.scheme() {
+                req.headers_mut().remove(http::header::AUTHORIZATION);
+                req.headers_mut().remove(http::header::COOKIE);
+            }
+        }
+    }
+
+    fn check_uri(
+        &self,
+        ctx: &Ctx<'_>,
+        settings: &FetchSettings,
+        uri: &Uri,
+    ) -> rquickjs::Result<()> {
+        let Some(host) = uri.host() else {
+            throw!(@type ctx, format!("Invalid url: {}", uri))
+        };
+
+        if !settings.is_host_allowed(host) {
+            throw!(@type ctx, format!("Requests to host '{host}' are not allowed"))
+        }
+
+        let winter = WinterTcInstance::from_ctx(ctx)?;
+        if let Err(err) = winter.borrow().settings().permissions().check_net(host) {
+            return Err(err.throw(ctx));
+        }
+
+        Ok(())
+    }
+
+    async fn dispatch<'js>(
         &self,
         ctx: &Ctx<'js>,
         req: Request<JsBody<'js>>,
     ) -> rquickjs::Result<Response<Body>> {
-        let this = self.state.borrow();
+        // Cloned out of the state, so the clients and settings can be replaced while a request runs
+        let (local, shared) = {
+            let this = self.state.borrow();
+            (this.local.clone(), this.shared.clone())
+        };
 
-        if let Some(local) = &this.local {
+        if let Some(local) = local {
             local.send(&ctx, req).await
-        } else if let Some(shared) = &this.shared {
+        } else if let Some(shared) = shared {
             let (parts, body) = req.into_parts();
 
             let (body, producer) = body.into_remote();
@@ -127,6 +272,13 @@ impl Client {
     }
 }
 
+const DEFAULT_MAX_REDIRECTS: usize = 20;
+
+fn resolve_location(base: &Uri, location: &str) -> Result<Uri, Box<dyn std::error::Error>> {
+    let base = url::Url::parse(&base.to_string())?;
+    Ok(base.join(location)?.as_str().parse()?)
+}
+
 #[cfg(feature = "reqwest")]
 impl SharedClient for reqwest::Client {
     fn send<'a>(
@@ -164,8 +316,6 @@ impl LocalClient for cyper::Client {
     ) -> LocalBoxFuture<'a, rquickjs::Result<Response<Body>>> {
         use futures::TryStreamExt;
         Box::pin(async {
-            use crate::fetch::body_static::to_bytes;
-
             let (parts, body) = req.into_parts();
 
             let bytes = throw_if!(ctx, to_bytes(body).await);
@@ -197,6 +347,128 @@ impl LocalClient for cyper::Client {
     }
 }
 
+type MockHandler = Rc<dyn Fn(&Request<bytes::Bytes>) -> Option<Response<Body>>>;
+
+/// Key of the javascript fetch handler in the context's [`Core`]
+const MOCK_FETCH_HANDLER: &str = "MockFetchHandler";
+
+#[derive(Default)]
+struct MockState {
+    handlers: Vec<MockHandler>,
+    requests: Vec<Request<bytes::Bytes>>,
+}
+
+/// A client which never touches the network.
+/// Requests are dispatched to the registered Rust handlers, in the order they were added,
+/// and then to the javascript handler set with `setFetchHandler`.
+/// Every request is recorded, and can be inspected with [`MockClient::requests`].
+#[derive(Clone, Default)]
+pub struct MockClient {
+    state: Rc<RefCell<MockState>>,
+}
+
+impl MockClient {
+    pub fn new() -> MockClient {
+        MockClient::default()
+    }
+
+    /// Add a handler. Returning `None` passes the request on to the next handler
+    pub fn handle<F>(&self, handler: F)
+    where
+        F: Fn(&Request<bytes::Bytes>) -> Option<Response<Body>> + 'static,
+    {
+        self.state.borrow_mut().handlers.push(Rc::new(handler));
+    }
+
+    /// The requests seen so far
+    pub fn requests(&self) -> Vec<Request<bytes::Bytes>> {
+        self.state.borrow().requests.clone()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state.borrow_mut().requests.clear();
+    }
+
+    /// Install the client as the http client for the context and define the
+    /// `setFetchHandler` global.
+    /// The javascript handler is kept in the context, so it is collected along with it
+    pub fn register<'js>(&self, ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+        Client::from_ctx(ctx)?.set_local_client(self.clone());
+
+        ctx.globals().set(
+            "setFetchHandler",
+            Func::new(|ctx: Ctx<'js>, handler: Option<Function<'js>>| {
+                Core::from_ctx(&ctx)?
+                    .borrow_mut()
+                    .register(MOCK_FETCH_HANDLER, handler)
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    async fn send_js<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        req: Request<bytes::Bytes>,
+    ) -> rquickjs::Result<Response<Body>> {
+        let handler = Core::from_ctx(ctx)?
+            .borrow()
+            .get::<Option<Function<'js>>>(MOCK_FETCH_HANDLER)?;
+
+        let Some(handler) = handler else {
+            throw!(
+                ctx,
+                format!("No fetch handler for {} {}", req.method(), req.uri())
+            )
+        };
+
+        let req = super::Request::from_native(ctx, req.map(Body::from))?;
+        let req = Class::instance(ctx.clone(), req)?;
+
+        let mut ret = handler.call::<_, Value>((req,))?;
+        if let Some(promise) = ret.clone().into_promise() {
+            ret = promise.into_future().await?;
+        }
+
+        let Ok(resp) = Class::<super::Response>::from_value(&ret) else {
+            throw!(@type ctx, "Expected fetch handler to return a Response")
+        };
+
+        let (parts, body) = resp.borrow().to_owned_native(ctx)?.into_parts();
+
+        let bytes = throw_if!(ctx, to_bytes(body).await);
+
+        Ok(Response::from_parts(parts, Body::from(bytes)))
+    }
+}
+
+impl LocalClient for MockClient {
+    fn send<'js, 'a>(
+        &'a self,
+        ctx: &'a Ctx<'js>,
+        req: Request<JsBody<'js>>,
+    ) -> LocalBoxFuture<'a, rquickjs::Result<Response<Body>>> {
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let bytes = throw_if!(ctx, to_bytes(body).await);
+            let req = Request::from_parts(parts, bytes);
+
+            self.state.borrow_mut().requests.push(req.clone());
+
+            // Handlers may use the client themselves, so none of the state is borrowed while they run
+            let handlers = self.state.borrow().handlers.clone();
+            for handler in handlers {
+                if let Some(resp) = handler(&req) {
+                    return Ok(resp);
+                }
+            }
+
+            self.send_js(ctx, req).await
+        })
+    }
+}
+
 struct ClientResourceId;
 
 impl ResourceId for ClientResourceId {
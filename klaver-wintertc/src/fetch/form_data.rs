@@ -0,0 +1,435 @@
+use std::cell::RefCell;
+
+use klaver_core::{
+    Exportable, throw, throw_if,
+    value::{
+        Pair, StringRef, TypedList,
+        iterable::{IterableProtocol, JsNativeIterator, NativeIteratorInterface},
+    },
+};
+use rquickjs::{
+    ArrayBuffer, Class, Coerced, Ctx, FromJs, Function, IntoJs, JsLifetime, String, Value,
+    class::{JsClass, Trace},
+    prelude::{Opt, This},
+};
+
+use crate::{blob::Blob, file::File};
+
+/// An entry value. Blobs are stored as files, like the spec says
+pub enum FormDataValue<'js> {
+    Text(String<'js>),
+    File(Class<'js, File<'js>>),
+}
+
+impl<'js> Trace<'js> for FormDataValue<'js> {
+    fn trace<'a>(&self, tracer: rquickjs::class::Tracer<'a, 'js>) {
+        match self {
+            FormDataValue::Text(text) => text.trace(tracer),
+            FormDataValue::File(file) => file.trace(tracer),
+        }
+    }
+}
+
+impl<'js> Clone for FormDataValue<'js> {
+    fn clone(&self) -> Self {
+        match self {
+            FormDataValue::Text(text) => FormDataValue::Text(text.clone()),
+            FormDataValue::File(file) => FormDataValue::File(file.clone()),
+        }
+    }
+}
+
+impl<'js> FromJs<'js> for FormDataValue<'js> {
+    fn from_js(ctx: &Ctx<'js>, value: Value<'js>) -> rquickjs::Result<Self> {
+        if let Ok(file) = Class::<File<'js>>::from_js(ctx, value.clone()) {
+            Ok(FormDataValue::File(file))
+        } else if let Ok(blob) = Class::<Blob<'js>>::from_js(ctx, value.clone()) {
+            let name = String::from_str(ctx.clone(), "blob")?;
+            let file = File::from_blob(&blob.borrow(), name);
+            Ok(FormDataValue::File(Class::instance(ctx.clone(), file)?))
+        } else {
+            let Coerced(text) = Coerced::<String<'js>>::from_js(ctx, value)?;
+            Ok(FormDataValue::Text(text))
+        }
+    }
+}
+
+impl<'js> IntoJs<'js> for FormDataValue<'js> {
+    fn into_js(self, ctx: &Ctx<'js>) -> rquickjs::Result<Value<'js>> {
+        match self {
+            FormDataValue::Text(text) => Ok(text.into_value()),
+            FormDataValue::File(file) => file.into_js(ctx),
+        }
+    }
+}
+
+pub struct FormDataEntry<'js> {
+    name: std::string::String,
+    value: FormDataValue<'js>,
+}
+
+impl<'js> Trace<'js> for FormDataEntry<'js> {
+    fn trace<'a>(&self, tracer: rquickjs::class::Tracer<'a, 'js>) {
+        self.value.trace(tracer);
+    }
+}
+
+#[derive(Trace)]
+#[rquickjs::class]
+pub struct FormData<'js> {
+    entries: Vec<FormDataEntry<'js>>,
+}
+
+unsafe impl<'js> JsLifetime<'js> for FormData<'js> {
+    type Changed<'to> = FormData<'to>;
+}
+
+impl<'js> FormData<'js> {
+    pub fn new_native() -> FormData<'js> {
+        FormData {
+            entries: Vec::default(),
+        }
+    }
+
+    fn push(&mut self, name: std::string::String, value: FormDataValue<'js>) {
+        self.entries.push(FormDataEntry { name, value });
+    }
+
+    fn iter(&self, kind: FormDataIteratorKind) -> FormDataIterator<'js> {
+        let items = self
+            .entries
+            .iter()
+            .map(|m| (m.name.clone(), m.value.clone()))
+            .collect::<Vec<_>>();
+
+        FormDataIterator {
+            items: RefCell::new(items.into_iter()),
+            kind,
+        }
+    }
+
+    /// Parse a `multipart/form-data` or `application/x-www-form-urlencoded` body
+    pub async fn from_body(
+        ctx: &Ctx<'js>,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> rquickjs::Result<FormData<'js>> {
+        let mut form = FormData::new_native();
+
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        match mime.as_str() {
+            "multipart/form-data" => {
+                let boundary = throw_if!(@type ctx, multer::parse_boundary(content_type));
+                let stream = futures::stream::once(async move {
+                    Ok::<_, std::convert::Infallible>(bytes::Bytes::from(body))
+                });
+
+                let mut multipart = multer::Multipart::new(stream, boundary);
+
+                while let Some(field) = throw_if!(@type ctx, multipart.next_field().await) {
+                    let name = field.name().unwrap_or_default().to_string();
+                    let filename = field.file_name().map(|m| m.to_string());
+                    let ty = field.content_type().map(|m| m.to_string());
+
+                    let data = throw_if!(@type ctx, field.bytes().await);
+
+                    // Only parts with a filename are files, a content type alone does not make one
+                    let value = if let Some(filename) = filename {
+                        let file = File::new_native(
+                            ArrayBuffer::new_copy(ctx.clone(), &data)?,
+                            ty.map(|ty| String::from_str(ctx.clone(), &ty))
+                                .transpose()?,
+                            String::from_str(ctx.clone(), &filename)?,
+                        );
+
+                        FormDataValue::File(Class::instance(ctx.clone(), file)?)
+                    } else {
+                        let text = throw_if!(@type ctx, str::from_utf8(&data));
+                        FormDataValue::Text(String::from_str(ctx.clone(), text)?)
+                    };
+
+                    form.push(name, value);
+                }
+            }
+            "application/x-www-form-urlencoded" => {
+                for (name, value) in form_urlencoded::parse(&body) {
+                    let value = String::from_str(ctx.clone(), &value)?;
+                    form.push(name.into_owned(), FormDataValue::Text(value));
+                }
+            }
+            _ => {
+                throw!(@type ctx, format!("Could not parse content as FormData: {content_type}"))
+            }
+        }
+
+        Ok(form)
+    }
+
+    /// Encode the entries as `multipart/form-data`, returning the content type and the body
+    pub fn encode(&self) -> rquickjs::Result<(std::string::String, Vec<u8>)> {
+        let boundary = generate_boundary();
+        let mut output = Vec::new();
+
+        for entry in &self.entries {
+            output.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+            output.extend_from_slice(
+                format!(
+                    "Content-Disposition: form-data; name=\"{}\"",
+                    escape_name(&entry.name)
+                )
+                .as_bytes(),
+            );
+
+            match &entry.value {
+                FormDataValue::Text(text) => {
+                    output.extend_from_slice(b"\r\n\r\n");
+                    output.extend_from_slice(StringRef::from_string(text.clone())?.as_bytes());
+                }
+                FormDataValue::File(file) => {
+                    let file = file.borrow();
+                    let filename = file.name.to_string()?;
+
+                    let ty = match &file.ty {
+                        Some(ty) => ty.to_string()?,
+                        None => "application/octet-stream".to_string(),
+                    };
+
+                    output.extend_from_slice(
+                        format!(
+                            "; filename=\"{}\"\r\nContent-Type: {ty}\r\n\r\n",
+                            escape_name(&filename)
+                        )
+                        .as_bytes(),
+                    );
+                    output.extend_from_slice(file.buffer.as_slice()?);
+                }
+            }
+
+            output.extend_from_slice(b"\r\n");
+        }
+
+        output.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+        Ok((format!("multipart/form-data; boundary={boundary}"), output))
+    }
+}
+
+#[rquickjs::methods]
+impl<'js> FormData<'js> {
+    #[qjs(constructor)]
+    pub fn new() -> rquickjs::Result<FormData<'js>> {
+        Ok(FormData::new_native())
+    }
+
+    pub fn append(
+        &mut self,
+        ctx: Ctx<'js>,
+        Coerced(name): Coerced<std::string::String>,
+        value: FormDataValue<'js>,
+        filename: Opt<std::string::String>,
+    ) -> rquickjs::Result<()> {
+        self.push(name, with_filename(&ctx, value, filename.0)?);
+        Ok(())
+    }
+
+    pub fn set(
+        &mut self,
+        ctx: Ctx<'js>,
+        Coerced(name): Coerced<std::string::String>,
+        value: FormDataValue<'js>,
+        filename: Opt<std::string::String>,
+    ) -> rquickjs::Result<()> {
+        let value = with_filename(&ctx, value, filename.0)?;
+
+        // Replace the first entry with the given name and remove the rest
+        match self.entries.iter().position(|m| m.name == name) {
+            Some(idx) => {
+                self.entries[idx].value = value;
+                let mut current = 0;
+                self.entries.retain(|m| {
+                    let keep = m.name != name || current == idx;
+                    current += 1;
+                    keep
+                });
+            }
+            None => self.push(name, value),
+        }
+
+        Ok(())
+    }
+
+    pub fn get(
+        &self,
+        Coerced(name): Coerced<std::string::String>,
+    ) -> rquickjs::Result<Option<FormDataValue<'js>>> {
+        Ok(self
+            .entries
+            .iter()
+            .find(|m| m.name == name)
+            .map(|m| m.value.clone()))
+    }
+
+    #[qjs(rename = "getAll")]
+    pub fn get_all(
+        &self,
+        ctx: Ctx<'js>,
+        Coerced(name): Coerced<std::string::String>,
+    ) -> rquickjs::Result<TypedList<'js, FormDataValue<'js>>> {
+        let list = TypedList::new(ctx)?;
+
+        for entry in self.entries.iter().filter(|m| m.name == name) {
+            list.push(entry.value.clone())?;
+        }
+
+        Ok(list)
+    }
+
+    pub fn has(&self, Coerced(name): Coerced<std::string::String>) -> bool {
+        self.entries.iter().any(|m| m.name == name)
+    }
+
+    pub fn delete(&mut self, Coerced(name): Coerced<std::string::String>) {
+        self.entries.retain(|m| m.name != name);
+    }
+
+    pub fn entries(&self) -> rquickjs::Result<JsNativeIterator<'js>> {
+        Ok(JsNativeIterator::new(
+            self.iter(FormDataIteratorKind::Entries),
+        ))
+    }
+
+    pub fn keys(&self) -> rquickjs::Result<JsNativeIterator<'js>> {
+        Ok(JsNativeIterator::new(self.iter(FormDataIteratorKind::Keys)))
+    }
+
+    pub fn values(&self) -> rquickjs::Result<JsNativeIterator<'js>> {
+        Ok(JsNativeIterator::new(
+            self.iter(FormDataIteratorKind::Values),
+        ))
+    }
+
+    #[qjs(rename = "forEach")]
+    pub fn for_each(
+        This(this): This<Class<'js, Self>>,
+        func: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        // Release the borrow before calling out, the callback might mutate the form
+        let entries = this
+            .borrow()
+            .entries
+            .iter()
+            .map(|m| (m.name.clone(), m.value.clone()))
+            .collect::<Vec<_>>();
+
+        for (name, value) in entries {
+            func.call::<_, ()>((value, name, this.clone()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'js> IterableProtocol<'js> for FormData<'js> {
+    type Iterator = FormDataIterator<'js>;
+
+    fn create_iterator(&self, _ctx: &Ctx<'js>) -> rquickjs::Result<Self::Iterator> {
+        Ok(self.iter(FormDataIteratorKind::Entries))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum FormDataIteratorKind {
+    Entries,
+    Keys,
+    Values,
+}
+
+/// Iterator over a snapshot of the entries, taken when the iterator is created.
+pub struct FormDataIterator<'js> {
+    items: RefCell<std::vec::IntoIter<(std::string::String, FormDataValue<'js>)>>,
+    kind: FormDataIteratorKind,
+}
+
+impl<'js> Trace<'js> for FormDataIterator<'js> {
+    fn trace<'a>(&self, tracer: rquickjs::class::Tracer<'a, 'js>) {
+        for (_, value) in self.items.borrow().as_slice() {
+            value.trace(tracer);
+        }
+    }
+}
+
+impl<'js> NativeIteratorInterface<'js> for FormDataIterator<'js> {
+    type Item = Value<'js>;
+
+    fn next(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Option<Self::Item>> {
+        let Some((name, value)) = self.items.borrow_mut().next() else {
+            return Ok(None);
+        };
+
+        let item = match self.kind {
+            FormDataIteratorKind::Entries => Pair(name, value).into_js(ctx)?,
+            FormDataIteratorKind::Keys => name.into_js(ctx)?,
+            FormDataIteratorKind::Values => value.into_js(ctx)?,
+        };
+
+        Ok(Some(item))
+    }
+
+    fn returns(&self, _ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+        Ok(())
+    }
+}
+
+/// A file given with a filename is replaced by a file of that name, sharing the data
+fn with_filename<'js>(
+    ctx: &Ctx<'js>,
+    value: FormDataValue<'js>,
+    filename: Option<std::string::String>,
+) -> rquickjs::Result<FormDataValue<'js>> {
+    match (value, filename) {
+        (FormDataValue::File(file), Some(filename)) => {
+            let file = {
+                let file = file.borrow();
+                File::new_native(
+                    file.buffer.clone(),
+                    file.ty.clone(),
+                    String::from_str(ctx.clone(), &filename)?,
+                )
+            };
+            Ok(FormDataValue::File(Class::instance(ctx.clone(), file)?))
+        }
+        (value, _) => Ok(value),
+    }
+}
+
+fn escape_name(name: &str) -> std::string::String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn generate_boundary() -> std::string::String {
+    format!("----KlaverFormBoundary{}", uuid::Uuid::new_v4().simple())
+}
+
+impl<'js> Exportable<'js> for FormData<'js> {
+    fn export<T>(
+        ctx: &Ctx<'js>,
+        _registry: &klaver_core::Registry,
+        target: &T,
+    ) -> rquickjs::Result<()>
+    where
+        T: klaver_core::ExportTarget<'js>,
+    {
+        target.set(ctx, FormData::NAME, Class::<Self>::create_constructor(ctx)?)?;
+        Self::add_iterable_prototype(ctx)?;
+
+        Ok(())
+    }
+}
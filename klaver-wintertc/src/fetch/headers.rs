@@ -1,15 +1,22 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
 use http::HeaderMap;
-use klaver_core::Exportable;
 use klaver_core::value::{
-    StringExt, TypedMultiMap, TypedMultiMapEntries, iterable::IterableProtocol,
-    iterable::JsNativeIterator,
+    Pair, StringExt, TypedList, TypedMultiMap,
+    iterable::{
+        IterableProtocol, JsIterable, JsNativeIterator, NativeIteratorExt, NativeIteratorInterface,
+        is_iteratable,
+    },
 };
+use klaver_core::{Exportable, throw};
 use rquickjs::{
-    Class, Coerced, Ctx, FromJs, JsLifetime, String,
+    Class, Coerced, Ctx, FromJs, Function, IntoJs, JsLifetime, String,
     class::{JsClass, Trace},
-    function::Opt,
+    function::{Opt, This},
 };
 
+const SET_COOKIE: &str = "set-cookie";
+
 #[derive(Trace)]
 pub struct HeadersInit<'js> {
     pub inner: Class<'js, Headers<'js>>,
@@ -21,16 +28,47 @@ impl<'js> FromJs<'js> for HeadersInit<'js> {
             return Ok(HeadersInit { inner: ret });
         }
 
-        let Some(obj) = value.into_object() else {
-            return Err(rquickjs::Error::new_from_js("value", "oject"));
+        let inner = TypedMultiMap::new(ctx.clone())?;
+
+        // Strings are iterable too, but never a valid init
+        let Some(obj) = value.as_object() else {
+            throw!(@type ctx, "Expected Headers, a sequence of pairs or a record")
         };
 
-        let inner = TypedMultiMap::new(ctx.clone())?;
+        if is_iteratable(&value) {
+            // We got a sequence of name/value pairs
+            let iter = JsIterable::from_js(ctx, value.clone())?.iterator()?;
 
-        for k in obj.keys::<String<'js>>() {
-            let k = k?;
-            let v: rquickjs::String = obj.get(k.clone())?;
-            inner.append(ctx, k, v)?;
+            for item in iter
+                .from_javascript::<rquickjs::Value<'js>>()
+                .into_iter(ctx)
+            {
+                let item = item?;
+                if !is_iteratable(&item) {
+                    throw!(@type ctx, "Expected a sequence of name/value pairs")
+                }
+
+                let pair = JsIterable::from_js(ctx, item)?
+                    .iterator()?
+                    .from_javascript::<rquickjs::Value<'js>>()
+                    .into_iter(ctx)
+                    .collect::<rquickjs::Result<Vec<_>>>()?;
+
+                let Ok([k, v]) = <[rquickjs::Value<'js>; 2]>::try_from(pair) else {
+                    throw!(@type ctx, "Expected a header pair of exactly a name and a value")
+                };
+
+                let Coerced(k) = Coerced::<String<'js>>::from_js(ctx, k)?;
+                let Coerced(v) = Coerced::<String<'js>>::from_js(ctx, v)?;
+                inner.append(ctx, header_name(ctx, &k)?, header_value(ctx, v)?)?;
+            }
+        } else {
+            // We got a record
+            for k in obj.keys::<String<'js>>() {
+                let k = k?;
+                let Coerced(v) = obj.get::<_, Coerced<String<'js>>>(k.clone())?;
+                inner.append(ctx, header_name(ctx, &k)?, header_value(ctx, v)?)?;
+            }
         }
 
         Ok(HeadersInit {
@@ -74,16 +112,59 @@ impl<'js> Headers<'js> {
 
         Class::instance(ctx.clone(), Headers { inner })
     }
+
+    /// Create a new instance with a copy of the entries
+    pub fn clone_native(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Headers<'js>> {
+        let inner = TypedMultiMap::new(ctx.clone())?;
+
+        for pair in self.inner.entries()?.into_iter(ctx) {
+            let pair = pair?;
+            inner.append(ctx, pair.0, pair.1)?;
+        }
+
+        Ok(Headers { inner })
+    }
+
+    /// Entries as exposed to javascript: lowercased names in sorted order, with
+    /// repeated headers combined, except for `set-cookie` which is never combined.
+    fn sorted_entries(
+        &self,
+        ctx: &Ctx<'js>,
+    ) -> rquickjs::Result<Vec<(std::string::String, std::string::String)>> {
+        let mut map = BTreeMap::<_, Vec<std::string::String>>::new();
+
+        for pair in self.inner.entries()?.into_iter(ctx) {
+            let pair = pair?;
+            map.entry(pair.0.to_string()?)
+                .or_default()
+                .push(pair.1.to_string()?);
+        }
+
+        let mut entries = Vec::with_capacity(map.len());
+
+        for (k, v) in map {
+            if k == SET_COOKIE {
+                entries.extend(v.into_iter().map(|v| (k.clone(), v)));
+            } else {
+                entries.push((k, v.join(", ")));
+            }
+        }
+
+        Ok(entries)
+    }
 }
 
 #[rquickjs::methods]
 impl<'js> Headers<'js> {
     #[qjs(constructor)]
-    pub fn new(ctx: Ctx<'js>, _init: Opt<rquickjs::Array<'js>>) -> rquickjs::Result<Self> {
-        Ok(Headers {
-            inner: TypedMultiMap::new(ctx)?,
-        })
+    pub fn new(ctx: Ctx<'js>, init: Opt<HeadersInit<'js>>) -> rquickjs::Result<Self> {
+        match init.0 {
+            // Copy the entries so the new instance does not share state with the init
+            Some(init) => init.inner.borrow().clone_native(&ctx),
+            None => Headers::new_native(ctx),
+        }
     }
+
     pub fn append(
         &mut self,
         ctx: Ctx<'js>,
@@ -91,7 +172,7 @@ impl<'js> Headers<'js> {
         Coerced(value): Coerced<String<'js>>,
     ) -> rquickjs::Result<()> {
         self.inner
-            .append(&ctx, key, value.to_lowercase(ctx.clone())?)
+            .append(&ctx, header_name(&ctx, &key)?, header_value(&ctx, value)?)
     }
 
     pub fn set(
@@ -100,7 +181,8 @@ impl<'js> Headers<'js> {
         key: String<'js>,
         Coerced(value): Coerced<String<'js>>,
     ) -> rquickjs::Result<()> {
-        self.inner.set(&ctx, key, value)
+        self.inner
+            .set(&ctx, header_name(&ctx, &key)?, header_value(&ctx, value)?)
     }
 
     pub fn get(
@@ -108,11 +190,45 @@ impl<'js> Headers<'js> {
         ctx: Ctx<'js>,
         key: String<'js>,
     ) -> rquickjs::Result<Option<rquickjs::String<'js>>> {
-        self.inner.get(key.to_lowercase(ctx)?)
+        let Some(values) = self.inner.get_all(header_name(&ctx, &key)?)? else {
+            return Ok(None);
+        };
+
+        if values.len() == 1 {
+            return values.get(0);
+        }
+
+        let values = values
+            .values()?
+            .into_iter(&ctx)
+            .map(|v| v?.to_string())
+            .collect::<rquickjs::Result<Vec<_>>>()?;
+
+        Ok(Some(String::from_str(ctx, &values.join(", "))?))
+    }
+
+    #[qjs(rename = "getSetCookie")]
+    pub fn get_set_cookie(&self, ctx: Ctx<'js>) -> rquickjs::Result<TypedList<'js, String<'js>>> {
+        let list = TypedList::new(ctx.clone())?;
+
+        if let Some(values) = self
+            .inner
+            .get_all(String::from_str(ctx.clone(), SET_COOKIE)?)?
+        {
+            for value in values.values()?.into_iter(&ctx) {
+                list.push(value?)?;
+            }
+        }
+
+        Ok(list)
     }
 
     pub fn has(&self, ctx: Ctx<'js>, key: String<'js>) -> rquickjs::Result<bool> {
-        self.inner.has(key.to_lowercase(ctx)?)
+        self.inner.has(header_name(&ctx, &key)?)
+    }
+
+    pub fn delete(&self, ctx: Ctx<'js>, key: String<'js>) -> rquickjs::Result<()> {
+        self.inner.delete(header_name(&ctx, &key)?)
     }
 
     pub fn entries(&self, ctx: Ctx<'js>) -> rquickjs::Result<Class<'js, JsNativeIterator<'js>>> {
@@ -122,20 +238,102 @@ impl<'js> Headers<'js> {
         )
     }
 
-    pub fn values(&self) -> rquickjs::Result<JsNativeIterator<'js>> {
-        Ok(JsNativeIterator::new(self.inner.values()?))
+    pub fn values(&self, ctx: Ctx<'js>) -> rquickjs::Result<JsNativeIterator<'js>> {
+        let values = self.sorted_entries(&ctx)?.into_iter().map(|(_, v)| v);
+        Ok(JsNativeIterator::new(HeadersIterator::new(values)))
     }
 
-    pub fn keys(&self) -> rquickjs::Result<JsNativeIterator<'js>> {
-        Ok(JsNativeIterator::new(self.inner.keys()?))
+    pub fn keys(&self, ctx: Ctx<'js>) -> rquickjs::Result<JsNativeIterator<'js>> {
+        let keys = self.sorted_entries(&ctx)?.into_iter().map(|(k, _)| k);
+        Ok(JsNativeIterator::new(HeadersIterator::new(keys)))
+    }
+
+    #[qjs(rename = "forEach")]
+    pub fn for_each(
+        ctx: Ctx<'js>,
+        This(this): This<Class<'js, Self>>,
+        func: Function<'js>,
+    ) -> rquickjs::Result<()> {
+        // Release the borrow before calling out, the callback might mutate the headers
+        let entries = this.borrow().sorted_entries(&ctx)?;
+
+        for (k, v) in entries {
+            func.call::<_, ()>((v, k, this.clone()))?;
+        }
+
+        Ok(())
     }
 }
 
 impl<'js> IterableProtocol<'js> for Headers<'js> {
-    type Iterator = TypedMultiMapEntries<'js, String<'js>, String<'js>>;
+    type Iterator = HeadersIterator<Pair<std::string::String, std::string::String>>;
+
+    fn create_iterator(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Self::Iterator> {
+        let entries = self
+            .sorted_entries(ctx)?
+            .into_iter()
+            .map(|(k, v)| Pair(k, v));
+        Ok(HeadersIterator::new(entries))
+    }
+}
+
+/// Lowercase the name, throwing a `TypeError` if it isn't a valid header name
+fn header_name<'js>(ctx: &Ctx<'js>, name: &String<'js>) -> rquickjs::Result<String<'js>> {
+    let name = name.to_lowercase(ctx.clone())?;
+    let str = name.to_string()?;
+
+    if http::HeaderName::from_bytes(str.as_bytes()).is_err() {
+        throw!(@type ctx, format!("Invalid header name: {str:?}"))
+    }
+
+    Ok(name)
+}
+
+/// Strip surrounding whitespace, throwing a `TypeError` if it isn't a valid header value
+fn header_value<'js>(ctx: &Ctx<'js>, value: String<'js>) -> rquickjs::Result<String<'js>> {
+    let str = value.to_string()?;
+    let trimmed = str.trim_matches([' ', '\t', '\r', '\n']);
+
+    if http::HeaderValue::from_bytes(trimmed.as_bytes()).is_err() {
+        throw!(@type ctx, format!("Invalid header value: {str:?}"))
+    }
+
+    if trimmed.len() == str.len() {
+        Ok(value)
+    } else {
+        String::from_str(ctx.clone(), trimmed)
+    }
+}
 
-    fn create_iterator(&self, _ctx: &Ctx<'js>) -> rquickjs::Result<Self::Iterator> {
-        self.inner.entries()
+/// Iterator over a snapshot of the headers, taken when the iterator is created.
+pub struct HeadersIterator<T> {
+    items: RefCell<std::vec::IntoIter<T>>,
+}
+
+impl<T> HeadersIterator<T> {
+    fn new(items: impl Iterator<Item = T>) -> HeadersIterator<T> {
+        HeadersIterator {
+            items: RefCell::new(items.collect::<Vec<_>>().into_iter()),
+        }
+    }
+}
+
+impl<'js, T> Trace<'js> for HeadersIterator<T> {
+    fn trace<'a>(&self, _tracer: rquickjs::class::Tracer<'a, 'js>) {}
+}
+
+impl<'js, T> NativeIteratorInterface<'js> for HeadersIterator<T>
+where
+    T: IntoJs<'js>,
+{
+    type Item = T;
+
+    fn next(&self, _ctx: &Ctx<'js>) -> rquickjs::Result<Option<Self::Item>> {
+        Ok(self.items.borrow_mut().next())
+    }
+
+    fn returns(&self, _ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+        Ok(())
     }
 }
 
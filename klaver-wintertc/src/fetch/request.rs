@@ -7,9 +7,10 @@ use rquickjs::{
 };
 
 use super::{
-    Headers, Method, StaticBody,
+    FormData, Headers, Method, StaticBody,
     body::{BodyMixin, JsBody},
     body_static::Body,
+    client::Client,
     request_init::RequestInit,
 };
 
@@ -48,7 +49,9 @@ impl<'js> Request<'js> {
         http::Request<JsBody<'js>>,
         Option<Class<'js, AbortSignal<'js>>>,
     )> {
-        let mut builder = http::Request::builder().uri(self.url.str_ref()?.as_str());
+        let mut builder = http::Request::builder()
+            .method(self.method.0.clone())
+            .uri(self.url.str_ref()?.as_str());
 
         let headers = self.headers.borrow();
 
@@ -74,7 +77,9 @@ impl<'js> Request<'js> {
         http::Request<StaticBody>,
         Option<Class<'js, AbortSignal<'js>>>,
     )> {
-        let mut builder = http::Request::builder().uri(self.url.str_ref()?.as_str());
+        let mut builder = http::Request::builder()
+            .method(self.method.0.clone())
+            .uri(self.url.str_ref()?.as_str());
 
         let headers = self.headers.borrow();
 
@@ -136,6 +141,11 @@ impl<'js> Request<'js> {
 
         let method = method.unwrap_or(Method(http::Method::GET));
 
+        // Relative urls are resolved against the base url of the fetch settings
+        let resolved =
+            throw_if!(@type ctx, Client::from_ctx(&ctx)?.resolve_url(url.str_ref()?.as_str()));
+        let url = String::from_str(ctx.clone(), &resolved)?;
+
         let body = if let Some(body) = body {
             let body: BodyMixin<'js> = body.to_body(&ctx, &headers)?;
             body
@@ -153,6 +163,17 @@ impl<'js> Request<'js> {
         })
     }
 
+    pub fn clone(&self, ctx: Ctx<'js>) -> rquickjs::Result<Request<'js>> {
+        Ok(Request {
+            url: self.url.clone(),
+            method: self.method.clone(),
+            headers: Class::instance(ctx.clone(), self.headers.borrow().clone_native(&ctx)?)?,
+            body: self.body.tee(&ctx)?,
+            signal: self.signal.clone(),
+            ext: self.ext.clone(),
+        })
+    }
+
     #[qjs(get, rename = "bodyRead")]
     pub fn body_read(&self) -> bool {
         self.body.body_read()
@@ -186,6 +207,16 @@ impl<'js> Request<'js> {
 
         self.body.blob(&ctx, content_type).await
     }
+
+    #[qjs(rename = "formData")]
+    pub async fn form_data(&self, ctx: Ctx<'js>) -> rquickjs::Result<FormData<'js>> {
+        let content_type = self
+            .headers
+            .borrow()
+            .get(ctx.clone(), String::from_str(ctx.clone(), "content-type")?)?;
+
+        self.body.form_data(&ctx, content_type).await
+    }
 }
 
 klaver_core::create_export!(Request<'js>);
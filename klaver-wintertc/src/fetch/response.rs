@@ -1,5 +1,5 @@
 use super::{
-    Headers, StaticBody,
+    FormData, Headers, StaticBody,
     body::{BodyMixin, JsBody},
     body_init::BodyInit,
     body_static::Body,
@@ -7,9 +7,10 @@ use super::{
 };
 use crate::{blob::Blob, streams::ReadableStream};
 use http::{Extensions, StatusCode};
-use klaver_core::{throw_if, value::StringExt, value::iterable::NativeIteratorExt};
+use klaver_core::{throw, throw_if, value::StringExt, value::iterable::NativeIteratorExt};
 use rquickjs::{
-    ArrayBuffer, Class, Ctx, JsLifetime, String, TypedArray, Value, class::Trace, prelude::Opt,
+    ArrayBuffer, Class, Coerced, Ctx, JsLifetime, String, TypedArray, Value, class::Trace,
+    prelude::Opt,
 };
 
 #[rquickjs::class]
@@ -19,6 +20,8 @@ pub struct Response<'js> {
     pub status: StatusCode,
     pub body: BodyMixin<'js>,
     pub ext: Option<Extensions>,
+    /// Set for `Response.error()`, which reports a status of 0 to javascript
+    pub error: bool,
 }
 
 impl<'js> Trace<'js> for Response<'js> {
@@ -79,6 +82,7 @@ impl<'js> Response<'js> {
             status: parts.status.into(),
             body,
             ext: parts.extensions.into(),
+            error: false,
         })
     }
 }
@@ -110,9 +114,92 @@ impl<'js> Response<'js> {
             status,
             body,
             ext: None,
+            error: false,
         })
     }
 
+    #[qjs(static, rename = "json")]
+    pub fn json_static(
+        ctx: Ctx<'js>,
+        data: Value<'js>,
+        init: Opt<ResponseInit<'js>>,
+    ) -> rquickjs::Result<Response<'js>> {
+        let Some(json) = ctx.json_stringify(data)? else {
+            throw!(@type ctx, "Value is not JSON serializable")
+        };
+
+        let resp = Response::new(ctx.clone(), Opt(Some(BodyInit::String(json))), init)?;
+
+        let content_type = String::from_str(ctx.clone(), "content-type")?;
+        if !resp
+            .headers
+            .borrow()
+            .has(ctx.clone(), content_type.clone())?
+        {
+            resp.headers.borrow_mut().set(
+                ctx.clone(),
+                content_type,
+                Coerced(String::from_str(ctx.clone(), "application/json")?),
+            )?;
+        }
+
+        Ok(resp)
+    }
+
+    #[qjs(static)]
+    pub fn redirect(
+        ctx: Ctx<'js>,
+        Coerced(url): Coerced<String<'js>>,
+        status: Opt<u16>,
+    ) -> rquickjs::Result<Response<'js>> {
+        let status = status.0.unwrap_or(302);
+
+        if !matches!(status, 301 | 302 | 303 | 307 | 308) {
+            throw!(@range ctx, format!("Invalid redirect status: {status}"))
+        }
+
+        let mut headers = Headers::new_native(ctx.clone())?;
+        headers.set(
+            ctx.clone(),
+            String::from_str(ctx.clone(), "location")?,
+            Coerced(url),
+        )?;
+
+        Ok(Response {
+            headers: Class::instance(ctx.clone(), headers)?,
+            status: throw_if!(ctx, StatusCode::from_u16(status)),
+            body: BodyMixin::empty(),
+            ext: None,
+            error: false,
+        })
+    }
+
+    #[qjs(static)]
+    pub fn error(ctx: Ctx<'js>) -> rquickjs::Result<Response<'js>> {
+        Ok(Response {
+            headers: Class::instance(ctx.clone(), Headers::new_native(ctx.clone())?)?,
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            body: BodyMixin::empty(),
+            ext: None,
+            error: true,
+        })
+    }
+
+    pub fn clone(&self, ctx: Ctx<'js>) -> rquickjs::Result<Response<'js>> {
+        Ok(Response {
+            headers: Class::instance(ctx.clone(), self.headers.borrow().clone_native(&ctx)?)?,
+            status: self.status,
+            body: self.body.tee(&ctx)?,
+            ext: self.ext.clone(),
+            error: self.error,
+        })
+    }
+
+    #[qjs(get, rename = "type")]
+    pub fn ty(&self) -> &'static str {
+        if self.error { "error" } else { "default" }
+    }
+
     #[qjs(get, rename = "bodyRead")]
     pub fn body_read(&self) -> bool {
         self.body.body_read()
@@ -124,12 +211,12 @@ impl<'js> Response<'js> {
 
     #[qjs(get)]
     pub fn status(&self) -> u16 {
-        self.status.as_u16()
+        if self.error { 0 } else { self.status.as_u16() }
     }
 
     #[qjs(get)]
     pub fn ok(&self) -> bool {
-        self.status.is_success()
+        !self.error && self.status.is_success()
     }
 
     #[qjs(get, rename = "statusText")]
@@ -158,6 +245,16 @@ impl<'js> Response<'js> {
         self.body.blob(&ctx, content_type).await
     }
 
+    #[qjs(rename = "formData")]
+    pub async fn form_data(&self, ctx: Ctx<'js>) -> rquickjs::Result<FormData<'js>> {
+        let content_type = self
+            .headers
+            .borrow()
+            .get(ctx.clone(), String::from_str(ctx.clone(), "content-type")?)?;
+
+        self.body.form_data(&ctx, content_type).await
+    }
+
     pub async fn json(&self, ctx: Ctx<'js>) -> rquickjs::Result<Value<'js>> {
         self.body.json(&ctx).await
     }
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use http::{HeaderMap, Uri};
+
+use crate::permissions::host_matches;
+
+#[derive(Debug, Clone)]
+pub struct FetchSettings {
+    base_url: Uri,
+    default_headers: HeaderMap,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    max_redirects: Option<usize>,
+    allow_hosts: Option<Vec<String>>,
+    deny_hosts: Vec<String>,
+}
+
+impl Default for FetchSettings {
+    fn default() -> Self {
+        FetchSettings {
+            base_url: Uri::from_static("http://localhost:3000/"),
+            default_headers: HeaderMap::default(),
+            connect_timeout: None,
+            timeout: None,
+            max_redirects: None,
+            allow_hosts: None,
+            deny_hosts: Vec::default(),
+        }
+    }
+}
+
+impl FetchSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Relative urls passed to `fetch` are resolved against this url
+    pub fn with_base_url(mut self, base_url: Uri) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    pub fn base_url(&self) -> &Uri {
+        &self.base_url
+    }
+
+    /// Headers added to every request, unless the request already sets them
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers = headers;
+        self
+    }
+
+    pub fn default_headers(&self) -> &HeaderMap {
+        &self.default_headers
+    }
+
+    /// Applied by the backend's http client. Backends without support, like `CompioBackend`,
+    /// fail to initialize when it is set
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    /// Timeout for the whole request, until the response headers are received.
+    /// It needs the `timers` feature, without it setting the backend fails
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Maximum number of redirects to follow, defaults to 20.
+    /// Zero disables redirects
+    pub fn with_max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = Some(max);
+        self
+    }
+
+    pub fn max_redirects(&self) -> Option<usize> {
+        self.max_redirects
+    }
+
+    /// Only allow requests to these hosts. A leading `*.` matches any subdomain
+    pub fn allow_host(mut self, host: impl Into<String>) -> Self {
+        self.allow_hosts
+            .get_or_insert_with(Vec::default)
+            .push(host.into());
+        self
+    }
+
+    /// Deny requests to this host. A leading `*.` matches any subdomain.
+    /// The deny list takes precedence over the allow list
+    pub fn deny_host(mut self, host: impl Into<String>) -> Self {
+        self.deny_hosts.push(host.into());
+        self
+    }
+
+    pub fn is_host_allowed(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let host = host.as_str();
+
+        if self.deny_hosts.iter().any(|m| host_matches(m, host)) {
+            return false;
+        }
+
+        match &self.allow_hosts {
+            Some(allow) => allow.iter().any(|m| host_matches(m, host)),
+            None => true,
+        }
+    }
+
+    /// Resolve a, possibly relative, request uri against the base url
+    pub fn resolve(&self, uri: &Uri) -> Result<Uri, url::ParseError> {
+        if uri.scheme().is_some() {
+            return Ok(uri.clone());
+        }
+
+        let joined = self.resolve_url(&uri.to_string())?;
+
+        // A url is always a valid uri
+        Ok(joined.parse().expect("valid uri"))
+    }
+
+    /// Resolve a, possibly relative, url against the base url.
+    /// Paths without a leading slash are joined onto the base url's path, like in a browser
+    pub fn resolve_url(&self, url: &str) -> Result<String, url::ParseError> {
+        match url::Url::parse(url) {
+            Ok(_) => Ok(url.to_string()),
+            Err(url::ParseError::RelativeUrlWithoutBase) => {
+                let base = url::Url::parse(&self.base_url.to_string())?;
+                Ok(base.join(url)?.into())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_lists() {
+        let settings = FetchSettings::new()
+            .allow_host("*.example.com")
+            .allow_host("localhost")
+            .deny_host("admin.example.com");
+
+        assert!(settings.is_host_allowed("api.example.com"));
+        assert!(settings.is_host_allowed("LOCALHOST"));
+        assert!(!settings.is_host_allowed("admin.example.com"));
+        assert!(!settings.is_host_allowed("example.org"));
+
+        let settings = FetchSettings::new().deny_host("*.internal");
+        assert!(settings.is_host_allowed("example.org"));
+        assert!(!settings.is_host_allowed("db.internal"));
+    }
+
+    #[test]
+    fn resolve_against_base_url() {
+        let settings =
+            FetchSettings::new().with_base_url(Uri::from_static("https://api.example.com/v1/"));
+
+        let resolve = |uri: &'static str| {
+            settings
+                .resolve(&Uri::from_static(uri))
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(resolve("/users"), "https://api.example.com/users");
+        assert_eq!(
+            resolve("http://other.example.com/"),
+            "http://other.example.com/"
+        );
+
+        let resolve_url = |url: &str| settings.resolve_url(url).unwrap();
+
+        assert_eq!(
+            resolve_url("users?id=1"),
+            "https://api.example.com/v1/users?id=1"
+        );
+        assert_eq!(resolve_url("../users"), "https://api.example.com/users");
+        assert_eq!(resolve_url("/users"), "https://api.example.com/users");
+    }
+}
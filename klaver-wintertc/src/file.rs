@@ -0,0 +1,129 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use klaver_core::Subclass;
+use rquickjs::{
+    ArrayBuffer, Class, Ctx, FromJs, JsLifetime, Object, String,
+    class::{JsClass, Trace},
+    prelude::Opt,
+};
+
+use crate::blob::{Blob, BlobInit};
+
+#[derive(Debug, JsLifetime)]
+#[rquickjs::class]
+pub struct File<'js> {
+    pub buffer: ArrayBuffer<'js>,
+    #[qjs(rename = "type", get)]
+    pub ty: Option<String<'js>>,
+    #[qjs(get)]
+    pub name: String<'js>,
+    #[qjs(rename = "lastModified", get)]
+    pub last_modified: f64,
+}
+
+impl<'js> Trace<'js> for File<'js> {
+    fn trace<'a>(&self, tracer: rquickjs::class::Tracer<'a, 'js>) {
+        self.buffer.trace(tracer);
+        self.ty.trace(tracer);
+        self.name.trace(tracer);
+    }
+}
+
+impl<'js> File<'js> {
+    pub fn new_native(
+        buffer: ArrayBuffer<'js>,
+        ty: Option<String<'js>>,
+        name: String<'js>,
+    ) -> File<'js> {
+        File {
+            buffer,
+            ty,
+            name,
+            last_modified: now(),
+        }
+    }
+
+    /// Create a file sharing the data of the blob
+    pub fn from_blob(blob: &Blob<'js>, name: String<'js>) -> File<'js> {
+        File::new_native(blob.buffer.clone(), blob.ty.clone(), name)
+    }
+
+    /// A blob sharing the data of the file
+    pub fn to_blob(&self) -> Blob<'js> {
+        Blob {
+            buffer: self.buffer.clone(),
+            ty: self.ty.clone(),
+        }
+    }
+}
+
+#[rquickjs::methods]
+impl<'js> File<'js> {
+    #[qjs(constructor)]
+    pub fn new(
+        ctx: Ctx<'js>,
+        inits: Vec<BlobInit<'js>>,
+        name: String<'js>,
+        options: Opt<FileOptions<'js>>,
+    ) -> rquickjs::Result<File<'js>> {
+        let mut data = Vec::<u8>::new();
+
+        for init in inits {
+            init.extend(&ctx, &mut data)?;
+        }
+
+        let options = options.0.unwrap_or_default();
+
+        Ok(File {
+            buffer: ArrayBuffer::new(ctx, data)?,
+            ty: options.ty,
+            name,
+            last_modified: options.last_modified.unwrap_or_else(now),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct FileOptions<'js> {
+    ty: Option<String<'js>>,
+    last_modified: Option<f64>,
+}
+
+impl<'js> FromJs<'js> for FileOptions<'js> {
+    fn from_js(ctx: &Ctx<'js>, value: rquickjs::Value<'js>) -> rquickjs::Result<Self> {
+        let obj = Object::from_js(ctx, value)?;
+
+        Ok(FileOptions {
+            ty: obj.get("type")?,
+            last_modified: obj.get("lastModified")?,
+        })
+    }
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|m| m.as_millis() as f64)
+        .unwrap_or_default()
+}
+
+// Inheritance
+
+impl<'js> Subclass<'js, Blob<'js>> for File<'js> {}
+
+// Export
+
+impl<'js> klaver_core::Exportable<'js> for File<'js> {
+    fn export<T>(
+        ctx: &Ctx<'js>,
+        _registry: &klaver_core::Registry,
+        target: &T,
+    ) -> rquickjs::Result<()>
+    where
+        T: klaver_core::ExportTarget<'js>,
+    {
+        File::inherit(ctx)?;
+        target.set(ctx, File::NAME, Class::<File>::create_constructor(ctx)?)?;
+        Ok(())
+    }
+}
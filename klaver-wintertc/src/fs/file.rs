@@ -7,7 +7,8 @@ use rquickjs::{
 };
 use vfs::{SeekFrom, VFileExt, boxed::LocalBoxVFile};
 
-#[rquickjs::class]
+/// Exported as `FileHandle`, so it does not shadow the global `File` of fetch
+#[rquickjs::class(rename = "FileHandle")]
 pub struct File<'js> {
     pub inner: AsyncLock<LocalBoxVFile>,
     #[qjs(get, rename = "fileName")]
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use klaver_core::Exportable;
 use klaver_core::{throw, throw_if};
@@ -35,10 +35,11 @@ impl<'js> FileSystem<'js> {
         ctx: Ctx<'js>,
         name: &str,
         path: LocalBoxVPath,
+        root: PathBuf,
     ) -> rquickjs::Result<FileSystem<'js>> {
         let name = String::from_str(ctx.clone(), name)?;
 
-        let root = Class::instance(ctx.clone(), FileSystemEntry { path })?;
+        let root = Class::instance(ctx.clone(), FileSystemEntry { path, root })?;
 
         Ok(FileSystem { name, root })
     }
@@ -1,3 +1,5 @@
+use std::path::{Path, PathBuf};
+
 use futures::{Stream, TryStreamExt, stream::BoxStream};
 use klaver_core::Exportable;
 use klaver_core::{
@@ -16,10 +18,14 @@ use rquickjs::{
 use vfs::boxed::LocalBoxVPath;
 
 use super::file::File;
+use crate::WinterTcInstance;
 
 #[rquickjs::class]
 pub struct FileSystemEntry {
     pub path: LocalBoxVPath,
+    /// Where the file system was opened, on the host. Every access is checked against the permissions
+    /// with the entry's path below it
+    pub root: PathBuf,
 }
 
 unsafe impl<'js> JsLifetime<'js> for FileSystemEntry {
@@ -67,6 +73,7 @@ impl FileSystemEntry {
     ) -> rquickjs::Result<FileSystemEntry> {
         Ok(FileSystemEntry {
             path: throw_if!(ctx, self.path.resolve(path.as_str())),
+            root: self.root.clone(),
         })
     }
 
@@ -75,10 +82,16 @@ impl FileSystemEntry {
         ctx: Ctx<'js>,
         this: This<Class<'js, FileSystemEntry>>,
     ) -> rquickjs::Result<Value<'js>> {
+        this.borrow().check(&ctx, false)?;
+
         let future = this.borrow().path.read_dir();
         let stream = throw_if!(ctx, future.await);
 
-        let stream = StreamAsyncIterator::new(stream.map_ok(|path| FileSystemEntry { path }));
+        let root = this.borrow().root.clone();
+        let stream = StreamAsyncIterator::new(stream.map_ok(move |path| FileSystemEntry {
+            path,
+            root: root.clone(),
+        }));
 
         let iterator = NativeAsyncIterator::new(stream);
         let iterator_class = Class::instance(ctx.clone(), iterator)?;
@@ -90,7 +103,10 @@ impl FileSystemEntry {
         ctx: Ctx<'js>,
         this: This<Class<'js, FileSystemEntry>>,
     ) -> rquickjs::Result<Value<'js>> {
-        let metadata = throw_if!(ctx, this.borrow().path.metadata().await);
+        this.borrow().check(&ctx, false)?;
+
+        let future = this.borrow().path.metadata();
+        let metadata = throw_if!(ctx, future.await);
 
         let object = Object::new(ctx.clone())?;
 
@@ -105,6 +121,14 @@ impl FileSystemEntry {
         ctx: Ctx<'js>,
         options: OpenOptions,
     ) -> rquickjs::Result<File<'js>> {
+        if options.writes() {
+            this.borrow().check(&ctx, true)?;
+        }
+
+        if options.inner.read || !options.writes() {
+            this.borrow().check(&ctx, false)?;
+        }
+
         let future = this.borrow().path.open(options.inner);
         let inner = throw_if!(ctx, future.await);
 
@@ -130,6 +154,29 @@ impl FileSystemEntry {
     }
 }
 
+impl FileSystemEntry {
+    /// The path of the entry on the host
+    pub fn host_path(&self) -> PathBuf {
+        let path = self.path.to_string();
+        self.root.join(Path::new(path.trim_start_matches('/')))
+    }
+
+    /// Check the current permissions for reading or writing the entry
+    fn check(&self, ctx: &Ctx<'_>, write: bool) -> rquickjs::Result<()> {
+        let instance = WinterTcInstance::from_ctx(ctx)?;
+        let instance = instance.borrow();
+        let permissions = instance.settings().permissions();
+
+        let path = self.host_path();
+        let ret = match write {
+            true => permissions.check_write(&path),
+            false => permissions.check_read(&path),
+        };
+
+        ret.map_err(|err| err.throw(ctx))
+    }
+}
+
 impl<'js> Exportable<'js> for FileSystemEntry {
     fn export<T>(
         ctx: &Ctx<'js>,
@@ -152,6 +199,12 @@ pub struct OpenOptions {
     inner: vfs::OpenOptions,
 }
 
+impl OpenOptions {
+    fn writes(&self) -> bool {
+        self.inner.write || self.inner.truncate || self.inner.create || self.inner.append
+    }
+}
+
 impl<'js> FromJs<'js> for OpenOptions {
     fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> rquickjs::Result<Self> {
         let obj: Object = value.get()?;
@@ -172,6 +225,7 @@ pin_project! {
     pub struct ListMap {
         #[pin]
         stream: BoxStream<'static, Result<LocalBoxVPath, vfs::Error>>,
+        root: PathBuf,
     }
 }
 
@@ -181,9 +235,11 @@ impl Stream for ListMap {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.project()
-            .stream
-            .poll_next(cx)
-            .map_ok(|path| FileSystemEntry { path })
+        let this = self.project();
+        let root = this.root;
+        this.stream.poll_next(cx).map_ok(|path| FileSystemEntry {
+            path,
+            root: root.clone(),
+        })
     }
 }
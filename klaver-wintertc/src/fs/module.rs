@@ -1,9 +1,8 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, path::PathBuf};
 
 use klaver_core::value::StringRef;
 use klaver_core::{Exportable, Registry, throw_if};
 use klaver_modules::{Global, GlobalInfo};
-use relative_path::RelativePath;
 use rquickjs::{
     Ctx,
     prelude::{Async, Func},
@@ -34,6 +33,23 @@ impl<'js> Exportable<'js> for FsModule {
             Func::new(Async(|ctx: Ctx<'js>, path: StringRef<'js>| async move {
                 let instance = WinterTcInstance::from_ctx(&ctx)?;
 
+                // Opening needs some access to the path, and every read and write below it is checked again
+                let root = {
+                    let instance = instance.borrow();
+                    let settings = instance.settings();
+                    let resolved = settings.file_system().resolve(path.as_str());
+                    let resolved = PathBuf::from(resolved.as_str());
+
+                    let permissions = settings.permissions();
+                    if permissions.check_write(&resolved).is_err() {
+                        if let Err(err) = permissions.check_read(&resolved) {
+                            return Err(err.throw(&ctx));
+                        }
+                    }
+
+                    resolved
+                };
+
                 let path = throw_if!(
                     ctx,
                     instance
@@ -44,7 +60,7 @@ impl<'js> Exportable<'js> for FsModule {
                         .await
                 );
 
-                FileSystem::new(ctx, "main", path)
+                FileSystem::new(ctx, "main", path, root)
             })),
         )?;
 
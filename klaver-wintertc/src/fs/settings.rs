@@ -42,6 +42,16 @@ impl FileSystemSettings {
         self.cwd = Some(cwd);
     }
 
+    /// The path `open` hands to the backend, relative to the working directory
+    pub fn resolve(&self, path: &str) -> RelativePathBuf {
+        if let Some(cwd) = &self.cwd {
+            let full_path = cwd.join_normalized(path);
+            full_path
+        } else {
+            RelativePathBuf::from(path)
+        }
+    }
+
     pub async fn open(&self, path: &str) -> Result<LocalBoxVPath, vfs::Error> {
         let Some(backend) = &self.backend else {
             return Err(vfs::Error::new(
@@ -50,13 +60,6 @@ impl FileSystemSettings {
             ));
         };
 
-        let path = if let Some(cwd) = &self.cwd {
-            let full_path = cwd.join_normalized(path);
-            full_path
-        } else {
-            RelativePathBuf::from(path)
-        };
-
-        backend.open(&path).await
+        backend.open(&self.resolve(path)).await
     }
 }
@@ -14,12 +14,17 @@ pub mod crypto;
 pub mod dom_exception;
 pub mod encoding;
 pub mod events;
+#[cfg(feature = "streams")]
+pub mod file;
 #[cfg(feature = "fetch")]
 pub mod fetch;
 #[cfg(feature = "fs")]
 pub mod fs;
 #[cfg(feature = "intl")]
 pub mod intl;
+pub mod permissions;
+#[cfg(feature = "serve")]
+pub mod serve;
 #[cfg(feature = "streams")]
 pub mod streams;
 #[cfg(feature = "timers")]
@@ -33,6 +38,7 @@ mod module;
 pub use self::{
     backend::Backend,
     module::WinterTC,
+    permissions::{PermissionDenied, Permissions},
     settings::{Settings, WinterTcInstance},
 };
 
@@ -25,6 +25,8 @@ impl<'js> klaver_modules::GlobalInfo for WinterTC {
         builder.global_dependency::<crate::worker::WorkerModule>();
         #[cfg(feature = "fs")]
         builder.global_dependency::<crate::fs::FsModule>();
+        #[cfg(feature = "serve")]
+        builder.global_dependency::<crate::serve::ServeModule>();
     }
 }
 
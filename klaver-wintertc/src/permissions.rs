@@ -0,0 +1,352 @@
+use core::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use rquickjs::{Class, Ctx, prelude::Opt};
+
+use crate::dom_exception::DOMException;
+
+/// Access granted for one kind of capability
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grant<T> {
+    All,
+    /// Only these entries. An empty list denies everything
+    Only(Vec<T>),
+}
+
+impl<T> Grant<T> {
+    pub fn none() -> Grant<T> {
+        Grant::Only(Vec::new())
+    }
+
+    /// Add `entry` to the allowlist. Does nothing if everything is already allowed
+    pub fn allow(&mut self, entry: T) {
+        if let Grant::Only(entries) = self {
+            entries.push(entry);
+        }
+    }
+
+    fn any(&self, check: impl Fn(&T) -> bool) -> bool {
+        match self {
+            Grant::All => true,
+            Grant::Only(entries) => entries.iter().any(check),
+        }
+    }
+}
+
+/// Permissions of the scripts running in a vm, in the spirit of Deno's `--allow-*` flags.
+/// Everything is allowed by default. Start from `Permissions::none()` to sandbox untrusted code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    /// Paths which can be opened for reading, including everything below them
+    pub read: Grant<PathBuf>,
+    /// Paths which can be opened for writing, including everything below them
+    pub write: Grant<PathBuf>,
+    /// Hosts which can be fetched or served on. A leading `*.` matches any subdomain
+    pub net: Grant<String>,
+    /// Environment variables which can be read
+    pub env: Grant<String>,
+    pub workers: bool,
+    pub timers: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Permissions::all()
+    }
+}
+
+impl Permissions {
+    pub fn all() -> Permissions {
+        Permissions {
+            read: Grant::All,
+            write: Grant::All,
+            net: Grant::All,
+            env: Grant::All,
+            workers: true,
+            timers: true,
+        }
+    }
+
+    pub fn none() -> Permissions {
+        Permissions {
+            read: Grant::none(),
+            write: Grant::none(),
+            net: Grant::none(),
+            env: Grant::none(),
+            workers: false,
+            timers: false,
+        }
+    }
+
+    pub fn allow_read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read.allow(path.into());
+        self
+    }
+
+    pub fn allow_write(mut self, path: impl Into<PathBuf>) -> Self {
+        self.write.allow(path.into());
+        self
+    }
+
+    pub fn allow_net(mut self, host: impl Into<String>) -> Self {
+        self.net.allow(host.into());
+        self
+    }
+
+    pub fn allow_env(mut self, name: impl Into<String>) -> Self {
+        self.env.allow(name.into());
+        self
+    }
+
+    pub fn allow_workers(mut self, on: bool) -> Self {
+        self.workers = on;
+        self
+    }
+
+    pub fn allow_timers(mut self, on: bool) -> Self {
+        self.timers = on;
+        self
+    }
+
+    pub fn check_read(&self, path: &Path) -> Result<(), PermissionDenied> {
+        let path = absolute(path);
+        if self.read.any(|allowed| path.starts_with(absolute(allowed))) {
+            return Ok(());
+        }
+        Err(PermissionDenied::new("read", path.display()))
+    }
+
+    pub fn check_write(&self, path: &Path) -> Result<(), PermissionDenied> {
+        let path = absolute(path);
+        if self
+            .write
+            .any(|allowed| path.starts_with(absolute(allowed)))
+        {
+            return Ok(());
+        }
+        Err(PermissionDenied::new("write", path.display()))
+    }
+
+    pub fn check_net(&self, host: &str) -> Result<(), PermissionDenied> {
+        if self.net.any(|allowed| host_matches(allowed, host)) {
+            return Ok(());
+        }
+        Err(PermissionDenied::new("net", host))
+    }
+
+    pub fn check_env(&self, name: &str) -> Result<(), PermissionDenied> {
+        if self.env.any(|allowed| allowed == name) {
+            return Ok(());
+        }
+        Err(PermissionDenied::new("env", name))
+    }
+
+    pub fn check_workers(&self) -> Result<(), PermissionDenied> {
+        if self.workers {
+            return Ok(());
+        }
+        Err(PermissionDenied::new("worker", "Worker"))
+    }
+
+    pub fn check_timers(&self) -> Result<(), PermissionDenied> {
+        if self.timers {
+            return Ok(());
+        }
+        Err(PermissionDenied::new("timers", "setTimeout"))
+    }
+}
+
+/// A denied permission check.
+/// Thrown to javascript as a `DOMException` named `PermissionDenied`
+#[derive(Debug, Clone)]
+pub struct PermissionDenied {
+    pub permission: &'static str,
+    pub target: String,
+}
+
+impl PermissionDenied {
+    pub fn new(permission: &'static str, target: impl fmt::Display) -> PermissionDenied {
+        PermissionDenied {
+            permission,
+            target: target.to_string(),
+        }
+    }
+
+    pub fn throw(&self, ctx: &Ctx<'_>) -> rquickjs::Error {
+        match self.to_exception(ctx) {
+            Ok(exception) => ctx.throw(exception.into_value()),
+            Err(err) => err,
+        }
+    }
+
+    fn to_exception<'js>(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Class<'js, DOMException<'js>>> {
+        let message = rquickjs::String::from_str(ctx.clone(), &self.to_string())?;
+        let name = rquickjs::String::from_str(ctx.clone(), "PermissionDenied")?;
+
+        Class::instance(
+            ctx.clone(),
+            DOMException::new(ctx.clone(), Opt(Some(message)), Opt(Some(name)))?,
+        )
+    }
+}
+
+impl fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Requires {} access to '{}'",
+            self.permission, self.target
+        )
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
+
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .to_ascii_lowercase()
+            .strip_suffix(&*domain.to_ascii_lowercase())
+            .is_some_and(|m| m.ends_with('.')),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Symlinks followed before giving up on a path, like the `ELOOP` limit of Linux
+const MAX_SYMLINKS: usize = 40;
+
+/// Resolve `path` against the current directory, following the symlinks in it, so they can't be
+/// used to escape an allowed directory. This includes dangling symlinks, which a write would create
+/// the target of. Components below the longest existing ancestor are appended as they are
+fn absolute(path: &Path) -> PathBuf {
+    resolve(path, &mut 0)
+}
+
+fn resolve(path: &Path, followed: &mut usize) -> PathBuf {
+    let mut out = match path.is_absolute() {
+        true => PathBuf::new(),
+        false => std::env::current_dir().unwrap_or_default(),
+    };
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            // Every symlink in `out` is resolved, so its parent is the real parent
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::Normal(name) => {
+                let next = out.join(name);
+                out = match std::fs::read_link(&next) {
+                    Ok(target) if *followed < MAX_SYMLINKS => {
+                        *followed += 1;
+                        resolve(&out.join(target), followed)
+                    }
+                    _ => next,
+                };
+            }
+            component => out.push(component),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_paths() {
+        let permissions = Permissions::none()
+            .allow_read("/klaver-sandbox/data")
+            .allow_write("/klaver-sandbox/data/out");
+
+        assert!(
+            permissions
+                .check_read(Path::new("/klaver-sandbox/data/file.txt"))
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_read(Path::new("/klaver-sandbox/data/../secret.txt"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_read(Path::new("/klaver-sandbox/database"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_write(Path::new("/klaver-sandbox/data/out/file.txt"))
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_write(Path::new("/klaver-sandbox/data/file.txt"))
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_paths_through_symlinks() {
+        let root = std::env::temp_dir().join(format!("klaver-permissions-{}", std::process::id()));
+        let data = root.join("data");
+        let outside = root.join("outside");
+
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::create_dir_all(outside.join("dir")).unwrap();
+        std::os::unix::fs::symlink(&outside, data.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("new.txt"), data.join("dangling")).unwrap();
+        std::os::unix::fs::symlink("../data/link", data.join("relative")).unwrap();
+
+        let permissions = Permissions::none().allow_write(data.clone());
+
+        let checks = [
+            "new.txt",
+            "link/new.txt",
+            "link/dir/new.txt",
+            "link/missing/new.txt",
+            "dangling",
+            "relative/new.txt",
+            "missing/../link/new.txt",
+        ]
+        .map(|path| permissions.check_write(&data.join(path)).is_ok());
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(checks, [true, false, false, false, false, false, false]);
+    }
+
+    #[test]
+    fn check_hosts() {
+        let permissions = Permissions::none()
+            .allow_net("example.com")
+            .allow_net("*.klaver.dev");
+
+        assert!(permissions.check_net("example.com").is_ok());
+        assert!(permissions.check_net("EXAMPLE.com").is_ok());
+        assert!(permissions.check_net("api.klaver.dev").is_ok());
+        assert!(permissions.check_net("klaver.dev").is_err());
+        assert!(permissions.check_net("evilklaver.dev").is_err());
+        assert!(permissions.check_net("sub.example.com").is_err());
+    }
+
+    #[test]
+    fn grants() {
+        let all = Permissions::all().allow_env("HOME");
+        assert_eq!(all.env, Grant::All);
+        assert!(all.check_env("PATH").is_ok());
+
+        let none = Permissions::none().allow_env("HOME");
+        assert!(none.check_env("HOME").is_ok());
+        assert!(none.check_env("PATH").is_err());
+        assert!(none.check_workers().is_err());
+        assert!(none.check_timers().is_err());
+
+        let err = none.check_env("PATH").unwrap_err();
+        assert_eq!(err.to_string(), "Requires env access to 'PATH'");
+    }
+}
@@ -0,0 +1,18 @@
+use std::{io, net::SocketAddr};
+
+use futures::future::LocalBoxFuture;
+
+/// A bidirectional byte stream hyper can drive a http connection over.
+pub trait Connection: hyper::rt::Read + hyper::rt::Write + Unpin {}
+
+impl<T> Connection for T where T: hyper::rt::Read + hyper::rt::Write + Unpin {}
+
+pub trait Listener {
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    fn accept(&self) -> LocalBoxFuture<'_, io::Result<(Box<dyn Connection>, SocketAddr)>>;
+}
+
+pub trait ServerBackend {
+    fn bind(&self, addr: SocketAddr) -> io::Result<Box<dyn Listener>>;
+}
@@ -0,0 +1,10 @@
+mod backend;
+mod module;
+mod server;
+mod service;
+
+pub use self::{
+    backend::{Connection, Listener, ServerBackend},
+    module::ServeModule,
+    server::{ServeOptions, Server, serve},
+};
@@ -0,0 +1,53 @@
+use std::borrow::Cow;
+
+use klaver_core::{Exportable, Registry};
+use rquickjs::{Ctx, Object, prelude::Func};
+
+use super::server::serve;
+
+pub struct ServeModule;
+
+impl<'js> Exportable<'js> for ServeModule {
+    fn export<T>(
+        ctx: &Ctx<'js>,
+        _registry: &klaver_core::Registry,
+        target: &T,
+    ) -> rquickjs::Result<()>
+    where
+        T: klaver_core::ExportTarget<'js>,
+    {
+        target.set(ctx, "serve", Func::from(serve))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "module")]
+impl klaver_modules::Global for ServeModule {
+    async fn define<'a, 'js: 'a>(&'a self, ctx: Ctx<'js>) -> rquickjs::Result<()> {
+        let globals = ctx.globals();
+
+        let klaver = match globals.get::<_, Option<Object>>("Klaver")? {
+            Some(klaver) => klaver,
+            None => {
+                let klaver = Object::new(ctx.clone())?;
+                globals.set("Klaver", klaver.clone())?;
+                klaver
+            }
+        };
+
+        Self::export(&ctx, &Registry::instance(&ctx)?, &klaver)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "module")]
+impl klaver_modules::GlobalInfo for ServeModule {
+    fn register(builder: &mut klaver_modules::GlobalBuilder<'_, Self>) {
+        builder.register(Self);
+    }
+
+    fn typings() -> Option<Cow<'static, str>> {
+        Some(Cow::Borrowed(include_str!("../../types/serve.d.ts")))
+    }
+}
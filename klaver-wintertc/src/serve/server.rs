@@ -0,0 +1,220 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use futures::FutureExt;
+use klaver_core::{StringExt, throw, throw_if};
+use klaver_runtime::{AsyncState, Resource, ResourceId, TaskHandle};
+use rquickjs::{
+    Class, Ctx, Error, FromJs, Function, JsLifetime, String, Value, class::Trace, prelude::Opt,
+};
+
+use crate::{
+    abort_controller::AbortSignal,
+    events::{DynEvent, Emitter},
+    settings::WinterTcInstance,
+};
+
+use super::{
+    backend::Listener,
+    service::{ConnectionResource, Service},
+};
+
+const DEFAULT_HOSTNAME: &str = "0.0.0.0";
+const DEFAULT_PORT: u16 = 3000;
+
+#[derive(Default)]
+pub struct ServeOptions<'js> {
+    pub port: Option<u16>,
+    pub hostname: Option<std::string::String>,
+    pub signal: Option<Class<'js, AbortSignal<'js>>>,
+    pub handler: Option<Function<'js>>,
+    pub on_error: Option<Function<'js>>,
+}
+
+impl<'js> FromJs<'js> for ServeOptions<'js> {
+    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> rquickjs::Result<Self> {
+        if value.is_null() || value.is_undefined() {
+            return Ok(ServeOptions::default());
+        }
+
+        let Ok(obj) = value.try_into_object() else {
+            return Err(Error::new_from_js("value", "object"));
+        };
+
+        Ok(ServeOptions {
+            port: obj.get("port")?,
+            hostname: obj.get("hostname")?,
+            signal: obj.get("signal")?,
+            handler: obj.get("handler")?,
+            on_error: obj.get("onError")?,
+        })
+    }
+}
+
+#[rquickjs::class]
+pub struct Server {
+    addr: SocketAddr,
+    handle: Option<TaskHandle>,
+}
+
+impl<'js> Trace<'js> for Server {
+    fn trace<'a>(&self, _tracer: rquickjs::class::Tracer<'a, 'js>) {}
+}
+
+unsafe impl<'js> JsLifetime<'js> for Server {
+    type Changed<'to> = Server;
+}
+
+impl Server {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+#[rquickjs::methods]
+impl Server {
+    #[qjs(get)]
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    #[qjs(get)]
+    pub fn hostname(&self) -> std::string::String {
+        self.addr.ip().to_string()
+    }
+
+    #[qjs(get)]
+    pub fn url(&self) -> std::string::String {
+        format!("http://{}/", self.addr)
+    }
+
+    pub fn shutdown(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.kill();
+        }
+    }
+}
+
+pub fn serve<'js>(
+    ctx: Ctx<'js>,
+    options: Value<'js>,
+    handler: Opt<Function<'js>>,
+) -> rquickjs::Result<Class<'js, Server>> {
+    let (options, handler) = if let Some(handler) = options.as_function() {
+        (ServeOptions::default(), handler.clone())
+    } else {
+        let options = ServeOptions::from_js(&ctx, options)?;
+        let Some(handler) = handler.0.or_else(|| options.handler.clone()) else {
+            throw!(@type ctx, "Expected a handler function")
+        };
+        (options, handler)
+    };
+
+    let hostname = options.hostname.as_deref().unwrap_or(DEFAULT_HOSTNAME);
+    let port = options.port.unwrap_or(DEFAULT_PORT);
+
+    let Some(addr) = throw_if!(ctx, (hostname, port).to_socket_addrs()).next() else {
+        throw!(ctx, format!("Could not resolve hostname: {hostname}"))
+    };
+
+    let listener = {
+        let instance = WinterTcInstance::from_ctx(&ctx)?;
+        let instance = instance.borrow();
+        if let Err(err) = instance.settings().permissions().check_net(hostname) {
+            return Err(err.throw(&ctx));
+        }
+        let Some(backend) = instance.settings().server() else {
+            throw!(ctx, "Server backend not defined")
+        };
+        throw_if!(ctx, backend.bind(addr))
+    };
+
+    let addr = throw_if!(ctx, listener.local_addr());
+
+    let abort = match &options.signal {
+        Some(signal) if signal.borrow().aborted => {
+            return Class::instance(ctx, Server { addr, handle: None });
+        }
+        Some(signal) => {
+            let (sx, rx) = flume::bounded(1);
+            signal.borrow_mut().add_native_listener(
+                String::from_str(ctx.clone(), "abort")?.str_ref()?.into(),
+                sx,
+            );
+            Some(rx)
+        }
+        None => None,
+    };
+
+    let handle = AsyncState::push(
+        &ctx,
+        ServerResource {
+            listener,
+            service: Service::new(addr, handler, options.on_error),
+            abort,
+        },
+    )?;
+
+    Class::instance(
+        ctx,
+        Server {
+            addr,
+            handle: Some(handle),
+        },
+    )
+}
+
+struct ServerResourceId;
+
+impl ResourceId for ServerResourceId {
+    fn name() -> &'static str {
+        "HttpServer"
+    }
+}
+
+struct ServerResource<'js> {
+    listener: Box<dyn Listener>,
+    service: Service<'js>,
+    abort: Option<flume::Receiver<DynEvent<'js>>>,
+}
+
+impl<'js> Resource<'js> for ServerResource<'js> {
+    type Id = ServerResourceId;
+
+    const INTERNAL: bool = false;
+    const SCOPED: bool = false;
+
+    async fn run(self, ctx: klaver_runtime::Context<'js>) -> rquickjs::Result<()> {
+        let abort = async {
+            match &self.abort {
+                Some(abort) => {
+                    abort.recv_async().await.ok();
+                }
+                None => futures::future::pending().await,
+            }
+        }
+        .fuse();
+
+        futures::pin_mut!(abort);
+
+        loop {
+            futures::select! {
+                ret = self.listener.accept().fuse() => {
+                    // Failing to accept a single connection should not bring the server down
+                    let Ok((io, _)) = ret else {
+                        continue;
+                    };
+
+                    AsyncState::push(
+                        ctx.ctx(),
+                        ConnectionResource::new(io, self.service.clone()),
+                    )?;
+                }
+                _ = abort => {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
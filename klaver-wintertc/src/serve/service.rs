@@ -0,0 +1,152 @@
+use std::net::SocketAddr;
+
+use http::{StatusCode, Uri, header::HOST};
+use hyper::{body::Incoming, server::conn::http1, service::service_fn};
+use klaver_core::{RuntimeError, throw, throw_if};
+use klaver_runtime::{Resource, ResourceId};
+use rquickjs::{Class, Exception, Function, Value};
+
+use crate::fetch::{Body, Request, Response, StaticBody};
+
+use super::backend::Connection;
+
+#[derive(Clone)]
+pub(super) struct Service<'js> {
+    addr: SocketAddr,
+    handler: Function<'js>,
+    on_error: Option<Function<'js>>,
+}
+
+impl<'js> Service<'js> {
+    pub fn new(addr: SocketAddr, handler: Function<'js>, on_error: Option<Function<'js>>) -> Self {
+        Service {
+            addr,
+            handler,
+            on_error,
+        }
+    }
+
+    async fn call(
+        &self,
+        ctx: &klaver_runtime::Context<'js>,
+        req: http::Request<Incoming>,
+    ) -> Result<http::Response<StaticBody>, RuntimeError> {
+        let err = match self.handle(ctx, req).await {
+            Ok(resp) => return Ok(resp),
+            Err(err) => err,
+        };
+
+        if let Some(on_error) = &self.on_error {
+            let error = match err {
+                rquickjs::Error::Exception => ctx.catch(),
+                err => Exception::from_message(ctx.ctx().clone(), &err.to_string())?.into_value(),
+            };
+
+            if let Ok(resp) = self.respond(ctx, on_error.clone(), error).await {
+                return Ok(resp);
+            }
+        }
+
+        Ok(internal_server_error())
+    }
+
+    async fn handle(
+        &self,
+        ctx: &klaver_runtime::Context<'js>,
+        req: http::Request<Incoming>,
+    ) -> rquickjs::Result<http::Response<StaticBody>> {
+        let (mut parts, body) = req.into_parts();
+
+        // Incoming requests only carry the path, but the fetch api expects an absolute url
+        let host = parts
+            .headers
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .map(|host| host.to_string())
+            .unwrap_or_else(|| self.addr.to_string());
+
+        let path = parts
+            .uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+
+        parts.uri = throw_if!(ctx, format!("http://{host}{path}").parse::<Uri>());
+
+        let req = http::Request::from_parts(parts, Body::from_streaming(body));
+        let req = Class::instance(ctx.ctx().clone(), Request::from_native(ctx, req)?)?;
+
+        self.respond(ctx, self.handler.clone(), req.into_value())
+            .await
+    }
+
+    async fn respond(
+        &self,
+        ctx: &klaver_runtime::Context<'js>,
+        func: Function<'js>,
+        arg: Value<'js>,
+    ) -> rquickjs::Result<http::Response<StaticBody>> {
+        let mut ret = ctx.invoke_callback::<_, Value>(func, (arg,))?;
+        if let Some(promise) = ret.clone().into_promise() {
+            ret = promise.into_future().await?;
+        }
+
+        let Ok(resp) = Class::<Response>::from_value(&ret) else {
+            throw!(@type ctx, "Expected handler to return a Response")
+        };
+
+        resp.borrow().to_owned_native(ctx)
+    }
+}
+
+fn internal_server_error() -> http::Response<StaticBody> {
+    let mut resp = http::Response::new(StaticBody::Bytes {
+        bytes: Some(bytes::Bytes::from_static(b"Internal Server Error")),
+    });
+    *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    resp
+}
+
+pub(super) struct ConnectionResourceId;
+
+impl ResourceId for ConnectionResourceId {
+    fn name() -> &'static str {
+        "HttpConnection"
+    }
+}
+
+pub(super) struct ConnectionResource<'js> {
+    io: Box<dyn Connection>,
+    service: Service<'js>,
+}
+
+impl<'js> ConnectionResource<'js> {
+    pub fn new(io: Box<dyn Connection>, service: Service<'js>) -> Self {
+        ConnectionResource { io, service }
+    }
+}
+
+impl<'js> Resource<'js> for ConnectionResource<'js> {
+    type Id = ConnectionResourceId;
+
+    const INTERNAL: bool = false;
+    const SCOPED: bool = true;
+
+    async fn run(self, ctx: klaver_runtime::Context<'js>) -> rquickjs::Result<()> {
+        let service = self.service;
+
+        let conn = http1::Builder::new().serve_connection(
+            self.io,
+            service_fn(|req| {
+                let service = service.clone();
+                let ctx = ctx.clone();
+                async move { service.call(&ctx, req).await }
+            }),
+        );
+
+        // Errors on the connection (client hangups, malformed requests) are not script errors
+        conn.await.ok();
+
+        Ok(())
+    }
+}
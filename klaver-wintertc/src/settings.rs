@@ -3,13 +3,15 @@ use std::sync::Arc;
 use klaver_core::{Core, throw_if};
 use rquickjs::{Class, Ctx, JsLifetime, class::Trace};
 
-use crate::backend::Backend;
 #[cfg(feature = "fetch")]
-use crate::fetch::{Client, LocalClient, SharedClient};
+use crate::fetch::{Client, FetchSettings, LocalClient, SharedClient};
 #[cfg(feature = "fs")]
 use crate::fs::FileSystemSettings;
+#[cfg(feature = "serve")]
+use crate::serve::ServerBackend;
 #[cfg(feature = "timers")]
 use crate::timers::TimingBackend;
+use crate::{backend::Backend, permissions::Permissions};
 
 #[rquickjs::class]
 pub struct WinterTcInstance {
@@ -46,6 +48,12 @@ impl WinterTcInstance {
         ctx: &Ctx<'_>,
         backend: Arc<dyn Backend + Send + Sync>,
     ) -> rquickjs::Result<()> {
+        // The timeout of a request is raced against a timer of the backend
+        #[cfg(all(feature = "fetch", not(feature = "timers")))]
+        if self.settings.fetch().timeout().is_some() {
+            klaver_core::throw!(ctx, "A fetch timeout needs the timers feature")
+        }
+
         self.backend = backend;
         throw_if!(ctx, self.backend.init(ctx, &mut self.settings));
         Ok(())
@@ -54,6 +62,11 @@ impl WinterTcInstance {
     pub fn settings(&self) -> &Settings {
         &self.settings
     }
+
+    /// Settings must be changed before the backend is set, for the backend to pick them up
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
 }
 
 impl<'js> Trace<'js> for WinterTcInstance {
@@ -65,28 +78,42 @@ unsafe impl<'js> JsLifetime<'js> for WinterTcInstance {
 }
 
 pub struct Settings {
+    permissions: Permissions,
     #[cfg(feature = "fetch")]
     http_client: Client,
     #[cfg(feature = "timers")]
     timers: TimingBackend,
     #[cfg(feature = "fs")]
     file_system: FileSystemSettings,
+    #[cfg(feature = "serve")]
+    server: Option<Box<dyn ServerBackend>>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
+            permissions: Permissions::default(),
             #[cfg(feature = "fetch")]
             http_client: Client::new(),
             #[cfg(feature = "timers")]
             timers: TimingBackend::null(),
             #[cfg(feature = "fs")]
             file_system: FileSystemSettings::default(),
+            #[cfg(feature = "serve")]
+            server: None,
         }
     }
 }
 
 impl Settings {
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
     #[cfg(feature = "fetch")]
     pub fn set_http_client<T: SharedClient + 'static>(&mut self, client: T) {
         self.http_client.set_shared_client(client);
@@ -102,6 +129,16 @@ impl Settings {
         &self.http_client
     }
 
+    #[cfg(feature = "fetch")]
+    pub fn set_fetch(&mut self, settings: FetchSettings) {
+        self.http_client.set_settings(settings);
+    }
+
+    #[cfg(feature = "fetch")]
+    pub fn fetch(&self) -> FetchSettings {
+        self.http_client.settings()
+    }
+
     #[cfg(feature = "timers")]
     pub fn set_timers<B: crate::timers::TimerBackend + 'static>(&mut self, timers: B) {
         self.timers = TimingBackend::new(timers);
@@ -121,4 +158,14 @@ impl Settings {
     pub fn file_system(&self) -> &FileSystemSettings {
         &self.file_system
     }
+
+    #[cfg(feature = "serve")]
+    pub fn set_server<B: ServerBackend + 'static>(&mut self, server: B) {
+        self.server = Some(Box::new(server));
+    }
+
+    #[cfg(feature = "serve")]
+    pub fn server(&self) -> Option<&dyn ServerBackend> {
+        self.server.as_deref()
+    }
 }
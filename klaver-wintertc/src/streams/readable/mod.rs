@@ -6,6 +6,7 @@ mod resource;
 mod source;
 mod state;
 mod stream;
+mod tee;
 
 use klaver_core::ExportTarget;
 
@@ -7,6 +7,7 @@ use crate::streams::{
         resource::ReadableStreamResource,
         source::{JsUnderlyingSource, UnderlyingSource},
         state::ReadableStreamData,
+        tee::TeeSource,
     },
 };
 use futures::{TryStream, stream::LocalBoxStream};
@@ -109,6 +110,24 @@ impl<'js> ReadableStream<'js> {
         Ok(output)
     }
 
+    /// Split the stream into two branches, both receiving every chunk.
+    /// This locks the stream.
+    pub fn tee_native(
+        &self,
+        ctx: &Ctx<'js>,
+    ) -> rquickjs::Result<(
+        Class<'js, ReadableStream<'js>>,
+        Class<'js, ReadableStream<'js>>,
+    )> {
+        let reader = self.get_reader(ctx.clone())?;
+        let (first, second) = TeeSource::new(reader);
+
+        let first = Class::instance(ctx.clone(), Self::from_native(ctx, first, None)?)?;
+        let second = Class::instance(ctx.clone(), Self::from_native(ctx, second, None)?)?;
+
+        Ok((first, second))
+    }
+
     pub fn to_stream(
         &self,
         ctx: Ctx<'js>,
@@ -216,6 +235,11 @@ impl<'js> ReadableStream<'js> {
         Ok(self.state.borrow().is_locked())
     }
 
+    pub fn tee(&self, ctx: Ctx<'js>) -> rquickjs::Result<Vec<Class<'js, ReadableStream<'js>>>> {
+        let (first, second) = self.tee_native(&ctx)?;
+        Ok(vec![first, second])
+    }
+
     #[qjs(rename = "pipeTo")]
     pub async fn pipe_to(
         &self,
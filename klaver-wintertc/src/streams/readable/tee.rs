@@ -0,0 +1,104 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use async_trait::async_trait;
+use rquickjs::{Class, Ctx, Value, class::Trace};
+
+use super::{
+    NativeSource, controller::ReadableStreamDefaultController, reader::ReadableStreamDefaultReader,
+};
+
+/// State shared between the two branches of a teed stream.
+/// Every chunk read from the source is queued for both branches, so each branch
+/// sees the chunks in the order they were read, regardless of which branch pulled.
+struct TeeState<'js> {
+    reader: ReadableStreamDefaultReader<'js>,
+    queues: RefCell<[VecDeque<Value<'js>>; 2]>,
+    done: Cell<bool>,
+}
+
+impl<'js> Trace<'js> for TeeState<'js> {
+    fn trace<'a>(&self, tracer: rquickjs::class::Tracer<'a, 'js>) {
+        self.reader.trace(tracer);
+        for queue in self.queues.borrow().iter() {
+            for value in queue {
+                value.trace(tracer);
+            }
+        }
+    }
+}
+
+pub struct TeeSource<'js> {
+    state: Rc<TeeState<'js>>,
+    branch: usize,
+}
+
+impl<'js> TeeSource<'js> {
+    pub fn new(reader: ReadableStreamDefaultReader<'js>) -> (TeeSource<'js>, TeeSource<'js>) {
+        let state = Rc::new(TeeState {
+            reader,
+            queues: RefCell::new([VecDeque::new(), VecDeque::new()]),
+            done: Cell::new(false),
+        });
+
+        (
+            TeeSource {
+                state: state.clone(),
+                branch: 0,
+            },
+            TeeSource { state, branch: 1 },
+        )
+    }
+}
+
+impl<'js> Trace<'js> for TeeSource<'js> {
+    fn trace<'a>(&self, tracer: rquickjs::class::Tracer<'a, 'js>) {
+        // Only one of the branches traces the shared state
+        if self.branch == 0 {
+            self.state.trace(tracer);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<'js> NativeSource<'js> for TeeSource<'js> {
+    async fn start(
+        &mut self,
+        _ctx: Ctx<'js>,
+        _ctrl: Class<'js, ReadableStreamDefaultController<'js>>,
+    ) -> rquickjs::Result<()> {
+        Ok(())
+    }
+
+    async fn pull(
+        &mut self,
+        ctx: Ctx<'js>,
+        ctrl: Class<'js, ReadableStreamDefaultController<'js>>,
+    ) -> rquickjs::Result<()> {
+        let queued = self.state.queues.borrow_mut()[self.branch].pop_front();
+
+        if let Some(next) = queued {
+            return ctrl.borrow_mut().enqueue(ctx, next);
+        }
+
+        if !self.state.done.get() {
+            if let Some(next) = self.state.reader.read_native(&ctx).await? {
+                let mut queues = self.state.queues.borrow_mut();
+                queues[0].push_back(next.clone());
+                queues[1].push_back(next);
+            } else {
+                self.state.done.set(true);
+            }
+        }
+
+        let queued = self.state.queues.borrow_mut()[self.branch].pop_front();
+
+        match queued {
+            Some(next) => ctrl.borrow_mut().enqueue(ctx, next),
+            None => ctrl.borrow().close(ctx),
+        }
+    }
+}
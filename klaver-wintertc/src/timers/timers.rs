@@ -49,6 +49,11 @@ impl Timers {
         //     throw!(@type &ctx, "Timing backend not defined")
         // };
 
+        let winter = WinterTcInstance::from_ctx(&ctx)?;
+        if let Err(err) = winter.borrow().settings().permissions().check_timers() {
+            return Err(err.throw(&ctx));
+        }
+
         let timeout = Duration::from_millis(timeout.unwrap_or(0));
 
         let task_handle = AsyncState::push(
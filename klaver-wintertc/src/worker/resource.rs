@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use crate::{
-    Backend, WinterTcInstance,
+    Backend, Permissions, WinterTcInstance,
     channel::{Channel, MessagePort},
 };
 use klaver_core::{Registry, throw_if};
@@ -24,6 +24,7 @@ pub struct WorkerResource {
     channel: Channel,
     registry: Registry,
     backend: Arc<dyn Backend + Send + Sync>,
+    permissions: Permissions,
 }
 
 impl<'a> WorkerResource {
@@ -33,6 +34,7 @@ impl<'a> WorkerResource {
         channel: Channel,
         registry: Registry,
         backend: Arc<dyn Backend + Send + Sync>,
+        permissions: Permissions,
     ) -> Self {
         Self {
             path,
@@ -40,6 +42,7 @@ impl<'a> WorkerResource {
             channel,
             registry,
             backend,
+            permissions,
         }
     }
 }
@@ -56,12 +59,16 @@ impl<'js> Resource<'js> for WorkerResource {
             let registry = self.registry;
             let channel = self.channel;
             let backend = self.backend;
+            let permissions = self.permissions;
             let ret = worker
                 .async_with(async move |ctx| {
                     registry.attach(&ctx)?;
-                    WinterTcInstance::from_ctx(&ctx)?
+                    let winter = WinterTcInstance::from_ctx(&ctx)?;
+                    winter
                         .borrow_mut()
-                        .set_backend(&ctx, backend)?;
+                        .settings_mut()
+                        .set_permissions(permissions);
+                    winter.borrow_mut().set_backend(&ctx, backend)?;
                     Ok(())
                 })
                 .await;
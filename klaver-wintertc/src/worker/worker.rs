@@ -39,6 +39,11 @@ impl<'js> WebWorker<'js> {
         ctx: Ctx<'js>,
         path: std::string::String,
     ) -> rquickjs::Result<Class<'js, WebWorker<'js>>> {
+        let winter = WinterTcInstance::from_ctx(&ctx)?;
+        if let Err(err) = winter.borrow().settings().permissions().check_workers() {
+            return Err(err.throw(&ctx));
+        }
+
         let registry = Registry::instance(&ctx)?;
 
         let channel = MessageChannel::new(ctx.clone())?;
@@ -52,14 +57,14 @@ impl<'js> WebWorker<'js> {
             .clone()
             .upgrade(&ctx)?;
 
-        let winter = WinterTcInstance::from_ctx(&ctx)?;
-
+        // Workers run with the same permissions as the script spawning them
         let resource = WorkerResource::new(
             path,
             env,
             channel,
             registry,
             winter.borrow().backend().clone(),
+            winter.borrow().settings().permissions().clone(),
         );
 
         let handle = AsyncState::push(&ctx, resource)?;
@@ -6,8 +6,170 @@ declare interface Crypto {
   getRandomValues(buffer: Buffer): void;
 }
 
+type HashAlgorithmIdentifier =
+  | "SHA-1"
+  | "SHA-256"
+  | "SHA-384"
+  | "SHA-512"
+  | { name: "SHA-1" | "SHA-256" | "SHA-384" | "SHA-512" };
+
+type NamedCurve = "P-256" | "P-384";
+
+type KeyFormat = "raw" | "jwk" | "pkcs8" | "spki";
+
+type KeyType = "secret" | "public" | "private";
+
+type KeyUsage =
+  | "encrypt"
+  | "decrypt"
+  | "sign"
+  | "verify"
+  | "deriveKey"
+  | "deriveBits";
+
+interface JsonWebKey {
+  kty?: string;
+  crv?: string;
+  alg?: string;
+  k?: string;
+  x?: string;
+  y?: string;
+  d?: string;
+  key_ops?: KeyUsage[];
+  ext?: boolean;
+}
+
+interface HmacImportParams {
+  name: "HMAC";
+  hash: HashAlgorithmIdentifier;
+  length?: number;
+}
+
+interface EcKeyImportParams {
+  name: "ECDSA";
+  namedCurve: NamedCurve;
+}
+
+interface EcdsaParams {
+  name: "ECDSA";
+  hash: HashAlgorithmIdentifier;
+}
+
+interface AesKeyAlgorithmParams {
+  name: "AES-GCM" | "AES-CBC";
+  length: number;
+}
+
+interface AesGcmParams {
+  name: "AES-GCM";
+  iv: Buffer;
+  additionalData?: Buffer;
+  tagLength?: 128;
+}
+
+interface AesCbcParams {
+  name: "AES-CBC";
+  iv: Buffer;
+}
+
+interface Pbkdf2Params {
+  name: "PBKDF2";
+  hash: HashAlgorithmIdentifier;
+  salt: Buffer;
+  iterations: number;
+}
+
+interface HkdfParams {
+  name: "HKDF";
+  hash: HashAlgorithmIdentifier;
+  salt: Buffer;
+  info: Buffer;
+}
+
+type ImportAlgorithm =
+  | HmacImportParams
+  | EcKeyImportParams
+  | "AES-GCM"
+  | "AES-CBC"
+  | "Ed25519"
+  | "PBKDF2"
+  | "HKDF"
+  | { name: "AES-GCM" | "AES-CBC" | "Ed25519" | "PBKDF2" | "HKDF" };
+
+type SignAlgorithm = "HMAC" | "Ed25519" | EcdsaParams | { name: "HMAC" | "Ed25519" };
+
+type DeriveAlgorithm = Pbkdf2Params | HkdfParams;
+
+interface KeyAlgorithm {
+  name: string;
+  hash?: { name: string };
+  length?: number;
+  namedCurve?: NamedCurve;
+}
+
+declare class CryptoKey {
+  private constructor();
+  readonly type: KeyType;
+  readonly extractable: boolean;
+  readonly algorithm: KeyAlgorithm;
+  readonly usages: KeyUsage[];
+}
+
 declare interface SubtleCrypto {
-  digest(algo: "SHA-1" | "SHA-256", input: Buffer): ArrayBuffer;
+  digest(algo: HashAlgorithmIdentifier, input: Buffer): Promise<ArrayBuffer>;
+  /**
+   * Non-standard. Hash a stream chunk by chunk, without buffering it.
+   * The stream is locked while it is being read
+   */
+  digestStream(
+    algo: HashAlgorithmIdentifier,
+    stream: ReadableStream
+  ): Promise<ArrayBuffer>;
+  importKey(
+    format: "jwk",
+    keyData: JsonWebKey,
+    algorithm: ImportAlgorithm,
+    extractable: boolean,
+    keyUsages: KeyUsage[]
+  ): Promise<CryptoKey>;
+  importKey(
+    format: "raw" | "pkcs8" | "spki",
+    keyData: Buffer,
+    algorithm: ImportAlgorithm,
+    extractable: boolean,
+    keyUsages: KeyUsage[]
+  ): Promise<CryptoKey>;
+  exportKey(format: "jwk", key: CryptoKey): Promise<JsonWebKey>;
+  exportKey(format: "raw" | "pkcs8" | "spki", key: CryptoKey): Promise<ArrayBuffer>;
+  sign(algorithm: SignAlgorithm, key: CryptoKey, data: Buffer): Promise<ArrayBuffer>;
+  verify(
+    algorithm: SignAlgorithm,
+    key: CryptoKey,
+    signature: Buffer,
+    data: Buffer
+  ): Promise<boolean>;
+  encrypt(
+    algorithm: AesGcmParams | AesCbcParams,
+    key: CryptoKey,
+    data: Buffer
+  ): Promise<ArrayBuffer>;
+  decrypt(
+    algorithm: AesGcmParams | AesCbcParams,
+    key: CryptoKey,
+    data: Buffer
+  ): Promise<ArrayBuffer>;
+  deriveBits(
+    algorithm: DeriveAlgorithm,
+    baseKey: CryptoKey,
+    length: number
+  ): Promise<ArrayBuffer>;
+  deriveKey(
+    algorithm: DeriveAlgorithm,
+    baseKey: CryptoKey,
+    derivedKeyType: AesKeyAlgorithmParams | HmacImportParams,
+    extractable: boolean,
+    keyUsages: KeyUsage[]
+  ): Promise<CryptoKey>;
 }
 
-declare const crypto: Crypto;
\ No newline at end of file
+declare const crypto: Crypto;
@@ -8,11 +8,19 @@ declare type Method =
     | "OPTION";
 
 declare class Headers {
+    constructor(init?: HeadersInit);
     append(key: string, value: string): void;
     set(key: string, value: string): void;
-    get(key: string): string;
+    get(key: string): string | null;
     getAll(key: string): string[];
+    getSetCookie(): string[];
     has(key: string): boolean;
+    delete(key: string): void;
+    forEach(fn: (value: string, key: string, headers: Headers) => void): void;
+    entries(): IterableIterator<[string, string]>;
+    keys(): IterableIterator<string>;
+    values(): IterableIterator<string>;
+    [Symbol.iterator](): IterableIterator<[string, string]>;
 }
 
 declare type Body =
@@ -23,6 +31,7 @@ declare type Body =
     | Int16Array
     | Int32Array
     | Uint32Array
+    | FormData
     | string;
 
 declare interface RequestInit {
@@ -41,6 +50,8 @@ declare class Request {
 
     text(): Promise<string>;
     json<T = unknown>(): Promise<T>;
+    formData(): Promise<FormData>;
+    clone(): Request;
     readonly body: ReadableStream;
 }
 
@@ -54,13 +65,22 @@ declare interface ResponseInit {
 declare class Response {
     readonly url: string;
     readonly status: number;
+    readonly ok: boolean;
+    readonly type: "default" | "error";
     readonly headers: Headers;
 
     constructor(body?: Body, options?: ResponseInit);
 
+    static json(data: unknown, options?: ResponseInit): Response;
+    static redirect(url: string | URL, status?: 301 | 302 | 303 | 307 | 308): Response;
+    static error(): Response;
+
+    clone(): Response;
+
     text(): Promise<string>;
     json<T = unknown>(): Promise<T>;
     arrayBuffer(): Promise<ArrayBuffer>;
+    formData(): Promise<FormData>;
     stream(): AsyncIterable<ArrayBuffer>;
 }
 
@@ -91,3 +111,20 @@ declare class URLSearchParams {
     delete(key: string): void;
     entries(): IterableIterator<[string, string]>;
 }
+
+declare type FormDataEntryValue = string | File;
+
+declare class FormData {
+    constructor();
+    append(name: string, value: string | Blob, filename?: string): void;
+    set(name: string, value: string | Blob, filename?: string): void;
+    get(name: string): FormDataEntryValue | undefined;
+    getAll(name: string): FormDataEntryValue[];
+    has(name: string): boolean;
+    delete(name: string): void;
+    forEach(fn: (value: FormDataEntryValue, name: string, form: FormData) => void): void;
+    entries(): IterableIterator<[string, FormDataEntryValue]>;
+    keys(): IterableIterator<string>;
+    values(): IterableIterator<FormDataEntryValue>;
+    [Symbol.iterator](): IterableIterator<[string, FormDataEntryValue]>;
+}
@@ -1,6 +1,12 @@
 
 
-declare interface File {
+/**
+ * An open file.
+ *
+ * Exported as `File` before, which shadowed the global `File` of fetch.
+ * Import it as `FileHandle` from `@klaver/fs`.
+ */
+declare interface FileHandle {
     read(len: number): Promise<ArrayBuffer>;
     arrayBuffer(): Promise<ArrayBuffer>;
     write(buffer: ArrayBuffer): Promise<void>;
@@ -20,7 +26,7 @@ declare interface FileSystemEntry {
     resolve(path: string): FileSystemEntry;
     metadata(): Promise<Metadata>;
     listDir(): Promise<IterableIterator<FileSystemEntry>>;
-    open(opts: OpenOptions): Promise<File>
+    open(opts: OpenOptions): Promise<FileHandle>
 }
 
 declare interface Metadata {
@@ -0,0 +1,21 @@
+declare namespace Klaver {
+    type ServeHandler = (request: Request) => Response | Promise<Response>;
+
+    interface ServeOptions {
+        port?: number;
+        hostname?: string;
+        signal?: AbortSignal;
+        handler?: ServeHandler;
+        onError?: (error: unknown) => Response | Promise<Response>;
+    }
+
+    interface Server {
+        readonly port: number;
+        readonly hostname: string;
+        readonly url: string;
+        shutdown(): void;
+    }
+
+    function serve(handler: ServeHandler): Server;
+    function serve(options: ServeOptions, handler?: ServeHandler): Server;
+}
@@ -0,0 +1,59 @@
+use http_body_util::BodyExt;
+use klaver_core::{throw, throw_if};
+use klaver_runtime::Runner;
+use klaver_wintertc::fetch::{Body, Request, Response};
+use rquickjs::{Class, Function, Module, Object, Value, function::This};
+
+use crate::Vm;
+
+impl Vm {
+    /// Dispatch a request to the `fetch` method of the default export of `module`.
+    /// The response body is read to completion before the event loop is left.
+    pub async fn handle_request(
+        &self,
+        module: &str,
+        req: http::Request<Body>,
+    ) -> klaver_vm::Result<http::Response<Body>> {
+        self.vm
+            .run(FetchRunner {
+                module: module.to_string(),
+                req,
+            })
+            .await
+    }
+}
+
+struct FetchRunner {
+    module: String,
+    req: http::Request<Body>,
+}
+
+impl<'js> Runner<'js> for FetchRunner {
+    type Output = http::Response<Body>;
+
+    async fn run(self, ctx: klaver_runtime::Context<'js>) -> rquickjs::Result<Self::Output> {
+        let module = Module::import(&ctx, self.module)?
+            .into_future::<Object>()
+            .await?;
+
+        let handler = module.get::<_, Object>("default")?;
+        let fetch = handler.get::<_, Function>("fetch")?;
+
+        let req = Class::instance(ctx.ctx().clone(), Request::from_native(&ctx, self.req)?)?;
+
+        let mut ret = fetch.call::<_, Value>((This(handler), req))?;
+        if let Some(promise) = ret.clone().into_promise() {
+            ret = promise.into_future().await?;
+        }
+
+        let Ok(resp) = Class::<Response>::from_value(&ret) else {
+            throw!(@type ctx, "Expected fetch to return a Response")
+        };
+
+        let (parts, body) = resp.borrow().to_owned_native(&ctx)?.into_parts();
+
+        let bytes = throw_if!(ctx, body.collect().await).to_bytes();
+
+        Ok(http::Response::from_parts(parts, Body::from(bytes)))
+    }
+}
@@ -1,21 +1,30 @@
-use std::path::PathBuf;
+#[cfg(feature = "fetch")]
+mod fetch;
+
+use std::{path::PathBuf, sync::Arc};
 
 #[cfg(feature = "swc")]
 use klaver_modules::loaders::{SwcCompilerOptions, SwcDecocators, SwcTransformer};
 use klaver_modules::{
-    GlobalInfo, ModuleInfo,
-    loaders::FileLoader,
-    resolvers::{FileResolver, ResolveOptions},
+    Global, GlobalInfo, ModuleInfo,
+    loaders::{
+        BytecodeLoader, BytesTransformer, CjsTransformer, FileLoader, JsonTransformer,
+        MemoryModules, TextTransformer, UrlLoader,
+    },
+    resolvers::{FileResolver, ImportMapResolver, ResolveOptions},
 };
 
 use klaver_vm::Options;
-use klaver_wintertc::Backend;
-use rquickjs::CatchResultExt;
+use klaver_wintertc::{Backend, Permissions, WinterTcInstance};
+use rquickjs::Ctx;
 
 pub struct Builder<T> {
     opts: Options,
     resolver_opts: Option<ResolveOptions>,
     search_paths: Vec<PathBuf>,
+    #[cfg(feature = "fetch")]
+    fetch: Option<klaver_wintertc::fetch::FetchSettings>,
+    permissions: Option<Permissions>,
     backend: T,
 }
 
@@ -25,6 +34,9 @@ impl<T: Backend + Send + Sync + 'static> Builder<T> {
             opts: Options::default(),
             resolver_opts: None,
             search_paths: Vec::new(),
+            #[cfg(feature = "fetch")]
+            fetch: None,
+            permissions: None,
             backend,
         }
     }
@@ -39,11 +51,75 @@ impl<T: Backend + Send + Sync + 'static> Builder<T> {
         self
     }
 
+    /// Rewrite specifiers with an import map, like one read from `importmap.json`
+    pub fn import_map(mut self, import_map: ImportMapResolver) -> Self {
+        self.opts = self.opts.import_map(import_map);
+        self
+    }
+
+    /// Fail calls running longer than `limit` with `RuntimeError::Timeout`
+    pub fn time_limit(mut self, limit: std::time::Duration) -> Self {
+        self.opts = self.opts.time_limit(limit);
+        self
+    }
+
+    /// Cache compiled modules in memory, so contexts created after a reload only
+    /// compile the modules which changed
+    pub fn cache(mut self, on: bool) -> Self {
+        self.opts = self.opts.cache(on);
+        self
+    }
+
+    /// Cache compiled modules in `dir`, so they are reused across runs
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.opts = self.opts.cache_dir(dir);
+        self
+    }
+
+    /// Load modules from a precompiled bytecode bundle, before looking at the file system
+    pub fn bundle(mut self, loader: BytecodeLoader) -> Self {
+        self.opts = self.opts.resolver(loader.clone()).loader(loader);
+        self
+    }
+
+    /// Create the vm from a snapshot, with its modules already evaluated
+    pub fn snapshot(mut self, snapshot: klaver_vm::Snapshot) -> Self {
+        self.opts = self.opts.snapshot(snapshot);
+        self
+    }
+
+    /// Make in-memory modules importable, before looking at the file system
+    pub fn memory_modules(mut self, modules: MemoryModules) -> Self {
+        self.opts = self.opts.resolver(modules.clone()).loader(modules);
+        self
+    }
+
+    /// Allow importing `http://` and `https://` modules
+    pub fn url_modules(mut self, loader: UrlLoader) -> Self {
+        self.opts = self.opts.resolver(loader.resolver()).loader(loader);
+        self
+    }
+
+    /// Restrict what scripts can access. Everything is allowed by default
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = Some(permissions);
+        self
+    }
+
+    #[cfg(feature = "fetch")]
+    pub fn fetch(mut self, settings: klaver_wintertc::fetch::FetchSettings) -> Self {
+        self.fetch = Some(settings);
+        self
+    }
+
     pub fn module<M: ModuleInfo>(self) -> Self {
         Self {
             opts: self.opts.module::<M>(),
             resolver_opts: self.resolver_opts,
             search_paths: self.search_paths,
+            #[cfg(feature = "fetch")]
+            fetch: self.fetch,
+            permissions: self.permissions,
             backend: self.backend,
         }
     }
@@ -53,6 +129,9 @@ impl<T: Backend + Send + Sync + 'static> Builder<T> {
             opts: self.opts.global::<G>(),
             resolver_opts: self.resolver_opts,
             search_paths: self.search_paths,
+            #[cfg(feature = "fetch")]
+            fetch: self.fetch,
+            permissions: self.permissions,
             backend: self.backend,
         }
     }
@@ -69,7 +148,13 @@ impl<T: Backend + Send + Sync + 'static> Builder<T> {
             opts = opts.resolver(file_resolver);
         }
 
-        let mut file_loader = FileLoader::default();
+        // Import attributes take precedence over file extensions, and CommonJS
+        // files are picked out before the remaining javascript
+        let mut file_loader = FileLoader::default()
+            .with_transformer(JsonTransformer)
+            .with_transformer(TextTransformer)
+            .with_transformer(BytesTransformer)
+            .with_transformer(CjsTransformer::new());
 
         #[cfg(feature = "swc")]
         {
@@ -86,18 +171,49 @@ impl<T: Backend + Send + Sync + 'static> Builder<T> {
 
         opts = opts.loader(file_loader);
 
-        let vm = opts.global::<klaver_wintertc::WinterTC>().build().await?;
-
-        vm.async_with(async |ctx| {
-            klaver_wintertc::set_backend(&ctx, self.backend).catch(&ctx)?;
-            Ok(())
-        })
-        .await?;
+        let vm = opts
+            .global::<klaver_wintertc::WinterTC>()
+            .define_global(Configure {
+                #[cfg(feature = "fetch")]
+                fetch: self.fetch,
+                permissions: self.permissions,
+                backend: Arc::new(self.backend),
+            })
+            .build()
+            .await?;
 
         Ok(Vm { vm })
     }
 }
 
+/// Applies the settings of the builder to every context of the vm,
+/// so contexts replacing the first one after a reload or reset are configured the same
+struct Configure {
+    #[cfg(feature = "fetch")]
+    fetch: Option<klaver_wintertc::fetch::FetchSettings>,
+    permissions: Option<Permissions>,
+    backend: Arc<dyn Backend + Send + Sync>,
+}
+
+impl Global for Configure {
+    async fn define<'a, 'js: 'a>(&'a self, ctx: Ctx<'js>) -> rquickjs::Result<()> {
+        let instance = WinterTcInstance::from_ctx(&ctx)?;
+        let mut instance = instance.borrow_mut();
+
+        #[cfg(feature = "fetch")]
+        if let Some(fetch) = &self.fetch {
+            instance.settings_mut().set_fetch(fetch.clone());
+        }
+
+        if let Some(permissions) = &self.permissions {
+            instance.settings_mut().set_permissions(permissions.clone());
+        }
+
+        // Settings are applied first, so the backend picks them up
+        instance.set_backend(&ctx, self.backend.clone())
+    }
+}
+
 pub struct Vm {
     vm: klaver_vm::Vm,
 }
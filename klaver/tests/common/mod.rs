@@ -0,0 +1,60 @@
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use klaver_runtime::Runner;
+use rquickjs::Module;
+use tempfile::TempDir;
+
+/// Evaluates a script as a module, and returns what it assigned to `globalThis.result`
+pub struct Script(pub &'static str);
+
+impl<'js> Runner<'js> for Script {
+    type Output = String;
+
+    async fn run(self, ctx: klaver_runtime::Context<'js>) -> rquickjs::Result<String> {
+        let promise = Module::evaluate(ctx.ctx().clone(), "test", self.0)?;
+        promise.into_future::<()>().await?;
+        ctx.globals().get("result")
+    }
+}
+
+/// A temporary directory with the files of a test, removed when it is dropped
+pub struct Fixture {
+    _dir: TempDir,
+    path: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        // Files are resolved to canonical paths, so compare against those
+        let path = dir.path().canonicalize().unwrap();
+        Fixture { _dir: dir, path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Write `content` to `name`, creating its parent directories.
+    /// Returns the absolute path of the file
+    pub fn write(&self, name: &str, content: impl AsRef<[u8]>) -> String {
+        let path = self.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(&path, content).unwrap();
+        path.display().to_string()
+    }
+
+    pub fn mkdir(&self, name: &str) -> PathBuf {
+        let path = self.join(name);
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+}
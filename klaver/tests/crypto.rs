@@ -0,0 +1,102 @@
+#![cfg(feature = "crypto")]
+
+mod common;
+
+use klaver::Builder;
+use klaver_wintertc::CompioBackend;
+
+use common::Script;
+
+#[compio::test]
+async fn subtle_crypto() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const encoder = new TextEncoder();
+            const hex = (buffer) =>
+                Array.from(new Uint8Array(buffer), (b) => b.toString(16).padStart(2, "0")).join("");
+
+            const hmac = await crypto.subtle.importKey(
+                "raw",
+                encoder.encode("Jefe"),
+                { name: "HMAC", hash: "SHA-256" },
+                false,
+                ["sign", "verify"],
+            );
+            const data = encoder.encode("what do ya want for nothing?");
+            const signature = await crypto.subtle.sign("HMAC", hmac, data);
+            const valid = await crypto.subtle.verify("HMAC", hmac, signature, data);
+
+            const password = await crypto.subtle.importKey(
+                "raw",
+                encoder.encode("password"),
+                "PBKDF2",
+                false,
+                ["deriveKey"],
+            );
+            const aes = await crypto.subtle.deriveKey(
+                { name: "PBKDF2", hash: "SHA-256", salt: encoder.encode("salt"), iterations: 1000 },
+                password,
+                { name: "AES-GCM", length: 256 },
+                true,
+                ["encrypt", "decrypt"],
+            );
+            const iv = new Uint8Array(12);
+            const encrypted = await crypto.subtle.encrypt({ name: "AES-GCM", iv }, aes, encoder.encode("secret"));
+            const decrypted = await crypto.subtle.decrypt({ name: "AES-GCM", iv }, aes, encrypted);
+            const jwk = await crypto.subtle.exportKey("jwk", aes);
+
+            globalThis.result = [
+                hex(signature),
+                valid,
+                new TextDecoder().decode(decrypted),
+                jwk.alg,
+                aes.algorithm.length,
+            ].join(" ");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ret,
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843 true secret A256GCM 256"
+    );
+}
+
+#[cfg(feature = "streams")]
+#[compio::test]
+async fn subtle_digest() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const encoder = new TextEncoder();
+            const hex = (buffer) =>
+                Array.from(new Uint8Array(buffer), (b) => b.toString(16).padStart(2, "0")).join("");
+
+            const stream = new ReadableStream({
+                start(controller) {
+                    controller.enqueue(encoder.encode("ab"));
+                    controller.enqueue("c");
+                    controller.close();
+                },
+            });
+
+            const [digest, streamed] = await Promise.all([
+                crypto.subtle.digest({ name: "SHA-256" }, encoder.encode("abc")),
+                crypto.subtle.digestStream("SHA-256", stream),
+            ]);
+
+            globalThis.result = `${hex(digest)} ${hex(streamed)}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    assert_eq!(ret, format!("{expected} {expected}"));
+}
@@ -0,0 +1,551 @@
+mod common;
+
+use klaver::Builder;
+use klaver_wintertc::CompioBackend;
+#[cfg(feature = "fetch")]
+use rquickjs::CatchResultExt;
+
+use common::Script;
+
+#[compio::test]
+async fn response_static_constructors() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const json = Response.json({ hello: "world" }, { status: 201 });
+            const copy = json.clone();
+
+            const redirect = Response.redirect("http://localhost/next", 307);
+            const error = Response.error();
+
+            globalThis.result = [
+                json.status,
+                json.headers.get("content-type"),
+                JSON.stringify(await json.json()),
+                await copy.text(),
+                redirect.status,
+                redirect.headers.get("location"),
+                error.status,
+                error.type,
+            ].join(" ");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ret,
+        r#"201 application/json {"hello":"world"} {"hello":"world"} 307 http://localhost/next 0 error"#
+    );
+}
+
+#[compio::test]
+async fn body_read_while_reading() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            let controller;
+            const stream = new ReadableStream({
+                start(c) {
+                    controller = c;
+                },
+            });
+
+            const resp = new Response(stream);
+            const text = resp.text();
+
+            // The read is waiting for the stream, while the body is inspected
+            await new Promise((resolve) => setTimeout(resolve, 0));
+            const read = resp.bodyRead;
+
+            controller.enqueue(new TextEncoder().encode("hello"));
+            controller.close();
+
+            globalThis.result = `${read} ${await text}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "true hello");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn mock_fetch_client() {
+    use klaver_wintertc::fetch::{Body, MockClient};
+
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let mock = MockClient::new();
+    mock.handle(|req| {
+        (req.uri().host() == Some("rust.test"))
+            .then(|| http::Response::new(Body::from("from rust")))
+    });
+
+    vm.async_with(async |ctx| {
+        mock.register(&ctx).catch(&ctx)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            setFetchHandler(async (req) => new Response(`js ${req.method} ${await req.text()}`));
+
+            const rust = await fetch("http://rust.test/");
+            const js = await fetch("http://js.test/", { method: "POST", body: "hello" });
+
+            globalThis.result = `${await rust.text()}, ${await js.text()}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "from rust, js POST hello");
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].uri(), "http://js.test/");
+    assert_eq!(requests[1].body().as_ref(), b"hello");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn fetch_settings() {
+    use klaver_wintertc::fetch::{Body, FetchSettings, MockClient};
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-embedder", "klaver".parse().unwrap());
+
+    let vm = Builder::new(CompioBackend)
+        .fetch(
+            FetchSettings::new()
+                .with_base_url("https://api.example.com/v1/".parse().unwrap())
+                .with_default_headers(headers)
+                .deny_host("*.internal"),
+        )
+        .build()
+        .await
+        .unwrap();
+
+    let mock = MockClient::new();
+    mock.handle(|req| {
+        let header = req.headers()["x-embedder"].to_str().unwrap();
+        Some(http::Response::new(Body::from(format!(
+            "{} {header}",
+            req.uri()
+        ))))
+    });
+
+    vm.async_with(async |ctx| {
+        mock.register(&ctx).catch(&ctx)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const resp = await fetch("users?id=1");
+            const denied = await fetch("http://db.internal/").then(() => "allowed", () => "denied");
+
+            globalThis.result = `${await resp.text()} ${denied}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "https://api.example.com/v1/users?id=1 klaver denied");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn compio_backend_options() {
+    use klaver_wintertc::fetch::{Body, FetchSettings, MockClient};
+    use std::time::Duration;
+
+    // The compio backend can't apply a connect timeout, so it refuses it
+    let ret = Builder::new(CompioBackend)
+        .fetch(FetchSettings::new().with_connect_timeout(Duration::from_secs(1)))
+        .build()
+        .await;
+    assert!(ret.is_err());
+
+    let vm = Builder::new(CompioBackend)
+        .fetch(FetchSettings::new().with_max_redirects(1))
+        .build()
+        .await
+        .unwrap();
+
+    let mock = MockClient::new();
+    mock.handle(|req| {
+        let location = match req.uri().path() {
+            "/done" => return Some(http::Response::new(Body::from("done"))),
+            "/once" => "/done",
+            _ => "/twice",
+        };
+
+        Some(
+            http::Response::builder()
+                .status(302)
+                .header("location", location)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    });
+
+    vm.async_with(async |ctx| {
+        mock.register(&ctx).catch(&ctx)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const once = await fetch("http://example.test/once");
+            const twice = await fetch("http://example.test/twice").then(() => "followed", () => "too many");
+
+            globalThis.result = `${await once.text()} ${twice}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "done too many");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn fetch_timeout() {
+    use klaver_wintertc::fetch::{FetchSettings, MockClient};
+    use std::time::Duration;
+
+    let vm = Builder::new(CompioBackend)
+        .fetch(FetchSettings::new().with_timeout(Duration::from_millis(50)))
+        .build()
+        .await
+        .unwrap();
+
+    vm.async_with(async |ctx| {
+        MockClient::new().register(&ctx).catch(&ctx)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            setFetchHandler(() => new Promise(() => {}));
+
+            globalThis.result = await fetch("http://example.test/").then(
+                () => "responded",
+                (err) => err.message,
+            );
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "Request timed out");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn mock_fetch_client_reentrant() {
+    use klaver_wintertc::fetch::{Body, MockClient};
+
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let mock = MockClient::new();
+    let inner = mock.clone();
+    mock.handle(move |req| {
+        // Handlers can add handlers and read the requests while being called
+        if req.uri().host() == Some("add.test") {
+            inner.handle(|req| {
+                (req.uri().host() == Some("added.test"))
+                    .then(|| http::Response::new(Body::from("added")))
+            });
+            return Some(http::Response::new(Body::from(
+                inner.requests().len().to_string(),
+            )));
+        }
+        None
+    });
+
+    vm.async_with(async |ctx| {
+        mock.register(&ctx).catch(&ctx)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            setFetchHandler(async (req) => {
+                // The javascript handler can fetch and replace itself
+                const added = await fetch("http://added.test/");
+                setFetchHandler(() => new Response("replaced"));
+                return new Response(`js ${await added.text()}`);
+            });
+
+            const count = await fetch("http://add.test/");
+            const js = await fetch("http://js.test/");
+            const replaced = await fetch("http://js.test/");
+
+            globalThis.result = [await count.text(), await js.text(), await replaced.text()].join(" ");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "1 js added replaced");
+    assert_eq!(mock.requests().len(), 4);
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn replace_client_during_fetch() {
+    use klaver_wintertc::fetch::{Body, Client, FetchSettings, MockClient};
+
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let first = MockClient::new();
+    let second = MockClient::new();
+    second.handle(|_| Some(http::Response::new(Body::from("second"))));
+
+    vm.async_with(async |ctx| {
+        first.register(&ctx).catch(&ctx)?;
+
+        // The client and the settings are replaced while the first client sends a request
+        let client = Client::from_ctx(&ctx).catch(&ctx)?;
+        let second = second.clone();
+        first.handle(move |_| {
+            client.set_settings(FetchSettings::new().deny_host("denied.test"));
+            client.set_local_client(second.clone());
+            Some(http::Response::new(Body::from("first")))
+        });
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const first = await fetch("http://example.test/");
+            const second = await fetch("http://example.test/");
+            const denied = await fetch("http://denied.test/").then(() => "allowed", () => "denied");
+
+            globalThis.result = `${await first.text()} ${await second.text()} ${denied}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "first second denied");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn form_data_round_trip() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const form = new FormData();
+            form.append("text", "hello \"world\"");
+            form.append("blob", new Blob(["blob data"], { type: "text/plain" }), "blob.txt");
+            form.append("file", new File(["file data"], "file.bin", { type: "application/x-test" }));
+            form.append("unnamed", new Blob(["unnamed"]));
+
+            const resp = new Response(form);
+            const contentType = resp.headers.get("content-type");
+            const parsed = await resp.formData();
+
+            const describe = async (value) =>
+                value instanceof File
+                    ? `File(${value.name}, ${value.type}, ${await value.text()}, ${value.size}, ${value instanceof Blob})`
+                    : value;
+
+            const entries = [];
+            for (const [name, value] of parsed) {
+                entries.push(`${name}=${await describe(value)}`);
+            }
+
+            globalThis.result = [
+                contentType.startsWith("multipart/form-data; boundary=----KlaverFormBoundary"),
+                ...entries,
+            ].join("\n");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ret,
+        [
+            "true",
+            r#"text=hello "world""#,
+            "blob=File(blob.txt, text/plain, blob data, 9, true)",
+            "file=File(file.bin, application/x-test, file data, 9, true)",
+            "unnamed=File(blob, application/octet-stream, unnamed, 7, true)",
+        ]
+        .join("\n")
+    );
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn form_data_parse() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const body = [
+                "--boundary",
+                'Content-Disposition: form-data; name="field"',
+                "",
+                "value",
+                "--boundary",
+                'Content-Disposition: form-data; name="typed"',
+                "Content-Type: application/json",
+                "",
+                "{}",
+                "--boundary",
+                'Content-Disposition: form-data; name="upload"; filename="upload.txt"',
+                "Content-Type: text/plain",
+                "",
+                "uploaded",
+                "--boundary--",
+                "",
+            ].join("\r\n");
+
+            const multipart = await new Response(body, {
+                headers: { "content-type": "multipart/form-data; boundary=boundary" },
+            }).formData();
+
+            const upload = multipart.get("upload");
+
+            const urlencoded = await new Response("a=1&b=x+y%21&a=2", {
+                headers: { "content-type": "application/x-www-form-urlencoded" },
+            }).formData();
+
+            const params = await new Response(new URLSearchParams([["q", "a&b=c"]])).formData();
+
+            globalThis.result = [
+                multipart.get("field"),
+                typeof multipart.get("typed"),
+                upload instanceof File,
+                upload.name,
+                upload.type,
+                await upload.text(),
+                urlencoded.getAll("a").join(","),
+                urlencoded.get("b"),
+                params.get("q"),
+            ].join(" ");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ret,
+        "value string true upload.txt text/plain uploaded 1,2 x y! a&b=c"
+    );
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn headers() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const describe = (headers) => [...headers].map(([k, v]) => `${k}=${v}`).join(";");
+
+            const check = (f) => {
+                try {
+                    f();
+                    return "ok";
+                } catch (e) {
+                    return e instanceof TypeError ? "TypeError" : String(e);
+                }
+            };
+
+            const pairs = new Headers([["X-B", " two "], ["x-a", "one"], ["X-B", "three"]]);
+            const record = new Headers({ "Content-Type": "text/plain", Accept: "*/*" });
+            const copy = new Headers(pairs);
+            copy.set("x-a", "changed");
+
+            const cookies = new Headers([["Set-Cookie", "a=1"], ["set-cookie", "b=2"]]);
+
+            const each = [];
+            pairs.forEach((value, name, headers) => each.push(`${name}:${value}:${headers === pairs}`));
+
+            const deleted = new Headers(record);
+            deleted.delete("CONTENT-TYPE");
+
+            globalThis.result = [
+                describe(pairs),
+                describe(record),
+                describe(copy),
+                pairs.get("x-b"),
+                pairs.has("X-A"),
+                each.join(";"),
+                describe(deleted),
+                deleted.has("content-type"),
+                cookies.getSetCookie().join(";"),
+                describe(cookies),
+                check(() => new Headers("x-a")),
+                check(() => new Headers(["x-a"])),
+                check(() => new Headers([["x-a", "1", "2"]])),
+                check(() => new Headers([["bad name", "1"]])),
+                check(() => new Headers({ "x-a": "bad\nvalue" })),
+                check(() => new Headers().append("x-a", "bad\rvalue")),
+                check(() => new Headers().set("bad:name", "1")),
+            ].join("\n");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ret,
+        [
+            "x-a=one;x-b=two, three",
+            "accept=*/*;content-type=text/plain",
+            "x-a=changed;x-b=two, three",
+            "two, three",
+            "true",
+            "x-a:one:true;x-b:two, three:true",
+            "accept=*/*",
+            "false",
+            "a=1;b=2",
+            "set-cookie=a=1;set-cookie=b=2",
+            "TypeError",
+            "TypeError",
+            "TypeError",
+            "TypeError",
+            "TypeError",
+            "TypeError",
+            "TypeError",
+        ]
+        .join("\n")
+    );
+}
@@ -0,0 +1,791 @@
+mod common;
+
+use klaver::Builder;
+use klaver_modules::{
+    loaders::{BytecodeLoader, MemoryModules, UrlLoader},
+    resolvers::ImportMapResolver,
+};
+use klaver_wintertc::CompioBackend;
+
+use common::{Fixture, Script};
+
+#[compio::test]
+async fn import_attributes() {
+    let fixture = Fixture::new();
+    fixture.write("config.json", r#"{ "name": "klaver", "tags": [1, 2] }"#);
+    fixture.write("message.txt", "hello \"world\"\n");
+    fixture.write("data.bin", [0u8, 1, 255]);
+    fixture.write(
+        "main.js",
+        r#"
+        import config from "./config.json" with { type: "json" };
+        import message from "./message.txt" with { type: "text" };
+        import data from "./data.bin" with { type: "bytes" };
+
+        globalThis.result = [
+            config.name,
+            config.tags.length,
+            JSON.stringify(message),
+            data instanceof Uint8Array,
+            data.join(","),
+        ].join(" ");
+        "#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    vm.run_module("./main.js").await.unwrap();
+
+    let ret = vm
+        .async_with(async |ctx| Ok(ctx.globals().get::<_, String>("result")?))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, r#"klaver 2 "hello \"world\"\n" true 0,1,255"#);
+}
+
+#[compio::test]
+async fn import_map() {
+    let fixture = Fixture::new();
+    fixture.write("packages/utils/index.js", "export const name = 'utils';");
+    fixture.write(
+        "packages/legacy/index.js",
+        r#"export { name } from "@app/utils";"#,
+    );
+    fixture.write(
+        "packages/legacy/utils.js",
+        "export const name = 'legacy utils';",
+    );
+    let import_map = fixture.write(
+        "importmap.json",
+        r#"{
+            "imports": {
+                "@app/": "./packages/",
+                "@app/utils": "./packages/utils/index.js"
+            },
+            "scopes": {
+                "./packages/legacy/": { "@app/utils": "./packages/legacy/utils.js" }
+            }
+        }"#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .import_map(ImportMapResolver::from_file(import_map).unwrap())
+        .build()
+        .await
+        .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const utils = await import("@app/utils");
+            const legacy = await import("@app/legacy/index.js");
+            globalThis.result = `${utils.name}, ${legacy.name}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "utils, legacy utils");
+}
+
+#[compio::test]
+async fn reload_modules() {
+    let fixture = Fixture::new();
+
+    let main = fixture.write(
+        "main.js",
+        "import { value } from './config.js';\nexport const get = () => value;",
+    );
+    let config = fixture.write("config.js", "export const value = 'first';");
+    let other = fixture.write("other.js", "export const value = 'other';");
+    let missing = fixture.join("missing.js").display().to_string();
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    let ret: String = vm.call_export(&main, "get", ()).await.unwrap();
+    assert_eq!(ret, "first");
+    vm.run_module(&other).await.unwrap();
+
+    let graph = vm.env().modules().graph();
+    assert_eq!(graph.imports(&main), vec![config.clone()]);
+    assert_eq!(graph.importers(&config), vec![main.clone()]);
+    assert_eq!(
+        graph.dependents(&config),
+        vec![config.clone(), main.clone()]
+    );
+
+    fixture.write("config.js", "export const value = 'second';");
+
+    assert_eq!(vm.reload(&config), vec![config.clone(), main.clone()]);
+    assert!(graph.contains(&other));
+    assert!(vm.reload(&missing).is_empty());
+
+    let ret: String = vm.call_export(&main, "get", ()).await.unwrap();
+    assert_eq!(ret, "second");
+    assert_eq!(graph.imports(&main), vec![config.clone()]);
+}
+
+#[compio::test]
+async fn reload_only_resets_importers() {
+    let fixture = Fixture::new();
+    let a = fixture.write("a.js", "export const get = () => 'a';");
+    let b = fixture.write(
+        "b.js",
+        "export const count = () => (globalThis.count = (globalThis.count ?? 0) + 1);",
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    let other = klaver_vm::Vm::new(vm.env(), Default::default())
+        .await
+        .unwrap();
+
+    let _: String = vm.call_export(&a, "get", ()).await.unwrap();
+    let count: i32 = other.call_export(&b, "count", ()).await.unwrap();
+    assert_eq!(count, 1);
+
+    fixture.write("a.js", "export const get = () => 'changed';");
+    assert_eq!(vm.reload(&a), vec![a.clone()]);
+
+    // The vm which loaded the module gets a fresh context with the new code
+    let ret: String = vm.call_export(&a, "get", ()).await.unwrap();
+    assert_eq!(ret, "changed");
+
+    // The other vm never loaded it, and keeps its state
+    let count: i32 = other.call_export(&b, "count", ()).await.unwrap();
+    assert_eq!(count, 2);
+}
+
+#[compio::test]
+async fn reload_resets_before_every_call() {
+    let fixture = Fixture::new();
+    let a = fixture.write("a.js", "export const get = () => 'a';");
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    let ret: String = vm.call_export(&a, "get", ()).await.unwrap();
+    assert_eq!(ret, "a");
+
+    // Another context on the runtime of the vm, which never loaded the module
+    let context = vm.create_context().await.unwrap();
+
+    vm.with(|ctx| Ok(ctx.globals().set("marker", true)?))
+        .await
+        .unwrap();
+    context
+        .with(|ctx| Ok(ctx.globals().set("marker", true)?))
+        .await
+        .unwrap();
+
+    fixture.write("a.js", "export const get = () => 'changed';");
+    assert_eq!(vm.reload(&a), vec![a.clone()]);
+
+    // `with` runs in a fresh context too, not only `run_module` and `call_export`
+    let marker = vm
+        .with(|ctx| Ok(ctx.globals().get::<_, Option<bool>>("marker")?))
+        .await
+        .unwrap();
+    assert_eq!(marker, None);
+
+    let marker = context
+        .with(|ctx| Ok(ctx.globals().get::<_, Option<bool>>("marker")?))
+        .await
+        .unwrap();
+    assert_eq!(marker, Some(true));
+
+    let ret: String = vm.call_export(&a, "get", ()).await.unwrap();
+    assert_eq!(ret, "changed");
+}
+
+#[compio::test]
+async fn resolution_errors() {
+    let fixture = Fixture::new();
+    let lib = fixture.mkdir("lib");
+    let main = fixture.write("main.js", "import './missing.js';");
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .search_path(lib)
+        .build()
+        .await
+        .unwrap();
+
+    let err = vm.run_module(&main).await.unwrap_err();
+    let klaver_vm::RuntimeError::Resolution(err) = err else {
+        panic!("Expected a resolution error, got: {err}");
+    };
+
+    assert_eq!(err.specifier, "./missing.js");
+    assert_eq!(err.base, main);
+
+    let files = err
+        .attempts
+        .iter()
+        .filter(|attempt| attempt.resolver.starts_with("FileResolver"))
+        .collect::<Vec<_>>();
+
+    assert_eq!(files.len(), 2);
+    assert!(
+        files[0]
+            .candidates
+            .contains(&fixture.join("missing.js").display().to_string())
+    );
+    assert!(err.to_string().contains("tried"));
+}
+
+#[compio::test]
+async fn resolution_errors_per_context() {
+    let fixture = Fixture::new();
+    let first = fixture.write("first.js", "import './missing-first.js';");
+    let second = fixture.write("second.js", "import './missing-second.js';");
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    // Another vm sharing the environment, and with it the module loader
+    let other = klaver_vm::Vm::new(vm.env(), Default::default())
+        .await
+        .unwrap();
+
+    let (err, other_err) = futures::join!(vm.run_module(&first), other.run_module(&second));
+
+    for (err, specifier) in [
+        (err.unwrap_err(), "./missing-first.js"),
+        (other_err.unwrap_err(), "./missing-second.js"),
+    ] {
+        let klaver_vm::RuntimeError::Resolution(err) = err else {
+            panic!("Expected a resolution error, got: {err}");
+        };
+        assert_eq!(err.specifier, specifier);
+    }
+}
+
+#[compio::test]
+async fn resolution_errors_per_context_on_runtime() {
+    let fixture = Fixture::new();
+    let first = fixture.write("first.js", "import './missing-first.js';");
+    let second = fixture.write("second.js", "import './missing-second.js';");
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    // A second context on the runtime of the vm
+    let context = vm.create_context().await.unwrap();
+
+    for (err, specifier) in [
+        (
+            vm.run_module(&first).await.unwrap_err(),
+            "./missing-first.js",
+        ),
+        (
+            context.run_module(&second).await.unwrap_err(),
+            "./missing-second.js",
+        ),
+    ] {
+        let klaver_vm::RuntimeError::Resolution(err) = err else {
+            panic!("Expected a resolution error, got: {err}");
+        };
+        assert_eq!(err.specifier, specifier);
+        assert!(!err.attempts.is_empty());
+    }
+}
+
+#[compio::test]
+async fn commonjs_modules() {
+    let fixture = Fixture::new();
+
+    fixture.write(
+        "node_modules/legacy/package.json",
+        r#"{ "name": "legacy", "main": "index.js" }"#,
+    );
+    fixture.write(
+        "node_modules/legacy/index.js",
+        r#"
+        const util = require("./util");
+        exports.greet = (name) => `${util.prefix} ${name}`;
+        exports.file = __filename;
+        "#,
+    );
+    fixture.write(
+        "node_modules/legacy/util.js",
+        r#"module.exports = { prefix: "hello" };"#,
+    );
+
+    // Cycles see the exports as they are so far
+    fixture.write(
+        "a.cjs",
+        r#"
+        exports.done = false;
+        const b = require("./b.cjs");
+        exports.fromB = b.sawA;
+        exports.done = true;
+        "#,
+    );
+    fixture.write("b.cjs", r#"exports.sawA = require("./a.cjs").done;"#);
+
+    fixture.write("esm.js", "export const value = 21;");
+    fixture.write(
+        "c.cjs",
+        r#"module.exports = require("./esm.js").value * 2;"#,
+    );
+
+    let main = fixture.write(
+        "main.js",
+        r#"
+        import legacy, { greet } from "legacy";
+        import a from "./a.cjs";
+        import c from "./c.cjs";
+        export const run = () =>
+            `${greet("world")}, ${legacy.file.endsWith("index.js")}, ${a.fromB}, ${a.done}, ${c}`;
+        "#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    let ret: String = vm.call_export(&main, "run", ()).await.unwrap();
+    assert_eq!(ret, "hello world, true, false, true, 42");
+}
+
+#[compio::test]
+async fn memory_modules() {
+    let modules = MemoryModules::new();
+    modules
+        .insert(
+            "app/util.js",
+            "export const greet = (name) => `hello ${name}`;",
+        )
+        .insert(
+            "app/main.js",
+            r#"
+            import { greet } from "./util.js";
+            globalThis.result = greet("world");
+            "#,
+        );
+
+    let vm = Builder::new(CompioBackend)
+        .memory_modules(modules.clone())
+        .build()
+        .await
+        .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            await import("app/main.js");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "hello world");
+
+    assert!(modules.remove("app/util.js"));
+    assert!(!modules.contains("app/util.js"));
+}
+
+#[compio::test]
+async fn url_modules() {
+    use klaver_modules::loaders::LockFile;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let fixture = Fixture::new();
+    let lock_file = fixture.join("klaver.lock");
+
+    let fetches = Arc::new(AtomicUsize::new(0));
+
+    let loader = |offline: bool| {
+        let fetches = fetches.clone();
+        UrlLoader::new(fixture.join("cache"))
+            .lock_file(LockFile::open(&lock_file).unwrap())
+            .offline(offline)
+            .fetcher(move |url: &str| {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                match url {
+                    "https://example.com/pkg/index.js" => Ok(
+                        r#"export { add } from "./add.js"; export * from "/lib/mul.js";"#.into(),
+                    ),
+                    "https://example.com/pkg/add.js" => {
+                        Ok("export const add = (a, b) => a + b;".into())
+                    }
+                    "https://example.com/lib/mul.js" => {
+                        Ok("export const mul = (a, b) => a * b;".into())
+                    }
+                    _ => Err("Not found".into()),
+                }
+            })
+    };
+
+    let script = r#"
+        const { add, mul } = await import("https://example.com/pkg/index.js");
+        globalThis.result = String(mul(add(1, 2), 3));
+    "#;
+
+    for offline in [false, true] {
+        let vm = Builder::new(CompioBackend)
+            .url_modules(loader(offline))
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(vm.run(Script(script)).await.unwrap(), "9");
+    }
+
+    // The second run is served from the cache
+    assert_eq!(fetches.load(Ordering::SeqCst), 3);
+
+    let lock = std::fs::read_to_string(&lock_file).unwrap();
+    assert_eq!(lock.lines().count(), 3);
+
+    // Modules that don't match the lock file are rejected
+    std::fs::write(&lock_file, lock.replace("sha256-", "sha256-0")).unwrap();
+
+    let vm = Builder::new(CompioBackend)
+        .url_modules(loader(true))
+        .build()
+        .await
+        .unwrap();
+
+    assert!(vm.run(Script(script)).await.is_err());
+}
+
+#[compio::test]
+async fn url_modules_redirect() {
+    use klaver_core::error::BoxError;
+    use klaver_modules::loaders::{FetchedModule, Fetcher};
+
+    struct Redirecting;
+
+    impl Fetcher for Redirecting {
+        fn fetch(&self, url: &str) -> Result<FetchedModule, BoxError> {
+            match url {
+                "https://example.com/pkg" => Ok(FetchedModule {
+                    url: "https://example.com/pkg@1.0.0/index.js".into(),
+                    content: r#"export { version } from "./version.js";"#.into(),
+                }),
+                "https://example.com/pkg@1.0.0/version.js" => Ok(FetchedModule {
+                    url: url.into(),
+                    content: r#"export const version = "1.0.0";"#.into(),
+                }),
+                _ => Err("Not found".into()),
+            }
+        }
+    }
+
+    let fixture = Fixture::new();
+
+    let loader = |offline: bool| {
+        UrlLoader::new(fixture.join("cache"))
+            .offline(offline)
+            .fetcher(Redirecting)
+    };
+
+    let script = r#"
+        const a = await import("https://example.com/pkg");
+        const b = await import("https://example.com/pkg");
+        globalThis.result = `${a.version} ${a === b}`;
+    "#;
+
+    // The offline run resolves against the redirected url from the cache
+    for offline in [false, true] {
+        let vm = Builder::new(CompioBackend)
+            .url_modules(loader(offline))
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(vm.run(Script(script)).await.unwrap(), "1.0.0 true");
+    }
+}
+
+#[compio::test]
+async fn url_modules_prefetch() {
+    use klaver_modules::loaders::FetchedModule;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let fixture = Fixture::new();
+    fixture.write(
+        "main.js",
+        r#"
+        import { add } from "https://example.com/add.js";
+        globalThis.result = String(add(1, 2));
+        "#,
+    );
+
+    let sources = |url: &str| -> Result<Vec<u8>, klaver_core::error::BoxError> {
+        match url {
+            "https://example.com/add.js" => Ok(r#"export { add } from "./math.js";"#.into()),
+            "https://example.com/math.js" => Ok("export const add = (a, b) => a + b;".into()),
+            _ => Err("Not found".into()),
+        }
+    };
+
+    let blocking = Arc::new(AtomicUsize::new(0));
+
+    let loader = UrlLoader::new(fixture.join("cache")).fetcher({
+        let blocking = blocking.clone();
+        move |url: &str| {
+            blocking.fetch_add(1, Ordering::SeqCst);
+            sources(url)
+        }
+    });
+    let prefetcher = loader.prefetcher();
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .url_modules(loader)
+        .build()
+        .await
+        .unwrap();
+
+    let fetched = prefetcher
+        .prefetch(vm.env(), "./main.js", |url: String| async move {
+            Ok(FetchedModule {
+                content: sources(&url)?,
+                url,
+            })
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(fetched, 2);
+
+    vm.run_module("./main.js").await.unwrap();
+
+    let ret = vm
+        .async_with(async |ctx| Ok(ctx.globals().get::<_, String>("result")?))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "3");
+
+    // Everything was downloaded before the module ran
+    assert_eq!(blocking.load(Ordering::SeqCst), 0);
+}
+
+#[compio::test]
+async fn bytecode_bundle() {
+    use klaver_modules::loaders::Bundle;
+
+    let fixture = Fixture::new();
+    fixture.write("util.js", "export const add = (a, b) => a + b;");
+    fixture.write(
+        "main.js",
+        r#"
+        import { add } from "./util.js";
+        globalThis.result = add(1, 2);
+        "#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    let bundle = Bundle::compile(vm.env(), "./main.js").await.unwrap();
+    assert_eq!(bundle.modules().count(), 2);
+
+    let bytes = bundle.to_bytes();
+    let bundle = unsafe { Bundle::from_bytes(&bytes) }.unwrap();
+
+    // Corrupted bundles are rejected by the checksum
+    let mut corrupted = bytes.clone();
+    *corrupted.last_mut().unwrap() ^= 0xff;
+    assert!(unsafe { Bundle::from_bytes(&corrupted) }.is_err());
+
+    // Round trip through an executable, with a stand-in for the runtime binary
+    let runtime = fixture.write("runtime", b"#!/bin/false\n");
+    assert!(
+        unsafe { Bundle::from_executable(&runtime) }
+            .unwrap()
+            .is_none()
+    );
+
+    let app = fixture.join("app");
+    bundle.write_executable(&runtime, &app).unwrap();
+    let bundle = unsafe { Bundle::from_executable(&app) }.unwrap().unwrap();
+    assert_eq!(bundle.entry(), "./main.js");
+
+    let vm = Builder::new(CompioBackend)
+        .bundle(BytecodeLoader::new(bundle))
+        .build()
+        .await
+        .unwrap();
+
+    vm.run_module("./main.js").await.unwrap();
+
+    let ret = vm
+        .async_with(async |ctx| Ok(ctx.globals().get::<_, i32>("result")?))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, 3);
+}
+
+#[compio::test]
+async fn bytecode_bundle_rejects_other_modules() {
+    use klaver_modules::loaders::Bundle;
+
+    let modules = MemoryModules::new();
+    modules.insert("app/util.js", "export const value = 1;");
+
+    let fixture = Fixture::new();
+    fixture.write("main.js", r#"import { value } from "app/util.js";"#);
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .memory_modules(modules)
+        .build()
+        .await
+        .unwrap();
+
+    // Memory modules would be missing when the bundle runs
+    assert!(Bundle::compile(vm.env(), "./main.js").await.is_err());
+}
+
+#[cfg(feature = "swc")]
+#[compio::test]
+async fn compile_cache() {
+    let fixture = Fixture::new();
+    let cache = fixture.join("cache");
+    fixture.write(
+        "math.ts",
+        r#"
+        export function add(a: number, b: number): number {
+            return a + b;
+        }
+        "#,
+    );
+
+    for _ in 0..2 {
+        let vm = Builder::new(CompioBackend)
+            .search_path(fixture.path())
+            .cache_dir(&cache)
+            .build()
+            .await
+            .unwrap();
+
+        let ret = vm
+            .run(Script(
+                r#"
+                const { add } = await import("./math.ts");
+                globalThis.result = String(add(1, 2));
+                "#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(ret, "3");
+    }
+
+    let entries = std::fs::read_dir(&cache)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "js"))
+        .count();
+
+    assert_eq!(entries, 1);
+}
+
+#[cfg(feature = "swc")]
+#[compio::test]
+async fn compile_cache_in_memory() {
+    let fixture = Fixture::new();
+    let math = fixture.write(
+        "math.ts",
+        "export const add = (a: number, b: number): number => a + b;",
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .cache(true)
+        .build()
+        .await
+        .unwrap();
+
+    let cache = vm.env().cache().expect("cache");
+    assert!(cache.dir().is_none());
+
+    let ret: i32 = vm.call_export(&math, "add", (1, 2)).await.unwrap();
+    assert_eq!(ret, 3);
+
+    // A changed module is compiled again, instead of served from the cache
+    fixture.write(
+        "math.ts",
+        "export const add = (a: number, b: number): number => a + b + 1;",
+    );
+    vm.reload(&math);
+
+    let ret: i32 = vm.call_export(&math, "add", (1, 2)).await.unwrap();
+    assert_eq!(ret, 4);
+}
+
+#[compio::test]
+async fn bytes_import_large() {
+    use klaver_modules::loaders::Bundle;
+
+    let fixture = Fixture::new();
+    let data = (0..4 * 1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+    fixture.write("data.bin", &data);
+    fixture.write(
+        "main.js",
+        r#"
+        import data from "./data.bin" with { type: "bytes" };
+        import again from "./data.bin" with { type: "bytes" };
+
+        globalThis.result = [data.length, data[1025], data === again].join(" ");
+        "#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    vm.run_module("./main.js").await.unwrap();
+
+    let ret = vm
+        .async_with(async |ctx| Ok(ctx.globals().get::<_, String>("result")?))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "4194304 1 true");
+
+    // The array is created natively, so there is no bytecode to put in a bundle
+    assert!(Bundle::compile(vm.env(), "./main.js").await.is_err());
+}
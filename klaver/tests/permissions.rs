@@ -0,0 +1,213 @@
+mod common;
+
+use klaver::Builder;
+use klaver_wintertc::{CompioBackend, Permissions};
+
+use common::{Fixture, Script};
+
+#[compio::test]
+async fn permissions() {
+    let fixture = Fixture::new();
+    fixture.write("data/file.txt", "hello");
+
+    let vm = Builder::new(CompioBackend)
+        .permissions(Permissions::none().allow_read(fixture.join("data")))
+        .build()
+        .await
+        .unwrap();
+
+    let path = fixture.path().to_string_lossy().to_string();
+    vm.async_with(async move |ctx| Ok(ctx.globals().set("dir", path)?))
+        .await
+        .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const check = async (f) => {
+                try {
+                    await f();
+                    return "allowed";
+                } catch (e) {
+                    return e instanceof DOMException ? e.name : String(e);
+                }
+            };
+
+            const data = await open(`${dir}/data`);
+            await data.root.resolve("file.txt").open({ read: true });
+
+            globalThis.result = [
+                await check(() => setTimeout(() => {}, 0)),
+                await check(() => open(dir)),
+                await check(() => data.root.resolve("file.txt").open({ write: true })),
+                await check(() => fetch("http://example.com/")),
+            ].join(",");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ret,
+        "PermissionDenied,PermissionDenied,PermissionDenied,PermissionDenied"
+    );
+}
+
+#[compio::test]
+async fn permissions_per_write() {
+    let fixture = Fixture::new();
+    fixture.write("data/file.txt", "hello");
+    fixture.mkdir("data/out");
+
+    let vm = Builder::new(CompioBackend)
+        .permissions(
+            Permissions::none()
+                .allow_read(fixture.join("data"))
+                .allow_write(fixture.join("data/out")),
+        )
+        .build()
+        .await
+        .unwrap();
+
+    let path = fixture.join("data").display().to_string();
+    vm.async_with(async move |ctx| Ok(ctx.globals().set("dir", path)?))
+        .await
+        .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const check = async (f) => {
+                try {
+                    await f();
+                    return "allowed";
+                } catch (e) {
+                    return e instanceof DOMException ? e.name : String(e);
+                }
+            };
+
+            // The root is read only, but writes below it are checked against their full path
+            const data = await open(dir);
+
+            globalThis.result = [
+                await check(() => data.root.resolve("out/new.txt").open({ write: true, create: true })),
+                await check(() => data.root.resolve("file.txt").open({ write: true })),
+                await check(() => data.root.resolve("file.txt").open({ read: true })),
+            ].join(",");
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "allowed,PermissionDenied,allowed");
+    assert!(fixture.join("data/out/new.txt").exists());
+}
+
+#[compio::test]
+async fn permissions_after_reload() {
+    let fixture = Fixture::new();
+    fixture.write("data/file.txt", "hello");
+
+    let source = r#"
+    export const check = async (path) => {
+        try {
+            await open(path);
+            return "allowed";
+        } catch (e) {
+            return e instanceof DOMException ? e.name : String(e);
+        }
+    };
+    "#;
+    let main = fixture.write("main.js", source);
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .permissions(Permissions::none().allow_read(fixture.join("data")))
+        .build()
+        .await
+        .unwrap();
+
+    let data = fixture.join("data").display().to_string();
+    let root = fixture.path().display().to_string();
+
+    for _ in 0..2 {
+        let allowed: String = vm
+            .call_export(&main, "check", (data.clone(),))
+            .await
+            .unwrap();
+        let denied: String = vm
+            .call_export(&main, "check", (root.clone(),))
+            .await
+            .unwrap();
+
+        assert_eq!(allowed, "allowed");
+        assert_eq!(denied, "PermissionDenied");
+
+        // The next call runs in a fresh context, which must still have the permissions and backend
+        fixture.write("main.js", format!("{source}\n// changed"));
+        assert_eq!(vm.reload(&main), vec![main.clone()]);
+    }
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn permissions_on_redirect() {
+    use klaver_wintertc::fetch::{Body, MockClient};
+    use rquickjs::CatchResultExt;
+
+    let vm = Builder::new(CompioBackend)
+        .permissions(Permissions::none().allow_net("allowed.test"))
+        .build()
+        .await
+        .unwrap();
+
+    let mock = MockClient::new();
+    mock.handle(|req| {
+        let location = match req.uri().path() {
+            "/denied" => "http://denied.test/",
+            "/local" => "/done",
+            "/done" => return Some(http::Response::new(Body::from("done"))),
+            _ => return None,
+        };
+
+        Some(
+            http::Response::builder()
+                .status(302)
+                .header("location", location)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    });
+
+    vm.async_with(async |ctx| {
+        mock.register(&ctx).catch(&ctx)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const local = await fetch("http://allowed.test/local");
+            const denied = await fetch("http://allowed.test/denied").then(
+                () => "allowed",
+                (e) => e instanceof DOMException ? e.name : String(e),
+            );
+
+            globalThis.result = `${await local.text()} ${denied}`;
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "done PermissionDenied");
+
+    // The denied host is never contacted
+    let requests = mock.requests();
+    assert!(
+        requests
+            .iter()
+            .all(|req| req.uri().host() == Some("allowed.test"))
+    );
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use klaver::Builder;
+use klaver_wintertc::CompioBackend;
+
+#[cfg(feature = "fetch")]
+use common::Fixture;
+use common::Script;
+
+#[compio::test]
+async fn serve_localhost() {
+    let vm = Builder::new(CompioBackend).build().await.unwrap();
+
+    let ret = vm
+        .run(Script(
+            r#"
+            const controller = new AbortController();
+
+            const server = Klaver.serve(
+                { port: 0, hostname: "127.0.0.1", signal: controller.signal },
+                async (req) => {
+                    const body = await req.text();
+                    return new Response(`${req.method} ${new URL(req.url).pathname} ${body}`, {
+                        status: 201,
+                    });
+                },
+            );
+
+            const resp = await fetch(`${server.url}echo`, { method: "POST", body: "Hello" });
+
+            globalThis.result = `${resp.status} ${await resp.text()}`;
+
+            controller.abort();
+            "#,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "201 POST /echo Hello");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn handle_request() {
+    use http_body_util::BodyExt;
+    use klaver_wintertc::fetch::Body;
+
+    let fixture = Fixture::new();
+    fixture.write(
+        "handler.js",
+        r#"
+        export default {
+            async fetch(req) {
+                return new Response(`${req.method} ${await req.text()}`, {
+                    headers: { "x-handler": "default" },
+                });
+            },
+        };
+        "#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    let req = http::Request::builder()
+        .method("POST")
+        .uri("http://localhost/")
+        .body(Body::from("Hello"))
+        .unwrap();
+
+    let resp = vm.handle_request("./handler.js", req).await.unwrap();
+
+    assert_eq!(resp.headers()["x-handler"], "default");
+
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, "POST Hello");
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn handle_request_after_reload() {
+    use http_body_util::BodyExt;
+    use klaver_wintertc::fetch::Body;
+
+    let fixture = Fixture::new();
+    let handler = fixture.write(
+        "handler.js",
+        "export default { fetch: () => new Response('first') };",
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    for expected in ["first", "second"] {
+        let req = http::Request::builder()
+            .uri("http://localhost/")
+            .body(Body::empty())
+            .unwrap();
+
+        let resp = vm.handle_request(&handler, req).await.unwrap();
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, expected);
+
+        fixture.write(
+            "handler.js",
+            "export default { fetch: () => new Response('second') };",
+        );
+        vm.reload(&handler);
+    }
+}
+
+#[cfg(feature = "fetch")]
+#[compio::test]
+async fn router_fetch_export() {
+    use http_body_util::BodyExt;
+    use klaver_wintertc::fetch::Body;
+
+    let fixture = Fixture::new();
+    fixture.write(
+        "router.js",
+        r#"
+        import { Router } from "@klaver/router";
+
+        const router = new Router();
+
+        router.use(async (req, next) => {
+            const resp = await next();
+            resp.headers.set("x-middleware", "true");
+            return resp;
+        });
+
+        router.get("/users/:id", (req, { params }) => new Response(`user ${params.id}`));
+        router.post("/files/*path", (req, { params }) => new Response(`file ${params.path}`));
+        router.get("/files/readme", () => new Response("readme"));
+
+        export default router;
+        "#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .module::<klaver_router::Module>()
+        .build()
+        .await
+        .unwrap();
+
+    let cases = [
+        ("GET", "/users/42", 200, "user 42"),
+        ("POST", "/files/a/b%20c.txt", 200, "file a/b c.txt"),
+        ("GET", "/files/readme", 200, "readme"),
+        // The static route only takes GET, so the wildcard route handles the POST
+        ("POST", "/files/readme", 200, "file readme"),
+        ("POST", "/users/42", 405, ""),
+        ("GET", "/posts", 404, ""),
+    ];
+
+    for (method, path, status, expected) in cases {
+        let req = http::Request::builder()
+            .method(method)
+            .uri(format!("http://localhost{path}"))
+            .body(Body::empty())
+            .unwrap();
+
+        let resp = vm.handle_request("./router.js", req).await.unwrap();
+
+        assert_eq!(resp.status(), status);
+        assert_eq!(resp.headers()["x-middleware"], "true");
+        if status == 405 {
+            assert_eq!(resp.headers()["allow"], "GET");
+        }
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, expected);
+    }
+}
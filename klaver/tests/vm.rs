@@ -0,0 +1,129 @@
+mod common;
+
+use std::time::Duration;
+
+use klaver::Builder;
+use klaver_wintertc::CompioBackend;
+
+use common::{Fixture, Script};
+
+#[compio::test]
+async fn time_limits() {
+    let vm = Builder::new(CompioBackend)
+        .time_limit(Duration::from_millis(100))
+        .build()
+        .await
+        .unwrap();
+
+    let err = vm.run(Script("while (true) {}")).await.unwrap_err();
+    assert!(matches!(err, klaver_vm::RuntimeError::Timeout(_)));
+
+    // A try block can not swallow the interrupt
+    let err = vm
+        .run(Script(
+            "try { while (true) {} } catch { globalThis.result = 'caught'; }",
+        ))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, klaver_vm::RuntimeError::Timeout(_)));
+
+    let handle = vm.interrupt_handle();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+
+    let err = vm
+        .run_with_limit(Script("while (true) {}"), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, klaver_vm::RuntimeError::Interrupted));
+
+    let ret = vm
+        .run(Script("globalThis.result = 'still running';"))
+        .await
+        .unwrap();
+    assert_eq!(ret, "still running");
+
+    // An interrupt while nothing runs is not kept for the next call
+    vm.interrupt_handle().interrupt();
+
+    let ret = vm
+        .run(Script("globalThis.result = 'not interrupted';"))
+        .await
+        .unwrap();
+    assert_eq!(ret, "not interrupted");
+
+    // Awaiting runs no javascript, so the interrupt handler never sees the deadline
+    let err = vm
+        .run(Script(
+            "await new Promise((resolve) => setTimeout(resolve, 1e9));",
+        ))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, klaver_vm::RuntimeError::Timeout(_)));
+
+    // Neither does waiting for the timers a call left behind
+    let err = vm
+        .run(Script("setTimeout(() => {}, 1e9);"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, klaver_vm::RuntimeError::Timeout(_)));
+
+    let ret = vm
+        .run(Script("globalThis.result = 'after timeout';"))
+        .await
+        .unwrap();
+    assert_eq!(ret, "after timeout");
+}
+
+#[compio::test]
+async fn snapshots() {
+    let fixture = Fixture::new();
+    fixture.write("util.js", "export const value = 'first';");
+    fixture.write(
+        "main.js",
+        r#"
+        import { value } from "./util.js";
+        globalThis.result = `${globalThis.prefix}:${value}`;
+        "#,
+    );
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .build()
+        .await
+        .unwrap();
+
+    let snapshot = klaver_vm::Snapshot::capture(vm.env(), &["./main.js"])
+        .await
+        .unwrap()
+        .with_script("globalThis.prefix = 'snapshot';");
+    assert_eq!(snapshot.modules().count(), 2);
+
+    // The snapshot holds the compiled modules, so changes on disk are not seen
+    fixture.write("util.js", "export const value = 'second';");
+
+    let vm = Builder::new(CompioBackend)
+        .search_path(fixture.path())
+        .snapshot(snapshot)
+        .build()
+        .await
+        .unwrap();
+
+    let ret = vm
+        .async_with(async |ctx| Ok(ctx.globals().get::<_, String>("result")?))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "snapshot:first");
+
+    // Fresh contexts are restored from the snapshot too
+    let context = vm.create_context().await.unwrap();
+    let ret = context
+        .async_with(async |ctx| Ok(ctx.globals().get::<_, String>("result")?))
+        .await
+        .unwrap();
+
+    assert_eq!(ret, "snapshot:first");
+}